@@ -1,32 +1,197 @@
 use std::io;
 use std::fmt;
 use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+use crate::jurisdiction::Jurisdiction;
+use crate::prices::PriceTable;
+use crate::rebalance;
+use crate::transaction::{Transaction, TransactionKind};
 use crate::utils::parse_value;
 
-#[derive(Clone)]
+/// How far from 100 an allocation's percentages may add up to and still count as complete
+pub const ALLOCATION_TOLERANCE: f64 = 1e-9;
+
+#[derive(Clone, PartialEq)]
 pub enum AccountType {
+    /// Traditional IRA
     Traditional,
     Taxable,
+    /// Roth IRA
     Roth,
     Allocation,
+    /// Employer plan with pre-tax deferrals, such as a 401(k) or 403(b)
+    Plan401k,
+    /// Roth deferrals to an employer plan
+    Roth401k,
+    Hsa,
+    Education529,
+    Sep,
+    Simple,
+    InheritedIra,
+    Deferred457,
+    Isa,
+    Sipp,
+    Tfsa,
+    Rrsp,
+}
+
+/// When money can come out of an account without a penalty
+#[derive(Clone, Copy, PartialEq)]
+pub enum Access {
+    Anytime,
+    /// Withdrawals before this age are penalized
+    FromAge(f64),
+    /// Withdrawals are only tax and penalty free when spent on these expenses
+    QualifiedExpenses(&'static str),
+}
+
+/// How an account's contributions, growth and withdrawals are taxed
+#[derive(Clone, Copy, PartialEq)]
+pub struct TaxTreatment {
+    /// Contributions are deducted from taxable income
+    pub pre_tax_contributions: bool,
+    /// Interest, dividends and realized gains are taxed every year
+    pub taxable_growth: bool,
+    /// Qualified withdrawals are taxed as ordinary income
+    pub taxed_withdrawals: bool,
+    pub accepts_contributions: bool,
+    pub required_distributions: bool,
+    pub access: Access,
+}
+
+impl TaxTreatment {
+    /// Names the group of accounts taxed the same way, for reports
+    pub fn bucket(&self) -> &'static str {
+        if self.taxable_growth {
+            "Taxable"
+        } else if self.taxed_withdrawals {
+            "Tax-deferred"
+        } else {
+            "Tax-free"
+        }
+    }
+}
+
+impl AccountType {
+    /// Returns a short name for the account type that `parse` reads back, apart from allocations,
+    /// for saving to files
+    pub fn key(&self) -> &'static str {
+        match self {
+            AccountType::Traditional => "traditional",
+            AccountType::Taxable => "taxable",
+            AccountType::Roth => "roth",
+            AccountType::Allocation => "allocation",
+            AccountType::Plan401k => "401k",
+            AccountType::Roth401k => "roth401k",
+            AccountType::Hsa => "hsa",
+            AccountType::Education529 => "529",
+            AccountType::Sep => "sep",
+            AccountType::Simple => "simple",
+            AccountType::InheritedIra => "inherited",
+            AccountType::Deferred457 => "457",
+            AccountType::Isa => "isa",
+            AccountType::Sipp => "sipp",
+            AccountType::Tfsa => "tfsa",
+            AccountType::Rrsp => "rrsp",
+        }
+    }
+
+    /// Reads an account type from a name, ignoring case, spaces and underscores
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert!(AccountType::parse("401k").unwrap() == AccountType::Plan401k);
+    /// ```
+    pub fn parse(name: &str) -> Result<AccountType, String> {
+        let key: String = name.chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+        match key.as_str() {
+            "traditional" | "ira" | "traditionalira" => Ok(AccountType::Traditional),
+            "roth" | "rothira" => Ok(AccountType::Roth),
+            "401k" | "401(k)" | "403b" | "403(b)" => Ok(AccountType::Plan401k),
+            "roth401k" | "roth401(k)" | "roth403b" | "roth403(b)" => Ok(AccountType::Roth401k),
+            "taxable" | "brokerage" => Ok(AccountType::Taxable),
+            "hsa" => Ok(AccountType::Hsa),
+            "529" | "education" | "education529" => Ok(AccountType::Education529),
+            "sep" | "sepira" => Ok(AccountType::Sep),
+            "simple" | "simpleira" => Ok(AccountType::Simple),
+            "inherited" | "inheritedira" => Ok(AccountType::InheritedIra),
+            "457" | "457b" | "457(b)" => Ok(AccountType::Deferred457),
+            "isa" | "stocksandsharesisa" => Ok(AccountType::Isa),
+            "sipp" => Ok(AccountType::Sipp),
+            "tfsa" => Ok(AccountType::Tfsa),
+            "rrsp" => Ok(AccountType::Rrsp),
+            _ => Err(format!("Unknown account type {}", name.trim())),
+        }
+    }
+}
+
+impl fmt::Display for TaxTreatment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut disp = format!("{}, {} contributions", self.bucket(),
+                               if self.pre_tax_contributions { "pre-tax" } else { "after-tax" });
+        if !self.accepts_contributions {
+            disp = format!("{}, no new contributions", self.bucket());
+        }
+        match self.access {
+            Access::Anytime => {}
+            Access::FromAge(age) => disp.push_str(&format!(", withdrawals from age {}", age)),
+            Access::QualifiedExpenses(expenses) => disp.push_str(&format!(", withdrawals for {}", expenses)),
+        }
+        if self.required_distributions {
+            disp.push_str(", required distributions");
+        }
+        disp.fmt(f)
+    }
 }
 
 impl fmt::Display for AccountType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
-            AccountType::Traditional => "IRA / 401(k)".fmt(f),
-            AccountType::Roth => "Roth IRA / Roth 401(k)".fmt(f),
+            AccountType::Traditional => "Traditional IRA".fmt(f),
+            AccountType::Roth => "Roth IRA".fmt(f),
+            AccountType::Plan401k => "401(k)".fmt(f),
+            AccountType::Roth401k => "Roth 401(k)".fmt(f),
             AccountType::Taxable => "Brokerage Account".fmt(f),
             AccountType::Allocation => "Allocation".fmt(f),
+            AccountType::Hsa => "HSA".fmt(f),
+            AccountType::Education529 => "529 Plan".fmt(f),
+            AccountType::Sep => "SEP IRA".fmt(f),
+            AccountType::Simple => "SIMPLE IRA".fmt(f),
+            AccountType::InheritedIra => "Inherited IRA".fmt(f),
+            AccountType::Deferred457 => "457(b)".fmt(f),
+            AccountType::Isa => "ISA".fmt(f),
+            AccountType::Sipp => "SIPP".fmt(f),
+            AccountType::Tfsa => "TFSA".fmt(f),
+            AccountType::Rrsp => "RRSP".fmt(f),
         }
     }
 }
 
+/// Shares of a security held in an account and the money paid for them
+pub struct Position {
+    pub ticker: String,
+    pub class: AssetClass,
+    pub shares: f64,
+    pub cost: f64,
+}
+
 pub struct Account {
+    /// What the user calls the account. Names are unique among a user's accounts, so saved
+    /// snapshots can be matched to the account in later sessions.
+    pub name: String,
     pub classification: AccountType, 
-    pub assets: Vec<Asset>
+    pub assets: Vec<Asset>,
+    ledger: Vec<Transaction>,
+    /// Money put into each asset class through the ledger, less the share of it taken out again
+    basis: Vec<Asset>,
 }
 
+/// Shows an allocation or a set of holdings. Accounts are shown with `describe`, which names and
+/// treats them as the jurisdiction does.
 impl fmt::Display for Account {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut units = '$';
@@ -34,8 +199,11 @@ impl fmt::Display for Account {
             units = '%';
         }
 
-        let mut disp = "Account Classification: ".to_string();
-        disp.push_str(&format!("{}\n", self.classification));
+        let mut disp = String::new();
+        if !self.name.is_empty() {
+            disp.push_str(&format!("Account Name: {}\n", self.name));
+        }
+        disp.push_str(&format!("Account Classification: {}\n", self.classification));
         for i in &self.assets {
             disp.push_str(&format!("{} {}\n", i, units));
         }
@@ -45,36 +213,147 @@ impl fmt::Display for Account {
 
 impl PartialEq for Account {
     fn eq(&self, other: &Account) -> bool {
-        match (&self.classification, &other.classification) {
-            (AccountType::Taxable, AccountType::Taxable) => true,
-            (AccountType::Traditional, AccountType::Traditional) => true,
-            (AccountType::Roth, AccountType::Roth) => true,
-            (_,_) => false,
-        }
+        *self == other.classification
     }
 }
 
 impl PartialEq<AccountType> for Account {
     fn eq(&self, other: &AccountType) -> bool {
-        match (&self.classification, other) {
-            (AccountType::Taxable, AccountType::Taxable) => true,
-            (AccountType::Traditional, AccountType::Traditional) => true,
-            (AccountType::Roth, AccountType::Roth) => true,
-            (_,_) => false,
-        }
+        self.classification != AccountType::Allocation && self.classification == *other
     }
 }
 impl Account {
     pub fn new(classification: AccountType) -> Account {
         Account { 
+            name: String::new(),
             classification,
             assets: vec![Asset::new(AssetClass::Domestic, 0.0),
             Asset::new(AssetClass::International, 0.0),
             Asset::new(AssetClass::Bond, 0.0),
-            Asset::new(AssetClass::RealEstate, 0.0)]
+            Asset::new(AssetClass::RealEstate, 0.0)],
+            ledger: Vec::new(),
+            basis: Vec::new(),
         }
     }
 
+    /// Appends a transaction to the account's ledger and applies it to the current holdings.
+    /// Outflows larger than the account's holdings of that asset class are rejected and not
+    /// recorded. Money moving in adds to the cost basis and money moving out takes the same share
+    /// of the basis as of the holdings; changes in market value leave the basis alone.
+    pub fn record(&mut self, transaction: Transaction) -> Result<(), String> {
+        let class = transaction.asset.class.clone();
+        let held = self.get_asset_value(class.clone());
+        if transaction.kind.is_outflow() {
+            self.remove_asset(&transaction.asset)?;
+        } else {
+            self.add_asset(Asset::new(class.clone(), transaction.asset.value));
+        }
+        if !transaction.kind.is_revaluation() {
+            let basis = match self.basis.iter_mut().find(|b| b.class == class) {
+                Some(basis) => basis,
+                None => {
+                    self.basis.push(Asset::new(class, 0.0));
+                    self.basis.last_mut().unwrap()
+                }
+            };
+            if !transaction.kind.is_outflow() {
+                basis.value += transaction.asset.value;
+            } else if held > 0.0 {
+                basis.value *= 1.0 - (transaction.asset.value / held).min(1.0);
+            }
+        }
+        self.ledger.push(transaction);
+        Ok(())
+    }
+
+    /// Returns the cost basis of each asset class, or None when the account has no ledger to
+    /// find it from
+    pub fn cost_basis(&self) -> Option<Account> {
+        if self.ledger.is_empty() {
+            return None;
+        }
+        let mut basis = Account::new(self.classification.clone());
+        for asset in &self.basis {
+            basis.add_asset(Asset::new(asset.class.clone(), asset.value));
+        }
+        Some(basis)
+    }
+
+    /// Copies the account's current holdings, leaving the ledger behind
+    pub fn snapshot(&self) -> Account {
+        Account {
+            name: self.name.clone(),
+            classification: self.classification.clone(),
+            assets: self.assets.iter().map(|a| Asset::new(a.class.clone(), a.value)).collect(),
+            ledger: Vec::new(),
+            basis: Vec::new(),
+        }
+    }
+
+    /// Brings the account's holdings to their market value by recording the change in each asset
+    /// class as a market gain or loss on the date, so the ledger keeps agreeing with the holdings
+    pub fn revalue(&mut self, valued: &Account, date: Date) -> Result<(), String> {
+        for class in AssetClass::all() {
+            let change = valued.get_asset_value(class.clone()) - self.get_asset_value(class.clone());
+            if change.abs() < rebalance::MINIMUM_TRADE {
+                continue;
+            }
+            let kind = if change > 0.0 { TransactionKind::MarketGain } else { TransactionKind::MarketLoss };
+            self.record(Transaction::new(date, kind, class, change.abs()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns every transaction recorded against the account in the order they were recorded
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.ledger
+    }
+
+    /// Replays the ledger to find what the account held at the end of the given date
+    pub fn holdings_at(&self, date: &Date) -> Account {
+        let mut holdings = Account::new(self.classification.clone());
+        for transaction in self.ledger.iter().filter(|t| t.date <= *date) {
+            holdings.add_asset(Asset::new(transaction.asset.class.clone(), transaction.signed_value()));
+        }
+        holdings
+    }
+
+    /// Replays the ledger to find the shares of each security held at the end of the given date.
+    /// Selling part of a position reduces its cost in proportion to the shares sold.
+    pub fn positions_at(&self, date: &Date) -> Vec<Position> {
+        let mut positions: Vec<Position> = Vec::new();
+        for transaction in self.ledger.iter().filter(|t| t.date <= *date) {
+            let ticker = match &transaction.ticker {
+                Some(ticker) => ticker,
+                None => continue,
+            };
+            let index = match positions.iter().position(|p| &p.ticker == ticker) {
+                Some(index) => index,
+                None => {
+                    positions.push(Position {
+                        ticker: ticker.clone(),
+                        class: transaction.asset.class.clone(),
+                        shares: 0.0,
+                        cost: 0.0,
+                    });
+                    positions.len() - 1
+                }
+            };
+            let position = &mut positions[index];
+            if transaction.kind.is_outflow() {
+                if position.shares > 0.0 {
+                    position.cost *= 1.0 - (transaction.shares / position.shares).min(1.0);
+                }
+                position.shares -= transaction.shares;
+            } else {
+                position.shares += transaction.shares;
+                position.cost += transaction.asset.value;
+            }
+        }
+        positions.retain(|p| p.shares > 0.0);
+        positions
+    }
+
     pub fn change_account_classification(&mut self, classification: AccountType) {
         self.classification = classification;
     }
@@ -107,7 +386,7 @@ impl Account {
         return mult;
     }
 
-    fn get_asset_value(&self, class: AssetClass) -> f64 {
+    pub fn get_asset_value(&self, class: AssetClass) -> f64 {
         let mut value = 0.0;
         for asset in &self.assets {
             if asset == &class {
@@ -176,7 +455,8 @@ impl Account {
     }
 
 
-    fn move_asset(&mut self, other: &mut Account, asset: Asset) -> Result<(), String>{
+    /// Moves an asset to another account, failing if this account holds less of its class
+    pub fn move_asset(&mut self, other: &mut Account, asset: Asset) -> Result<(), String> {
         let res = match self.remove_asset(&asset) {
             Ok(()) => Ok(()),
             Err(why) => Err(format!("Failed to move asset: {}", why)),
@@ -207,30 +487,64 @@ impl Account {
         }
         return x;
     }
+
+    /// Shows the account under its local name with its tax treatment and holdings
+    pub fn describe(&self, jurisdiction: &dyn Jurisdiction) -> String {
+        let mut disp = String::new();
+        if !self.name.is_empty() {
+            disp.push_str(&format!("Account Name: {}\n", self.name));
+        }
+        disp.push_str(&format!("Account Classification: {}\n", jurisdiction.account_name(&self.classification)));
+        disp.push_str(&format!("Tax Treatment: {}\n", jurisdiction.tax_treatment(&self.classification)));
+        for asset in &self.assets {
+            disp.push_str(&format!("{} $\n", asset));
+        }
+        disp
+    }
+
+    /// Whether the account is an allocation whose percentages add up to 100, allowing for rounding
+    pub fn is_full_allocation(&self) -> bool {
+        (self.get_total_value() - 100.0).abs() <= ALLOCATION_TOLERANCE
+    }
 }
 
-pub fn setup_new_account() -> Result<Account, String> {
+pub fn setup_new_account(prices: &PriceTable, jurisdiction: &dyn Jurisdiction) -> Result<Account, String> {
+    let account_types = jurisdiction.account_types();
     let account_type = loop {
         println!("What type of account would you like to setup?");
-        println!("1. Taxable\t2. Traditional/401(k)\t3. Roth/Roth 401(k)\t4. Cancel");
+        for (i, account_type) in account_types.iter().enumerate() {
+            print!("{}. {}\t", i + 1, jurisdiction.account_name(account_type));
+        }
+        println!("{}. Cancel", account_types.len() + 1);
         let mut account_type = String::new();
         io::stdin().read_line(&mut account_type)
             .expect("Failed to read line");
-        let choice: u8 = account_type.trim().parse().unwrap_or(0);
+        let choice: usize = account_type.trim().parse().unwrap_or(0);
         println!("\n");
-        match choice {
-            1 => break AccountType::Taxable,
-            2 => break AccountType::Traditional,
-            3 => break AccountType::Roth,
-            4 => return Err("Cancelled account creation".to_string()),
-            _ => continue,
+        if choice == account_types.len() + 1 {
+            return Err("Cancelled account creation".to_string());
+        } else if (1..=account_types.len()).contains(&choice) {
+            break account_types[choice - 1].clone();
         }
     };
-    setup_account(account_type)
+    let mut account = setup_account(account_type, prices)?;
+    println!("What would you like to call this account? Leave blank to name it after its type");
+    let mut name = String::new();
+    io::stdin().read_line(&mut name)
+        .expect("Failed to read line");
+    // Names are saved in comma separated files
+    account.name = name.trim().replace(',', " ");
+    Ok(account)
 }
 
-fn setup_account(account_type: AccountType) -> Result<Account, String> {
-    let mut account = Account::new(account_type);
+/// Asks for the account's opening holdings. They are recorded as transfers in, not contributions,
+/// so they do not count against the year's contribution limit. Each holding is recorded at its
+/// cost on the date it was acquired, keeping its holding period and basis for the tax reports,
+/// then the account is brought to today's value with a market gain or loss.
+fn setup_account(account_type: AccountType, prices: &PriceTable) -> Result<Account, String> {
+    let mut account = Account::new(account_type.clone());
+    let mut valued = Account::new(account_type);
+    let today = Date::today();
     loop {
         println!("What type of asset to account?");
         println!("1. Domestic\t2. International\t3. Bonds\t4. Real Estate\t5. Finish\t6. Cancel");
@@ -249,23 +563,147 @@ fn setup_account(account_type: AccountType) -> Result<Account, String> {
 
         let mut value = String::new();
         println!("How much money would you like to put towards this asset class?");
+        println!("Or enter a ticker and number of shares to value them from the price file, e.g. VTI 10");
         io::stdin().read_line(&mut value)
             .expect("Failed to read line");
-        let value: f64 = match parse_value(&value) {
-            Ok(val) => val,
-            Err(why) => {println!("{:?}", why); 
-                0.0},
+        let class = match choice {
+            1 => AssetClass::Domestic,
+            2 => AssetClass::International,
+            3 => AssetClass::Bond,
+            4 => AssetClass::RealEstate,
+            _ => continue,
+        };
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let (security, current) = if fields.len() == 2 {
+            let shares: f64 = match parse_value(fields[1]) {
+                Ok(shares) if shares > 0.0 => shares,
+                Ok(_) => {println!("The number of shares must be above zero");
+                    continue},
+                Err(why) => {println!("{:?}", why);
+                    continue},
+            };
+            let current = prices.price_on(fields[0], &today).map(|quote| quote.close * shares);
+            (Some((fields[0], shares)), current)
+        } else {
+            match parse_value(&value) {
+                Ok(val) => (None, Some(val)),
+                Err(why) => {println!("{:?}", why);
+                    continue},
+            }
+        };
+        println!("What date were these holdings acquired (YYYY-MM-DD)? Leave blank for today");
+        let mut acquired = String::new();
+        io::stdin().read_line(&mut acquired)
+            .expect("Failed to read line");
+        let acquired = if acquired.trim().is_empty() { today } else {
+            match Date::parse(&acquired) {
+                Ok(date) if date <= today => date,
+                Ok(_) => {println!("The holdings cannot be acquired after today");
+                    continue},
+                Err(why) => {println!("{}", why);
+                    continue},
+            }
+        };
+        println!("What did they cost in total? Leave blank to use their current value");
+        let mut cost = String::new();
+        io::stdin().read_line(&mut cost)
+            .expect("Failed to read line");
+        let cost = if cost.trim().is_empty() {
+            match current {
+                Some(current) => current,
+                None => {println!("No price for {} in the price file, so enter what the shares cost",
+                                  security.map_or("", |(ticker, _)| ticker));
+                    continue},
+            }
+        } else {
+            match parse_value(&cost) {
+                Ok(cost) => cost,
+                Err(why) => {println!("{:?}", why);
+                    continue},
+            }
+        };
+        let transaction = match security {
+            Some((ticker, shares)) => Transaction::with_shares(acquired, TransactionKind::TransferIn, class.clone(),
+                                                               ticker, shares, cost / shares),
+            None => Transaction::new(acquired, TransactionKind::TransferIn, class.clone(), cost),
         };
         println!("\n");
+        account.record(transaction)?;
+        valued.add_asset(Asset::new(class, current.unwrap_or(cost)));
+    }
+    account.revalue(&valued, today)?;
+    return Ok(account);
+}
+
+/// Ask user of the program for the details of a transaction to add to an account's ledger
+pub fn request_transaction() -> Result<Transaction, String> {
+    let kind = loop {
+        println!("What type of transaction would you like to record?");
+        println!("1. Contribution\t2. Withdrawal\t3. Buy\t4. Sell\t5. Dividend\t6. Transfer In\t7. Transfer Out\t8. Cancel");
+        let mut kind = String::new();
+        io::stdin().read_line(&mut kind)
+            .expect("Failed to read line");
+        let choice: u8 = kind.trim().parse().unwrap_or(0);
         match choice {
-            1 => account.add_asset(Asset::new(AssetClass::Domestic, value)),
-            2 => account.add_asset(Asset::new(AssetClass::International, value)),
-            3 => account.add_asset(Asset::new(AssetClass::Bond, value)),
-            4 => account.add_asset(Asset::new(AssetClass::RealEstate, value)),
+            1 => break TransactionKind::Contribution,
+            2 => break TransactionKind::Withdrawal,
+            3 => break TransactionKind::Buy,
+            4 => break TransactionKind::Sell,
+            5 => break TransactionKind::Dividend,
+            6 => break TransactionKind::TransferIn,
+            7 => break TransactionKind::TransferOut,
+            8 => return Err("Cancelled transaction".to_string()),
             _ => continue,
         }
+    };
+    let class = loop {
+        println!("Which asset class does the transaction affect?");
+        println!("1. Domestic\t2. International\t3. Bonds\t4. Real Estate\t5. Cancel");
+        let mut class = String::new();
+        io::stdin().read_line(&mut class)
+            .expect("Failed to read line");
+        let choice: u8 = class.trim().parse().unwrap_or(0);
+        match choice {
+            1 => break AssetClass::Domestic,
+            2 => break AssetClass::International,
+            3 => break AssetClass::Bond,
+            4 => break AssetClass::RealEstate,
+            5 => return Err("Cancelled transaction".to_string()),
+            _ => continue,
+        }
+    };
+    let security = if matches!(kind, TransactionKind::Buy | TransactionKind::Sell
+                                     | TransactionKind::TransferIn | TransactionKind::TransferOut) {
+        println!("Ticker and number of shares, e.g. VTI 10? Leave blank for a dollar amount");
+        let mut security = String::new();
+        io::stdin().read_line(&mut security)
+            .expect("Failed to read line");
+        let fields: Vec<&str> = security.split_whitespace().collect();
+        match fields.len() {
+            0 => None,
+            2 => Some((fields[0].to_uppercase(), parse_value(fields[1])?)),
+            _ => return Err(format!("Expected a ticker and number of shares but found {}", security.trim())),
+        }
+    } else {
+        None
+    };
+    println!("How much money did the transaction move?");
+    let mut value = String::new();
+    io::stdin().read_line(&mut value)
+        .expect("Failed to read line");
+    let value = parse_value(&value)?;
+    println!("What date did the transaction happen (YYYY-MM-DD)? Leave blank for today");
+    let mut date = String::new();
+    io::stdin().read_line(&mut date)
+        .expect("Failed to read line");
+    let date = if date.trim().is_empty() { Date::today() } else { Date::parse(&date)? };
+    println!("\n");
+    match security {
+        Some((ticker, shares)) if shares > 0.0 => Ok(Transaction::with_shares(date, kind, class, &ticker, shares,
+                                                                              value / shares)),
+        Some(_) => Err("The number of shares must be above zero".to_string()),
+        None => Ok(Transaction::new(date, kind, class, value)),
     }
-    return Ok(account);
 }
 
 pub fn request_allocation()-> Result<Account, String> {
@@ -291,6 +729,15 @@ pub fn request_allocation()-> Result<Account, String> {
             .expect("Failed to read line");
         let value: f64 = value.trim().parse().unwrap_or(0.0);
         println!("\n");
+        if value < 0.0 {
+            println!("Percent cannot be negative");
+            continue;
+        }
+        if allocation.get_total_value() + value > 100.0 + ALLOCATION_TOLERANCE {
+            println!("That would bring the allocation to {}%, enter at most {}%",
+                     allocation.get_total_value() + value, 100.0 - allocation.get_total_value());
+            continue;
+        }
         match class {
             1 => allocation.add_asset(Asset::new(AssetClass::Domestic, value)),
             2 => allocation.add_asset(Asset::new(AssetClass::International, value)),
@@ -298,11 +745,7 @@ pub fn request_allocation()-> Result<Account, String> {
             4 => allocation.add_asset(Asset::new(AssetClass::RealEstate, value)),
             _ => continue,
         }
-        let total = allocation.get_total_value();
-        if total < 100.0 {
-            continue;
-        } else if total > 100.0 {
-            allocation = Account::new(AccountType::Allocation); 
+        if !allocation.is_full_allocation() {
             continue;
         } else {
             break;
@@ -311,3 +754,58 @@ pub fn request_allocation()-> Result<Account, String> {
     }
     return Ok(allocation);
 }
+
+#[test]
+fn holdings_are_replayed_from_ledger_up_to_date() {
+    let mut account = Account::new(AccountType::Taxable);
+    let jan = Date::new(2019, 1, 2).unwrap();
+    let jun = Date::new(2019, 6, 3).unwrap();
+    account.record(Transaction::new(jan, TransactionKind::Contribution, AssetClass::Domestic, 1000.0)).unwrap();
+    account.record(Transaction::new(jun, TransactionKind::Sell, AssetClass::Domestic, 400.0)).unwrap();
+    account.record(Transaction::new(jun, TransactionKind::Buy, AssetClass::Bond, 400.0)).unwrap();
+    assert_eq!(1000.0, account.holdings_at(&jan).get_total_value());
+    assert_eq!(600.0, account.holdings_at(&jun).get_asset_value(AssetClass::Domestic));
+    assert_eq!(account.get_total_value(), account.holdings_at(&jun).get_total_value());
+}
+
+#[test]
+fn revaluation_is_in_the_ledger_but_not_the_basis() {
+    let mut account = Account::new(AccountType::Taxable);
+    let jan = Date::new(2019, 1, 2).unwrap();
+    let dec = Date::new(2019, 12, 31).unwrap();
+    account.record(Transaction::new(jan, TransactionKind::Contribution, AssetClass::Domestic, 1000.0)).unwrap();
+    let mut valued = Account::new(AccountType::Taxable);
+    valued.add_asset(Asset::new(AssetClass::Domestic, 1500.0));
+    account.revalue(&valued, dec).unwrap();
+    assert_eq!(1500.0, account.get_total_value());
+    assert_eq!(1500.0, account.holdings_at(&dec).get_total_value());
+    assert_eq!(1000.0, account.cost_basis().unwrap().get_total_value());
+    account.record(Transaction::new(dec, TransactionKind::Sell, AssetClass::Domestic, 750.0)).unwrap();
+    assert_eq!(500.0, account.cost_basis().unwrap().get_total_value());
+}
+
+#[test]
+fn overdrawn_outflow_is_not_recorded() {
+    let mut account = Account::new(AccountType::Roth);
+    let date = Date::new(2019, 1, 2).unwrap();
+    account.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Bond, 100.0)).unwrap();
+    assert!(account.record(Transaction::new(date, TransactionKind::Withdrawal, AssetClass::Bond, 150.0)).is_err());
+    assert_eq!(1, account.transactions().len());
+    assert_eq!(100.0, account.get_total_value());
+}
+
+#[test]
+fn accounts_of_different_types_differ() {
+    assert!(Account::new(AccountType::Sep) != Account::new(AccountType::Simple));
+}
+
+#[test]
+fn allocations_allow_for_rounding() {
+    let mut allocation = Account::new(AccountType::Allocation);
+    allocation.add_asset(Asset::new(AssetClass::Domestic, 33.3));
+    allocation.add_asset(Asset::new(AssetClass::International, 33.3));
+    allocation.add_asset(Asset::new(AssetClass::Bond, 33.4));
+    assert!(allocation.is_full_allocation());
+    allocation.add_asset(Asset::new(AssetClass::Bond, 0.1));
+    assert!(!allocation.is_full_allocation());
+}
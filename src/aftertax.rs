@@ -0,0 +1,125 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::jurisdiction::Jurisdiction;
+use crate::rebalance;
+
+/// Rates used to turn account balances into what they are worth after tax. Balances that will be
+/// taxed as income when withdrawn lose the income rate. When a gains rate is given, taxable
+/// accounts also lose that rate on gains not yet realized.
+pub struct TaxAdjustment {
+    pub income_rate: f64,
+    pub gains_rate: Option<f64>,
+}
+
+/// Returns what each asset class in the account is worth after the tax still owed on it. Accounts
+/// without a ledger have no known cost basis and are assumed to hold no gains.
+pub fn after_tax(account: &Account, adjustment: &TaxAdjustment, jurisdiction: &dyn Jurisdiction) -> Account {
+    let treatment = jurisdiction.tax_treatment(&account.classification);
+    let basis = account.cost_basis();
+    let mut adjusted = Account::new(account.classification.clone());
+    for asset in &account.assets {
+        let mut value = asset.value;
+        if treatment.taxed_withdrawals {
+            value *= 1.0 - adjustment.income_rate;
+        } else if let (true, Some(rate), Some(basis)) = (treatment.taxable_growth, adjustment.gains_rate, &basis) {
+            let gain = (asset.value - basis.get_asset_value(asset.class.clone())).max(0.0);
+            value -= rate * gain;
+        }
+        adjusted.add_asset(Asset::new(asset.class.clone(), value));
+    }
+    adjusted
+}
+
+/// Returns the combined holdings of the accounts, at face value or after tax
+pub fn combined(accounts: &[Account], adjustment: Option<&TaxAdjustment>, jurisdiction: &dyn Jurisdiction) -> Account {
+    let mut holdings = Account::new(AccountType::Taxable);
+    for account in accounts {
+        let counted = match adjustment {
+            Some(adjustment) => after_tax(account, adjustment, jurisdiction),
+            None => account.snapshot(),
+        };
+        for asset in &counted.assets {
+            holdings.add_asset(Asset::new(asset.class.clone(), asset.value));
+        }
+    }
+    holdings
+}
+
+/// Returns how many account dollars of each asset class to take out (+) or put in (-) to bring the
+/// after-tax holdings to the target. Each asset class's after-tax drift is scaled back up by what
+/// a dollar of it is worth after tax, since trades are placed at face value.
+pub fn pre_tax_drift(accounts: &[Account], target: &Account, adjustment: &TaxAdjustment,
+                     jurisdiction: &dyn Jurisdiction) -> Account {
+    let raw = combined(accounts, None, jurisdiction);
+    let adjusted = combined(accounts, Some(adjustment), jurisdiction);
+    let drift = rebalance::drift(&adjusted, target);
+    let mut trades = Account::new(AccountType::Taxable);
+    for class in AssetClass::all() {
+        let after_tax = adjusted.get_asset_value(class.clone());
+        let scale = if after_tax > 0.0 { raw.get_asset_value(class.clone()) / after_tax } else { 1.0 };
+        trades.add_asset(Asset::new(class.clone(), drift.get_asset_value(class) * scale));
+    }
+    trades
+}
+
+/// Lays out each asset class's value, share and drift from target at face value next to the same
+/// figures after tax
+pub fn report(accounts: &[Account], target: &Account, adjustment: &TaxAdjustment,
+              jurisdiction: &dyn Jurisdiction) -> String {
+    let raw = combined(accounts, None, jurisdiction);
+    let adjusted = combined(accounts, Some(adjustment), jurisdiction);
+    let mut disp = format!("{:<15}{:>16}{:>9}{:>9}{:>16}{:>9}{:>9}\n", "", "Raw $", "%", "Drift",
+                           "After-tax $", "%", "Drift");
+    for class in AssetClass::all() {
+        disp.push_str(&format!("{:<15}", class.to_string()));
+        for holdings in &[&raw, &adjusted] {
+            let total = holdings.get_total_value();
+            let value = holdings.get_asset_value(class.clone());
+            let share = if total > 0.0 { 100.0 * value / total } else { 0.0 };
+            disp.push_str(&format!("{:>16}{:>9.2}{:>+9.2}", value.separated_string_with_fixed_place(2), share,
+                                   share - target.get_asset_value(class.clone())));
+        }
+        disp.push('\n');
+    }
+    disp.push_str(&format!("{:<15}{:>16}{:>18}{:>16}\n", "Total", raw.get_total_value().separated_string_with_fixed_place(2),
+                           "", adjusted.get_total_value().separated_string_with_fixed_place(2)));
+    disp
+}
+
+#[test]
+fn pre_tax_balances_and_gains_are_discounted() {
+    use crate::date::Date;
+    use crate::jurisdiction::UnitedStates;
+    use crate::transaction::{Transaction, TransactionKind};
+    let adjustment = TaxAdjustment { income_rate: 0.25, gains_rate: Some(0.15) };
+    let mut traditional = Account::new(AccountType::Traditional);
+    traditional.add_asset(Asset::new(AssetClass::Bond, 100_000.0));
+    let mut roth = Account::new(AccountType::Roth);
+    roth.add_asset(Asset::new(AssetClass::Bond, 100_000.0));
+    assert_eq!(75_000.0, after_tax(&traditional, &adjustment, &UnitedStates).get_total_value());
+    assert_eq!(100_000.0, after_tax(&roth, &adjustment, &UnitedStates).get_total_value());
+
+    let mut taxable = Account::new(AccountType::Taxable);
+    let date = Date::new(2019, 1, 2).unwrap();
+    taxable.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Domestic, 10_000.0)).unwrap();
+    taxable.add_asset(Asset::new(AssetClass::Domestic, 10_000.0));
+    assert_eq!(18_500.0, after_tax(&taxable, &adjustment, &UnitedStates).get_total_value());
+
+    let holdings = combined(&[traditional, roth], Some(&adjustment), &UnitedStates);
+    assert_eq!(175_000.0, holdings.get_asset_value(AssetClass::Bond));
+
+    // 75,000 after tax of domestic stocks is 5,000 over a 50/50 target; selling it takes 6,666.67
+    // of the traditional account's pre-tax dollars
+    let mut stocks = Account::new(AccountType::Traditional);
+    stocks.add_asset(Asset::new(AssetClass::Domestic, 100_000.0));
+    let mut bonds = Account::new(AccountType::Roth);
+    bonds.add_asset(Asset::new(AssetClass::Bond, 65_000.0));
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    let trades = pre_tax_drift(&[stocks, bonds], &target, &adjustment, &UnitedStates);
+    assert!((trades.get_asset_value(AssetClass::Domestic) - 20_000.0 / 3.0).abs() < 1e-6);
+    assert!((trades.get_asset_value(AssetClass::Bond) + 5_000.0).abs() < 1e-6);
+}
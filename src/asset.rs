@@ -14,6 +14,34 @@ pub struct Asset {
     pub value: f64
 }
 
+impl AssetClass {
+    /// Returns every asset class in the order they are displayed
+    pub fn all() -> Vec<AssetClass> {
+        vec![AssetClass::Domestic, AssetClass::International, AssetClass::Bond, AssetClass::RealEstate]
+    }
+
+    /// Reads an asset class from a name, ignoring case, spaces and underscores
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// assert!(AssetClass::parse("Real Estate").unwrap() == AssetClass::RealEstate);
+    /// ```
+    pub fn parse(name: &str) -> Result<AssetClass, String> {
+        let key: String = name.chars()
+            .filter(|c| !c.is_whitespace() && *c != '_')
+            .collect::<String>()
+            .to_lowercase();
+        match key.as_str() {
+            "domestic" | "u.s.a." | "usa" | "us" => Ok(AssetClass::Domestic),
+            "international" | "intl" => Ok(AssetClass::International),
+            "bond" | "bonds" => Ok(AssetClass::Bond),
+            "realestate" | "reit" | "reits" => Ok(AssetClass::RealEstate),
+            _ => Err(format!("Unknown asset class {}", name.trim())),
+        }
+    }
+}
+
 impl fmt::Display for AssetClass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self {
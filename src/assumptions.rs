@@ -0,0 +1,131 @@
+use std::fs;
+use crate::asset::AssetClass;
+
+/// Expected annual return, annual volatility and correlations of each asset class, used to
+/// simulate and optimize portfolios
+pub struct MarketAssumptions {
+    pub classes: Vec<AssetClass>,
+    pub expected_returns: Vec<f64>,
+    pub volatilities: Vec<f64>,
+    pub correlations: Vec<Vec<f64>>,
+}
+
+impl MarketAssumptions {
+    /// Long-run assumptions to use when no file is given
+    pub fn standard() -> MarketAssumptions {
+        MarketAssumptions {
+            classes: AssetClass::all(),
+            expected_returns: vec![0.07, 0.075, 0.035, 0.065],
+            volatilities: vec![0.16, 0.18, 0.05, 0.19],
+            correlations: vec![
+                vec![1.00, 0.80, 0.10, 0.65],
+                vec![0.80, 1.00, 0.10, 0.55],
+                vec![0.10, 0.10, 1.00, 0.20],
+                vec![0.65, 0.55, 0.20, 1.00],
+            ],
+        }
+    }
+
+    /// Loads assumptions from a CSV file
+    pub fn load(path: &str) -> Result<MarketAssumptions, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read assumptions file {}: {}", path, why))?;
+        MarketAssumptions::parse(&text)
+    }
+
+    /// Parses CSV text with a row per asset class holding its expected return and volatility,
+    /// followed by its correlation with each asset class in the header
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let text = "class,return,volatility,domestic,bond\n\
+    ///             domestic,0.07,0.16,1.0,0.1\n\
+    ///             bond,0.035,0.05,0.1,1.0\n";
+    /// let assumptions = MarketAssumptions::parse(text).unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<MarketAssumptions, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let header = match lines.next() {
+            Some((_, header)) => header,
+            None => return Err("Assumptions file is empty".to_string()),
+        };
+        let columns = header.split(',')
+            .skip(3)
+            .map(AssetClass::parse)
+            .collect::<Result<Vec<AssetClass>, String>>()?;
+        let mut rows = Vec::new();
+        for (number, line) in lines {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != columns.len() + 3 {
+                return Err(format!("Line {}: expected {} columns but found {}", number + 1,
+                                   columns.len() + 3, fields.len()));
+            }
+            let class = AssetClass::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            let numbers = fields[1..].iter()
+                .map(|f| f.parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            rows.push((class, numbers));
+        }
+        if rows.len() != columns.len() || rows.iter().zip(&columns).any(|((class, _), column)| class != column) {
+            return Err("Rows must list the same asset classes, in the same order, as the correlation columns".to_string());
+        }
+        let assumptions = MarketAssumptions {
+            classes: columns,
+            expected_returns: rows.iter().map(|(_, n)| n[0]).collect(),
+            volatilities: rows.iter().map(|(_, n)| n[1]).collect(),
+            correlations: rows.iter().map(|(_, n)| n[2..].to_vec()).collect(),
+        };
+        assumptions.validate()?;
+        Ok(assumptions)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, row) in self.correlations.iter().enumerate() {
+            if (row[i] - 1.0).abs() > 1e-9 {
+                return Err(format!("{} must have a correlation of 1 with itself", self.classes[i]));
+            }
+            for (j, correlation) in row.iter().enumerate() {
+                if (correlation - self.correlations[j][i]).abs() > 1e-9 || correlation.abs() > 1.0 {
+                    return Err(format!("Correlation of {} and {} must be symmetric and between -1 and 1",
+                                       self.classes[i], self.classes[j]));
+                }
+            }
+        }
+        // Simulated returns are drawn through a Cholesky factor of the covariance matrix, which
+        // does not exist when an asset class has no volatility
+        if let Some(i) = self.volatilities.iter().position(|v| *v <= 0.0) {
+            return Err(format!("Volatility of {} must be above zero", self.classes[i]));
+        }
+        Ok(())
+    }
+
+    /// Returns the covariance matrix of annual returns
+    pub fn covariance(&self) -> Vec<Vec<f64>> {
+        self.correlations.iter().enumerate()
+            .map(|(i, row)| row.iter().enumerate()
+                 .map(|(j, correlation)| correlation * self.volatilities[i] * self.volatilities[j])
+                 .collect())
+            .collect()
+    }
+}
+
+#[test]
+fn assumptions_parse_correlation_matrix() {
+    let text = "class,return,volatility,domestic,bond\ndomestic,0.07,0.16,1.0,0.1\nbond,0.035,0.05,0.1,1.0\n";
+    let assumptions = MarketAssumptions::parse(text).unwrap();
+    assert!(assumptions.classes[1] == AssetClass::Bond);
+    assert!((assumptions.covariance()[0][1] - 0.1 * 0.16 * 0.05).abs() < 1e-12);
+}
+
+#[test]
+fn assumptions_reject_asymmetric_correlations() {
+    let text = "class,return,volatility,domestic,bond\ndomestic,0.07,0.16,1.0,0.1\nbond,0.035,0.05,0.3,1.0\n";
+    assert!(MarketAssumptions::parse(text).is_err());
+}
+
+#[test]
+fn assumptions_reject_zero_volatility() {
+    let text = "class,return,volatility,domestic,bond\ndomestic,0.07,0.16,1.0,0.1\nbond,0.035,0,0.1,1.0\n";
+    assert!(MarketAssumptions::parse(text).is_err());
+}
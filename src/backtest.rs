@@ -0,0 +1,206 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fmt;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::rebalance;
+use crate::returns::{self, ReturnHistory};
+use crate::stats;
+
+/// When a simulated portfolio is brought back to its target allocation
+pub enum Policy {
+    Never,
+    /// Rebalance every this many months
+    Calendar(usize),
+    /// Rebalance when any asset class drifts this many percentage points from target
+    Threshold(f64),
+    /// Never sell, only direct contributions to the asset classes below target
+    ContributionsOnly,
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Policy::Never => "Never".fmt(f),
+            Policy::Calendar(1) => "Monthly".fmt(f),
+            Policy::Calendar(3) => "Quarterly".fmt(f),
+            Policy::Calendar(12) => "Annual".fmt(f),
+            Policy::Calendar(months) => format!("Every {} months", months).fmt(f),
+            Policy::Threshold(band) => format!("{}% band", band).fmt(f),
+            Policy::ContributionsOnly => "Contributions only".fmt(f),
+        }
+    }
+}
+
+pub struct BacktestResult {
+    pub ending_value: f64,
+    /// Annualized standard deviation of monthly returns
+    pub volatility: f64,
+    pub max_drawdown: f64,
+    /// Dollars bought when rebalancing each year, as a fraction of the average portfolio value
+    pub turnover: f64,
+    pub trades: usize,
+    /// Withdrawals the portfolio could not pay
+    pub shortfall: f64,
+    /// First month, counting from 1, a withdrawal could not be paid in full
+    pub depleted: Option<usize>,
+}
+
+/// What happened to a simulated portfolio over one month
+pub struct Month {
+    /// Growth of the portfolio from returns alone, before the contribution
+    pub growth: f64,
+    pub trades: usize,
+    /// Dollars bought when rebalancing
+    pub bought: f64,
+    /// Part of the withdrawal the holdings could not pay
+    pub shortfall: f64,
+}
+
+/// Advances holdings by a month of returns, adds the contribution at the end of the month (a
+/// withdrawal when negative) and rebalances if the policy calls for it. A withdrawal that overdraws
+/// an asset class is made up from the others, and whatever the holdings cannot pay is returned as
+/// the shortfall. Month numbers start at 0.
+pub fn simulate_month(holdings: &mut Account, returns: &[(AssetClass, f64)], target: &Account,
+                      contribution: f64, policy: &Policy, month: usize) -> Month {
+    let opening = holdings.get_total_value();
+    for asset in &mut holdings.assets {
+        asset.value *= 1.0 + returns::return_of(returns, &asset.class);
+    }
+    let growth = if opening > 0.0 { holdings.get_total_value() / opening - 1.0 } else { 0.0 };
+
+    let contribution = match policy {
+        Policy::ContributionsOnly => rebalance::contribution_trades(holdings, target, contribution),
+        _ => target.multiply(0.01 * contribution),
+    };
+    for asset in &contribution.assets {
+        holdings.add_asset(Asset::new(asset.class.clone(), asset.value));
+    }
+    let overdrawn: f64 = holdings.assets.iter().map(|a| (-a.value).max(0.0)).sum();
+    let remaining: f64 = holdings.assets.iter().map(|a| a.value.max(0.0)).sum();
+    let shortfall = (overdrawn - remaining).max(0.0);
+    for asset in &mut holdings.assets {
+        asset.value = if remaining > overdrawn {
+            asset.value.max(0.0) * (1.0 - overdrawn / remaining)
+        } else {
+            0.0
+        };
+    }
+
+    let due = match policy {
+        Policy::Never | Policy::ContributionsOnly => false,
+        Policy::Calendar(months) => (month + 1) % (*months).max(1) == 0,
+        Policy::Threshold(band) => rebalance::largest_drift(holdings, target) > *band,
+    };
+    let (mut trades, mut bought) = (0, 0.0);
+    if due {
+        let rebalancing = rebalance::trades_to_target(holdings, target);
+        let (count, volume) = rebalance::trade_count_and_volume(&rebalancing);
+        trades = count;
+        bought = volume;
+        rebalance::apply(holdings, &rebalancing);
+    }
+    Month { growth, trades, bought, shortfall }
+}
+
+/// Simulates a portfolio that starts at the target allocation, adds the contribution at the end of
+/// every month (a withdrawal when negative) and is rebalanced according to the policy
+pub fn backtest(history: &ReturnHistory, target: &Account, start_value: f64, monthly_contribution: f64,
+                policy: &Policy) -> BacktestResult {
+    let mut holdings = target.multiply(0.01 * start_value);
+    holdings.change_account_classification(AccountType::Taxable);
+    let mut monthly_returns = Vec::new();
+    let mut values = Vec::new();
+    let mut bought = 0.0;
+    let mut trades = 0;
+    let mut shortfall = 0.0;
+    let mut depleted = None;
+
+    for (number, period) in history.periods.iter().enumerate() {
+        let month = simulate_month(&mut holdings, &period.returns, target, monthly_contribution, policy, number);
+        monthly_returns.push(month.growth);
+        trades += month.trades;
+        bought += month.bought;
+        if month.shortfall > 0.0 && depleted.is_none() {
+            depleted = Some(number + 1);
+        }
+        shortfall += month.shortfall;
+        values.push(holdings.get_total_value());
+    }
+
+    let periods_per_year = history.periods_per_year();
+    let years = history.periods.len() as f64 / periods_per_year;
+    let average_value = stats::mean(&values);
+    BacktestResult {
+        ending_value: holdings.get_total_value(),
+        volatility: stats::std_dev(&monthly_returns) * periods_per_year.sqrt(),
+        max_drawdown: stats::max_drawdown(&monthly_returns),
+        turnover: if years > 0.0 && average_value > 0.0 { bought / average_value / years } else { 0.0 },
+        trades,
+        shortfall,
+        depleted,
+    }
+}
+
+/// Compares the standard rebalancing policies over the return history
+pub fn report(history: &ReturnHistory, target: &Account, start_value: f64, monthly_contribution: f64) -> String {
+    let policies = [Policy::Never, Policy::Calendar(1), Policy::Calendar(3), Policy::Calendar(12),
+                    Policy::Threshold(5.0), Policy::ContributionsOnly];
+    let mut disp = format!("Backtest over {} months starting with ${}\n", history.periods.len(),
+                           start_value.separated_string_with_fixed_place(2));
+    disp.push_str(&format!("{:<20}{:>16}{:>12}{:>14}{:>12}{:>8}{:>10}\n",
+                           "Policy", "Ending Value", "Volatility", "Max Drawdown", "Turnover", "Trades", "Depleted"));
+    let mut shortfalls = Vec::new();
+    for policy in &policies {
+        let result = backtest(history, target, start_value, monthly_contribution, policy);
+        let depleted = result.depleted.map(|m| format!("Month {}", m)).unwrap_or_else(|| "Never".to_string());
+        disp.push_str(&format!("{:<20}{:>16}{:>11.2}%{:>13.2}%{:>11.2}%{:>8}{:>10}\n", policy.to_string(),
+                               result.ending_value.separated_string_with_fixed_place(2),
+                               100.0 * result.volatility, 100.0 * result.max_drawdown,
+                               100.0 * result.turnover, result.trades, depleted));
+        if result.shortfall > 0.0 {
+            shortfalls.push(format!("{} left {} of withdrawals unpaid\n", policy,
+                                    result.shortfall.separated_string_with_fixed_place(2)));
+        }
+    }
+    for shortfall in shortfalls {
+        disp.push_str(&shortfall);
+    }
+    disp
+}
+
+#[test]
+fn never_rebalancing_lets_winners_run() {
+    let history = ReturnHistory::parse("date,domestic,bond\n2019-01-31,0.5,0.0\n2019-02-28,0.5,0.0\n").unwrap();
+    let result = backtest(&history, &rebalance::sixty_forty(), 100.0, 0.0, &Policy::Never);
+    assert!((result.ending_value - 175.0).abs() < 1e-9);
+    assert_eq!(0, result.trades);
+}
+
+#[test]
+fn monthly_rebalancing_trades_every_month() {
+    let history = ReturnHistory::parse("date,domestic,bond\n2019-01-31,0.5,0.0\n2019-02-28,-0.2,0.0\n").unwrap();
+    let result = backtest(&history, &rebalance::sixty_forty(), 100.0, 0.0, &Policy::Calendar(1));
+    assert_eq!(4, result.trades);
+    assert!(result.max_drawdown > 0.0);
+    // 100 -> 130, rebalanced to 78/52, then domestic falls to 62.4
+    assert!((result.ending_value - 114.4).abs() < 1e-9);
+}
+
+#[test]
+fn threshold_policy_waits_for_band() {
+    let history = ReturnHistory::parse("date,domestic,bond\n2019-01-31,0.02,0.0\n2019-02-28,0.3,0.0\n").unwrap();
+    let result = backtest(&history, &rebalance::sixty_forty(), 100.0, 0.0, &Policy::Threshold(5.0));
+    assert_eq!(2, result.trades);
+}
+
+#[test]
+fn withdrawals_beyond_the_holdings_deplete_the_portfolio() {
+    let history = ReturnHistory::parse("date,domestic,bond\n2019-01-31,0.0,0.0\n2019-02-28,0.0,0.0\n\
+                                        2019-03-31,0.0,0.0\n").unwrap();
+    let result = backtest(&history, &rebalance::sixty_forty(), 100.0, -40.0, &Policy::Never);
+    // Bonds run out in the second month and the rest comes from domestic stocks
+    assert_eq!(Some(3), result.depleted);
+    assert!((result.shortfall - 20.0).abs() < 1e-9);
+    assert_eq!(0.0, result.ending_value);
+}
@@ -0,0 +1,235 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+use crate::gains;
+use crate::jurisdiction::Jurisdiction;
+use crate::prices::PriceTable;
+use crate::rebalance;
+use crate::tax::{Income, TaxTable};
+
+/// Shares of a lot proposed as a gift, valued at their last known price
+pub struct Gift {
+    /// Index of the account the lot is held in
+    pub account: usize,
+    pub ticker: String,
+    pub class: AssetClass,
+    pub acquired: Date,
+    pub shares: f64,
+    pub value: f64,
+    pub cost: f64,
+}
+
+impl Gift {
+    pub fn gain(&self) -> f64 {
+        self.value - self.cost
+    }
+}
+
+/// Lots to give in place of cash and what to buy back with the cash kept
+pub struct GivingPlan {
+    pub gifts: Vec<Gift>,
+    /// Part of the donation no appreciated lot was found for, to be given in cash
+    pub cash: f64,
+    /// Asset classes to buy in each account that gave lots
+    pub rebuys: Vec<(usize, Account)>,
+    /// Tickers held in long-term lots that could not be valued
+    pub unpriced: Vec<String>,
+}
+
+/// Proposes the lots to donate instead of cash. Long-term lots with a gain in accounts taxed on
+/// growth, costed with wash sales across all the accounts, are given in order of their gain as a share of their value, so every dollar donated carries
+/// away as much untaxed gain as possible. The last lot may be given in part. The cash that would
+/// otherwise have been donated is spent in the same accounts on the asset classes furthest below
+/// the target, which restores the allocation and buys new lots at today's prices. Without a full
+/// target allocation the cash buys back the asset classes given, in the amounts given.
+pub fn plan(accounts: &[Account], prices: &PriceTable, date: &Date, amount: f64, target: &Account,
+            jurisdiction: &dyn Jurisdiction) -> GivingPlan {
+    let mut candidates = Vec::new();
+    let mut unpriced: Vec<String> = Vec::new();
+    let ledgers = gains::replay_all(&accounts.iter().collect::<Vec<&Account>>(), date, jurisdiction);
+    for (index, (account, ledger)) in accounts.iter().zip(ledgers).enumerate() {
+        if !jurisdiction.tax_treatment(&account.classification).taxable_growth {
+            continue;
+        }
+        for lot in ledger.lots.into_iter().filter(|l| l.is_long_term(date)) {
+            let value = match prices.price_on(&lot.ticker, date) {
+                Some(quote) => lot.shares * quote.close,
+                None => {
+                    if !unpriced.contains(&lot.ticker) {
+                        unpriced.push(lot.ticker.clone());
+                    }
+                    continue;
+                }
+            };
+            if value > lot.cost {
+                candidates.push(Gift {
+                    account: index,
+                    ticker: lot.ticker,
+                    class: lot.class,
+                    acquired: lot.acquired,
+                    shares: lot.shares,
+                    value,
+                    cost: lot.cost,
+                });
+            }
+        }
+    }
+    candidates.sort_by(|a, b| (b.gain() / b.value).partial_cmp(&(a.gain() / a.value))
+        .unwrap_or(std::cmp::Ordering::Equal));
+    let mut gifts = Vec::new();
+    let mut remaining = amount;
+    for mut gift in candidates {
+        if remaining < rebalance::MINIMUM_TRADE {
+            break;
+        }
+        if gift.value > remaining {
+            let share = remaining / gift.value;
+            gift.shares *= share;
+            gift.cost *= share;
+            gift.value = remaining;
+        }
+        remaining -= gift.value;
+        gifts.push(gift);
+    }
+    let mut holdings = Account::new(AccountType::Taxable);
+    for account in accounts {
+        rebalance::apply(&mut holdings, account);
+    }
+    for gift in &gifts {
+        holdings.add_asset(Asset::new(gift.class.clone(), -gift.value));
+    }
+    let mut rebuys = Vec::new();
+    for (index, account) in accounts.iter().enumerate() {
+        let given: f64 = gifts.iter().filter(|g| g.account == index).map(|g| g.value).sum();
+        if given < rebalance::MINIMUM_TRADE {
+            continue;
+        }
+        let trades = if target.is_full_allocation() {
+            rebalance::contribution_trades(&holdings, target, given)
+        } else {
+            let mut trades = Account::new(account.classification.clone());
+            for gift in gifts.iter().filter(|g| g.account == index) {
+                trades.add_asset(Asset::new(gift.class.clone(), gift.value));
+            }
+            trades
+        };
+        rebalance::apply(&mut holdings, &trades);
+        rebuys.push((index, trades));
+    }
+    GivingPlan { gifts, cash: remaining.max(0.0), rebuys, unpriced }
+}
+
+/// Returns the tax that selling the donated lots on top of the base income would have cost
+pub fn tax_avoided(plan: &GivingPlan, table: &TaxTable, base: &Income) -> f64 {
+    let gain: f64 = plan.gifts.iter().map(|g| g.gain()).sum();
+    table.incremental(base, &Income { qualified: gain, ..Income::default() }).total()
+}
+
+/// Lays out the lots to give, the purchases that replace them and the tax the gifts avoid
+pub fn report(plan: &GivingPlan, accounts: &[Account], tax_avoided: f64) -> String {
+    let mut disp = String::new();
+    if plan.gifts.is_empty() {
+        disp.push_str("No appreciated long-term lots to give\n");
+    } else {
+        disp.push_str(&format!("{:<20}{:<8}{:>12}{:>12}{:>14}{:>14}{:>14}\n", "Account", "Ticker", "Acquired", "Shares",
+                               "Value", "Cost", "Gain"));
+        for gift in &plan.gifts {
+            disp.push_str(&format!("{:<20}{:<8}{:>12}{:>12.4}{:>14}{:>14}{:>14}\n", accounts[gift.account].name, gift.ticker,
+                                   gift.acquired.to_string(), gift.shares,
+                                   gift.value.separated_string_with_fixed_place(2),
+                                   gift.cost.separated_string_with_fixed_place(2),
+                                   gift.gain().separated_string_with_fixed_place(2)));
+        }
+    }
+    if plan.cash >= rebalance::MINIMUM_TRADE {
+        disp.push_str(&format!("Give the remaining {} in cash\n", plan.cash.separated_string_with_fixed_place(2)));
+    }
+    for (index, trades) in &plan.rebuys {
+        disp.push_str(&format!("Buy back in {} with the cash kept:", accounts[*index].name));
+        for trade in trades.assets.iter().filter(|t| t.value >= rebalance::MINIMUM_TRADE) {
+            disp.push_str(&format!(" {} {}", trade.class, trade.value.separated_string_with_fixed_place(2)));
+        }
+        disp.push('\n');
+    }
+    if !plan.gifts.is_empty() {
+        let gain: f64 = plan.gifts.iter().map(|g| g.gain()).sum();
+        disp.push_str(&format!("The new purchases reset the cost basis, leaving {} of gain untaxed\n",
+                               gain.separated_string_with_fixed_place(2)));
+        disp.push_str(&format!("Capital gains tax avoided: {}\n", tax_avoided.separated_string_with_fixed_place(2)));
+    }
+    for ticker in &plan.unpriced {
+        disp.push_str(&format!("Warning: no price for {}, its lots were not considered\n", ticker));
+    }
+    disp
+}
+
+#[test]
+fn most_appreciated_long_term_lots_are_given_first() {
+    use crate::jurisdiction::UnitedStates;
+    use crate::tax::Brackets;
+    use crate::transaction::{Transaction, TransactionKind};
+    let buy = |y, m, ticker, class, shares, price| Transaction::with_shares(Date::new(y, m, 1).unwrap(),
+                                                                            TransactionKind::Buy, class, ticker,
+                                                                            shares, price);
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.record(buy(2020, 1, "VTI", AssetClass::Domestic, 10.0, 100.0)).unwrap();
+    taxable.record(buy(2021, 1, "VXUS", AssetClass::International, 10.0, 50.0)).unwrap();
+    taxable.record(buy(2025, 12, "VTI", AssetClass::Domestic, 10.0, 150.0)).unwrap();
+    let date = Date::new(2026, 3, 1).unwrap();
+    let mut prices = PriceTable::new();
+    prices.add("VTI", date, 200.0);
+    prices.add("VXUS", date, 60.0);
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    target.add_asset(Asset::new(AssetClass::International, 50.0));
+    let plan = plan(&[taxable], &prices, &date, 1_000.0, &target, &UnitedStates);
+    // The 2020 VTI lot has doubled; the 2025 VTI lot is short-term
+    assert_eq!(1, plan.gifts.len());
+    assert_eq!("VTI", plan.gifts[0].ticker);
+    assert!((plan.gifts[0].shares - 5.0).abs() < 1e-9);
+    assert!((plan.gifts[0].gain() - 500.0).abs() < 1e-9);
+    assert_eq!(0.0, plan.cash);
+    let rebuy = &plan.rebuys[0].1;
+    assert!((rebuy.get_total_value() - 1_000.0).abs() < 1e-9);
+    assert!((rebuy.get_asset_value(AssetClass::International) - 1_000.0).abs() < 1e-9);
+    let table = TaxTable::new(2026, Brackets::new(vec![(0.0, 0.1)]).unwrap(), 0.0);
+    assert!((tax_avoided(&plan, &table, &Income::default()) - 50.0).abs() < 1e-9);
+}
+
+#[test]
+fn gifts_are_bought_back_in_kind_without_a_target() {
+    use crate::jurisdiction::UnitedStates;
+    use crate::transaction::{Transaction, TransactionKind};
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.record(Transaction::with_shares(Date::new(2020, 1, 1).unwrap(), TransactionKind::Buy, AssetClass::Domestic,
+                                            "VTI", 10.0, 100.0)).unwrap();
+    let date = Date::new(2026, 3, 1).unwrap();
+    let mut prices = PriceTable::new();
+    prices.add("VTI", date, 200.0);
+    let plan = plan(&[taxable], &prices, &date, 1_000.0, &Account::new(AccountType::Allocation),
+                    &UnitedStates);
+    let rebuy = &plan.rebuys[0].1;
+    assert!((rebuy.get_asset_value(AssetClass::Domestic) - 1_000.0).abs() < 1e-9);
+}
+
+#[test]
+fn losses_washed_in_another_account_are_part_of_the_gift_cost() {
+    use crate::jurisdiction::UnitedStates;
+    use crate::transaction::{Transaction, TransactionKind};
+    let trade = |date, kind, price| Transaction::with_shares(date, kind, AssetClass::Domestic, "VTI", 10.0, price);
+    let mut sold = Account::new(AccountType::Taxable);
+    sold.record(trade(Date::new(2020, 1, 1).unwrap(), TransactionKind::Buy, 100.0)).unwrap();
+    sold.record(trade(Date::new(2024, 6, 3).unwrap(), TransactionKind::Sell, 80.0)).unwrap();
+    let mut bought = Account::new(AccountType::Taxable);
+    bought.record(trade(Date::new(2024, 6, 10).unwrap(), TransactionKind::Buy, 80.0)).unwrap();
+    let date = Date::new(2026, 3, 2).unwrap();
+    let mut prices = PriceTable::new();
+    prices.add("VTI", date, 100.0);
+    // The replacement shares carry the $200 washed loss, leaving no gain to give away
+    let plan = plan(&[sold, bought], &prices, &date, 500.0, &Account::new(AccountType::Allocation),
+                    &UnitedStates);
+    assert!(plan.gifts.is_empty());
+    assert_eq!(500.0, plan.cash);
+}
@@ -0,0 +1,185 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::rebalance;
+use crate::tax::{Income, TaxTable};
+
+/// What the planner needs to know about the user's taxes and plans. Rates are fractions and
+/// growth is the yearly return expected on every account.
+pub struct ConversionInputs {
+    pub first_year: i32,
+    /// Ordinary income each year before any conversion and before the deduction
+    pub income: f64,
+    /// Tax rules the conversions are taxed under
+    pub table: TaxTable,
+    /// Conversions fill income up to the top of the bracket taxed at this rate
+    pub fill_rate: f64,
+    /// Rate expected on withdrawals from traditional accounts later on
+    pub projected_rate: f64,
+    pub growth: f64,
+    /// Number of years to convert in
+    pub years: u32,
+    /// Years from now until the money is spent
+    pub horizon: u32,
+}
+
+/// One year's conversion
+pub struct ConversionYear {
+    pub year: i32,
+    pub amount: f64,
+    pub tax: f64,
+    /// Rate on the last dollar converted
+    pub top_rate: f64,
+}
+
+/// Proposed conversions and where they leave the user's accounts
+pub struct ConversionPlan {
+    pub years: Vec<ConversionYear>,
+    /// Accounts at the end of the last conversion year, with the Roth account moved to the end
+    pub accounts: Vec<Account>,
+    /// After-tax wealth at the end of the horizon with the conversions less without them
+    pub benefit: f64,
+}
+
+/// Account types whose balances can be converted to Roth. Inherited IRAs cannot be converted and
+/// 457(b) money must first be rolled into an IRA.
+fn convertible(account_type: &AccountType) -> bool {
+    matches!(account_type, AccountType::Traditional | AccountType::Sep | AccountType::Simple)
+}
+
+/// Converts the amount from the accounts to the Roth account, taking the same share of every
+/// asset class in every convertible account so the household allocation does not change
+fn convert(accounts: &mut [Account], roth: &mut Account, amount: f64) -> Result<(), String> {
+    let total: f64 = accounts.iter()
+        .filter(|a| convertible(&a.classification))
+        .map(|a| a.get_total_value())
+        .sum();
+    if total <= 0.0 {
+        return Ok(());
+    }
+    let share = (amount / total).min(1.0);
+    for account in accounts.iter_mut().filter(|a| convertible(&a.classification)) {
+        let moves: Vec<Asset> = account.assets.iter()
+            .map(|asset| Asset::new(asset.class.clone(), (asset.value * share).min(asset.value)))
+            .filter(|asset| asset.value > 0.0)
+            .collect();
+        for asset in moves {
+            account.move_asset(roth, asset)?;
+        }
+    }
+    Ok(())
+}
+
+/// Proposes a conversion each year that fills income up to the top of the chosen bracket, until
+/// the traditional balances run out. The tax on each conversion is assumed to be paid from money
+/// outside the accounts that would otherwise have grown at the same rate, so the benefit of a
+/// conversion is the withdrawal tax it avoids later less the grown value of the tax paid now.
+pub fn plan(accounts: &[Account], inputs: &ConversionInputs) -> Result<ConversionPlan, String> {
+    let mut accounts: Vec<Account> = accounts.iter().map(|a| a.snapshot()).collect();
+    let mut roth = match accounts.iter().position(|a| a.classification == AccountType::Roth) {
+        Some(index) => accounts.remove(index),
+        None => Account::new(AccountType::Roth),
+    };
+    let room = (inputs.table.top_of_bracket(inputs.fill_rate) - inputs.income).max(0.0);
+    let mut years = Vec::new();
+    let mut benefit = 0.0;
+    for year in 0..inputs.years.min(inputs.horizon) {
+        let available: f64 = accounts.iter()
+            .filter(|a| convertible(&a.classification))
+            .map(|a| a.get_total_value())
+            .sum();
+        let amount = room.min(available);
+        if amount < rebalance::MINIMUM_TRADE {
+            break;
+        }
+        convert(&mut accounts, &mut roth, amount)?;
+        let tax = inputs.table.incremental(&Income::ordinary(inputs.income), &Income::ordinary(amount)).total();
+        let growth = (1.0 + inputs.growth).powi((inputs.horizon - year) as i32);
+        benefit += growth * (amount * inputs.projected_rate - tax);
+        years.push(ConversionYear {
+            year: inputs.first_year + year as i32,
+            amount,
+            tax,
+            top_rate: inputs.table.rate_on_last_dollar(inputs.income + amount),
+        });
+        for account in accounts.iter_mut() {
+            *account = account.multiply(1.0 + inputs.growth);
+        }
+        roth = roth.multiply(1.0 + inputs.growth);
+    }
+    accounts.push(roth);
+    Ok(ConversionPlan { years, accounts, benefit })
+}
+
+/// Lays out each year's conversion and its tax, the balances they leave and what they are worth
+pub fn report(plan: &ConversionPlan, inputs: &ConversionInputs) -> String {
+    if plan.years.is_empty() {
+        return "Nothing to convert: no traditional balances or no room left in the bracket".to_string();
+    }
+    let mut disp = format!("{:<6}{:>16}{:>14}{:>10}\n", "Year", "Convert", "Tax", "Top rate");
+    for year in &plan.years {
+        disp.push_str(&format!("{:<6}{:>16}{:>14}{:>9.0}%\n", year.year,
+                               year.amount.separated_string_with_fixed_place(2),
+                               year.tax.separated_string_with_fixed_place(2), 100.0 * year.top_rate));
+    }
+    let converted: f64 = plan.years.iter().map(|y| y.amount).sum();
+    let tax: f64 = plan.years.iter().map(|y| y.tax).sum();
+    disp.push_str(&format!("{:<6}{:>16}{:>14}\n", "Total", converted.separated_string_with_fixed_place(2),
+                           tax.separated_string_with_fixed_place(2)));
+    let mut traditional = 0.0;
+    let mut roth = 0.0;
+    let mut holdings = Account::new(AccountType::Taxable);
+    for account in &plan.accounts {
+        if convertible(&account.classification) {
+            traditional += account.get_total_value();
+        } else if matches!(account.classification, AccountType::Roth | AccountType::Roth401k) {
+            roth += account.get_total_value();
+        }
+        rebalance::apply(&mut holdings, account);
+    }
+    disp.push_str(&format!("After {}: traditional {}, Roth {}\n", plan.years.last().map(|y| y.year).unwrap_or(0),
+                           traditional.separated_string_with_fixed_place(2), roth.separated_string_with_fixed_place(2)));
+    let total = holdings.get_total_value();
+    if total > 0.0 {
+        disp.push_str("Household allocation:");
+        for class in AssetClass::all() {
+            disp.push_str(&format!(" {} {:.2}%", class.clone(), 100.0 * holdings.get_asset_value(class) / total));
+        }
+        disp.push('\n');
+    }
+    disp.push_str(&format!("Tax is assumed paid from outside the accounts. Against withdrawals taxed at {:.0}%, the conversions {} {} of after-tax wealth by {}\n",
+                           100.0 * inputs.projected_rate, if plan.benefit >= 0.0 { "add" } else { "cost" },
+                           plan.benefit.abs().separated_string_with_fixed_place(2),
+                           inputs.first_year + inputs.horizon as i32));
+    disp
+}
+
+#[test]
+fn conversions_fill_the_bracket_and_keep_the_allocation() {
+    use crate::tax::Brackets;
+    let mut traditional = Account::new(AccountType::Traditional);
+    traditional.add_asset(Asset::new(AssetClass::Domestic, 60_000.0));
+    traditional.add_asset(Asset::new(AssetClass::Bond, 40_000.0));
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.add_asset(Asset::new(AssetClass::Domestic, 50_000.0));
+    let inputs = ConversionInputs {
+        first_year: 2026,
+        income: 30_000.0,
+        table: TaxTable::new(2026, Brackets::new(vec![(0.0, 0.1), (10_000.0, 0.2), (50_000.0, 0.3)]).unwrap(), 0.0),
+        fill_rate: 0.2,
+        projected_rate: 0.3,
+        growth: 0.0,
+        years: 10,
+        horizon: 10,
+    };
+    let plan = plan(&[traditional, taxable], &inputs).unwrap();
+    let amounts: Vec<f64> = plan.years.iter().map(|y| y.amount).collect();
+    assert_eq!(vec![20_000.0; 5], amounts);
+    assert_eq!(4_000.0, plan.years[0].tax);
+    assert!((plan.benefit - 5.0 * 2_000.0).abs() < 1e-6);
+    let roth = plan.accounts.last().unwrap();
+    assert!(roth.classification == AccountType::Roth);
+    assert!((roth.get_asset_value(AssetClass::Bond) - 40_000.0).abs() < 1e-6);
+    assert!(plan.accounts[0].get_total_value() < 1e-6);
+}
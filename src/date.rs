@@ -0,0 +1,123 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A calendar date without a time of day
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Date {
+    /// Creates a Date, checking that the month and day exist
+    pub fn new(year: i32, month: u32, day: u32) -> Result<Date, String> {
+        if !(1..=12).contains(&month) {
+            return Err(format!("Month must be between 1 and 12, but was {}", month));
+        }
+        if day < 1 || day > days_in_month(year, month) {
+            return Err(format!("{}-{:02} does not have a day {}", year, month, day));
+        }
+        Ok(Date { year, month, day })
+    }
+
+    /// Parses a date written as YYYY-MM-DD
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let date = Date::parse("2019-03-31").unwrap();
+    /// assert_eq!(date, Date::new(2019, 3, 31).unwrap());
+    /// ```
+    pub fn parse(text: &str) -> Result<Date, String> {
+        let parts: Vec<&str> = text.trim().split('-').collect();
+        if parts.len() != 3 {
+            return Err(format!("Date should be written as YYYY-MM-DD, but was {}", text.trim()));
+        }
+        let year = parts[0].parse().map_err(|_| format!("Invalid year in date {}", text.trim()))?;
+        let month = parts[1].parse().map_err(|_| format!("Invalid month in date {}", text.trim()))?;
+        let day = parts[2].parse().map_err(|_| format!("Invalid day in date {}", text.trim()))?;
+        Date::new(year, month, day)
+    }
+
+    /// Returns the current date in UTC
+    pub fn today() -> Date {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        Date::from_days((seconds / 86_400) as i64)
+    }
+
+    /// Builds a Date from the number of days since 1970-01-01
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+
+    /// Returns the number of days since 1970-01-01
+    pub fn days_since_epoch(&self) -> i64 {
+        let year = if self.month <= 2 { self.year - 1 } else { self.year } as i64;
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = self.month as i64;
+        let mp = if month > 2 { month - 3 } else { month + 9 };
+        let doy = (153 * mp + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    /// Returns the number of days from this date until the other date
+    pub fn days_until(&self, other: &Date) -> i64 {
+        other.days_since_epoch() - self.days_since_epoch()
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[test]
+fn date_parses_iso_format() {
+    let date = Date::parse(" 2020-02-29\n").unwrap();
+    assert_eq!(date, Date { year: 2020, month: 2, day: 29 });
+    assert_eq!("2020-02-29", format!("{}", date));
+}
+
+#[test]
+fn date_rejects_days_that_do_not_exist() {
+    assert!(Date::parse("2019-02-29").is_err());
+    assert!(Date::parse("2019-13-01").is_err());
+    assert!(Date::parse("March 3rd").is_err());
+}
+
+#[test]
+fn date_round_trips_through_days_since_epoch() {
+    let date = Date::new(2008, 9, 15).unwrap();
+    assert_eq!(date, Date::from_days(date.days_since_epoch()));
+    assert_eq!(0, Date::new(1970, 1, 1).unwrap().days_since_epoch());
+    assert_eq!(366, Date::new(2020, 1, 1).unwrap().days_until(&Date::new(2021, 1, 1).unwrap()));
+}
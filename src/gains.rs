@@ -0,0 +1,419 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use crate::account::Account;
+use crate::asset::AssetClass;
+use crate::date::Date;
+use crate::jurisdiction::Jurisdiction;
+use crate::prices::PriceTable;
+use crate::transaction::{Transaction, TransactionKind};
+
+/// A loss is a wash sale when the same security is bought this many days before or after it
+const WASH_SALE_DAYS: i64 = 30;
+
+/// Whether shares acquired on the first date and sold on the second were held for more than a
+/// year, that is sold after the anniversary of the day they were acquired. Shares acquired on
+/// February 29 reach their anniversary on February 28.
+fn held_over_a_year(acquired: &Date, sold: &Date) -> bool {
+    let anniversary = Date::new(acquired.year + 1, acquired.month, acquired.day)
+        .unwrap_or(Date { year: acquired.year + 1, month: acquired.month, day: acquired.day - 1 });
+    *sold > anniversary
+}
+
+/// Shares of a security acquired together, with what was paid for them. A lot's cost includes
+/// any loss disallowed by a wash sale it replaced, and its acquired date is moved back by how
+/// long the sold shares had been held.
+pub struct Lot {
+    pub ticker: String,
+    pub class: AssetClass,
+    pub acquired: Date,
+    pub shares: f64,
+    pub cost: f64,
+    /// Index of the ledger transaction that opened the lot
+    source: usize,
+    /// Whether the lot's shares replaced shares sold at a loss
+    replacement: bool,
+}
+
+impl Lot {
+    /// Whether the lot has been held long enough by the end of the date for its gain to be
+    /// long-term
+    pub fn is_long_term(&self, date: &Date) -> bool {
+        held_over_a_year(&self.acquired, date)
+    }
+}
+
+/// Shares of one lot sold, as reported on a line of Form 8949
+pub struct Sale {
+    pub ticker: String,
+    pub acquired: Date,
+    pub sold: Date,
+    pub shares: f64,
+    pub proceeds: f64,
+    pub cost: f64,
+    /// Loss that cannot be claimed because the security was bought again within 30 days
+    pub wash_sale: f64,
+}
+
+impl Sale {
+    /// Gain after adding back any disallowed loss, negative for a loss
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost + self.wash_sale
+    }
+
+    pub fn is_long_term(&self) -> bool {
+        held_over_a_year(&self.acquired, &self.sold)
+    }
+
+    fn description(&self) -> String {
+        format!("{:.4} sh {}", self.shares, self.ticker)
+    }
+}
+
+/// The lots left open and the sales made from them
+pub struct Ledger {
+    pub lots: Vec<Lot>,
+    pub sales: Vec<Sale>,
+}
+
+/// Shares of a purchase that replace shares sold at a loss, with the loss disallowed and the
+/// number of days the sold shares had been held
+struct Replacement {
+    shares: f64,
+    disallowed: f64,
+    held: i64,
+}
+
+/// Splits the shares replacing a wash sale off the lot, so they alone carry the disallowed loss
+/// and the holding period of the shares sold
+fn replacement_lot(lot: &mut Lot, replacement: &Replacement) -> Lot {
+    let cost = lot.cost * replacement.shares / lot.shares;
+    lot.shares -= replacement.shares;
+    lot.cost -= cost;
+    Lot {
+        ticker: lot.ticker.clone(),
+        class: lot.class.clone(),
+        acquired: Date::from_days(lot.acquired.days_since_epoch() - replacement.held),
+        shares: replacement.shares,
+        cost: cost + replacement.disallowed,
+        source: lot.source,
+        replacement: true,
+    }
+}
+
+/// Replays an account's ledger up to the end of the date, matching every sale of a security to
+/// its oldest lots first. Purchases and transfers in open lots at the value recorded for them,
+/// and transfers out close lots without realizing a gain. A loss is disallowed, in proportion to
+/// the shares replaced, when the security is bought within 30 days of the sale in lots the sale
+/// did not close. The replacement shares take on the disallowed loss as cost and the holding
+/// period of the shares sold.
+pub fn replay(account: &Account, date: &Date) -> Ledger {
+    replay_accounts(&[account], &[true], date).remove(0)
+}
+
+/// Replays the ledgers of all the accounts together, so a purchase in any of them can make a
+/// loss in an account taxed on growth a wash sale. Losses replaced in accounts not taxed on growth
+/// are lost rather than added to the cost of the replacement shares.
+pub fn replay_all(accounts: &[&Account], date: &Date, jurisdiction: &dyn Jurisdiction) -> Vec<Ledger> {
+    let taxed: Vec<bool> = accounts.iter()
+        .map(|a| jurisdiction.tax_treatment(&a.classification).taxable_growth)
+        .collect();
+    replay_accounts(accounts, &taxed, date)
+}
+
+fn replay_accounts(accounts: &[&Account], taxed: &[bool], date: &Date) -> Vec<Ledger> {
+    let mut ledger: Vec<(usize, usize, &Transaction)> = accounts.iter()
+        .enumerate()
+        .flat_map(|(account, a)| a.transactions().iter().enumerate().map(move |(index, t)| (account, index, t)))
+        .filter(|(_, _, t)| t.date <= *date && t.ticker.is_some())
+        .collect();
+    ledger.sort_by_key(|(_, _, t)| t.date);
+    // Shares of each purchase already used to replace an earlier wash sale, and replacements
+    // waiting for purchases not yet made
+    let mut replaced: Vec<Vec<f64>> = accounts.iter().map(|a| vec![0.0; a.transactions().len()]).collect();
+    let mut deferred: Vec<Vec<Vec<Replacement>>> = accounts.iter()
+        .map(|a| a.transactions().iter().map(|_| Vec::new()).collect())
+        .collect();
+    let mut ledgers: Vec<Ledger> = accounts.iter().map(|_| Ledger { lots: Vec::new(), sales: Vec::new() }).collect();
+    for (account, index, transaction) in &ledger {
+        let ticker = transaction.ticker.clone().unwrap_or_default();
+        if !transaction.kind.is_outflow() {
+            if transaction.shares > 0.0 {
+                let mut lot = Lot {
+                    ticker,
+                    class: transaction.asset.class.clone(),
+                    acquired: transaction.date,
+                    shares: transaction.shares,
+                    cost: transaction.asset.value,
+                    source: *index,
+                    replacement: false,
+                };
+                let lots = &mut ledgers[*account].lots;
+                for replacement in &deferred[*account][*index] {
+                    lots.push(replacement_lot(&mut lot, replacement));
+                }
+                if lot.shares > 1e-9 {
+                    lots.push(lot);
+                }
+            }
+            continue;
+        }
+        let mut remaining = transaction.shares;
+        let mut closed = Vec::new();
+        let Ledger { lots, sales } = &mut ledgers[*account];
+        let first = sales.len();
+        for lot in lots.iter_mut().filter(|l| l.ticker == ticker) {
+            if remaining <= 0.0 {
+                break;
+            }
+            let shares = lot.shares.min(remaining);
+            let cost = lot.cost * shares / lot.shares;
+            lot.shares -= shares;
+            lot.cost -= cost;
+            remaining -= shares;
+            closed.push(lot.source);
+            if transaction.kind == TransactionKind::Sell {
+                sales.push(Sale {
+                    ticker: ticker.clone(),
+                    acquired: lot.acquired,
+                    sold: transaction.date,
+                    shares,
+                    proceeds: transaction.asset.value * shares / transaction.shares,
+                    cost,
+                    wash_sale: 0.0,
+                });
+            }
+        }
+        lots.retain(|l| l.shares > 1e-9);
+        if transaction.kind != TransactionKind::Sell || !taxed[*account] {
+            continue;
+        }
+        let mut losses: Vec<Sale> = ledgers[*account].sales.split_off(first);
+        for sale in losses.iter_mut().filter(|s| s.gain() < 0.0) {
+            let mut unreplaced = sale.shares;
+            for (other, source, purchase) in &ledger {
+                if unreplaced <= 0.0 {
+                    break;
+                }
+                if purchase.kind != TransactionKind::Buy || purchase.ticker.as_ref() != Some(&ticker)
+                    || (other == account && closed.contains(source))
+                    || transaction.date.days_until(&purchase.date).abs() > WASH_SALE_DAYS {
+                    continue;
+                }
+                // Earlier purchases whose shares have all been sold cannot replace the sale
+                let lots = &mut ledgers[*other].lots;
+                let position = lots.iter().position(|l| l.source == *source && !l.replacement);
+                let available = match position {
+                    Some(position) => lots[position].shares.min(purchase.shares - replaced[*other][*source]),
+                    None if purchase.date > transaction.date => purchase.shares - replaced[*other][*source],
+                    None => continue,
+                };
+                let shares = available.min(unreplaced);
+                if shares <= 0.0 {
+                    continue;
+                }
+                let disallowed = -sale.gain() * shares / unreplaced;
+                replaced[*other][*source] += shares;
+                unreplaced -= shares;
+                sale.wash_sale += disallowed;
+                let replacement = Replacement {
+                    shares,
+                    disallowed: if taxed[*other] { disallowed } else { 0.0 },
+                    held: sale.acquired.days_until(&sale.sold),
+                };
+                match position {
+                    Some(position) => {
+                        let lot = replacement_lot(&mut lots[position], &replacement);
+                        lots.insert(position, lot);
+                        lots.retain(|l| l.shares > 1e-9);
+                    }
+                    None => deferred[*other][*source].push(replacement),
+                }
+            }
+        }
+        ledgers[*account].sales.append(&mut losses);
+    }
+    ledgers
+}
+
+/// Returns the sales made during the year from the accounts taxed on growth, in date order. The
+/// ledgers are replayed 30 days into the next year so losses bought back in January are caught as
+/// wash sales, and purchases in every account, tax-advantaged ones included, count as
+/// replacements.
+pub fn realized(accounts: &[&Account], year: i32, jurisdiction: &dyn Jurisdiction) -> Vec<Sale> {
+    let end = Date::from_days(Date { year, month: 12, day: 31 }.days_since_epoch() + WASH_SALE_DAYS);
+    let mut sales: Vec<Sale> = replay_all(accounts, &end, jurisdiction).into_iter()
+        .zip(accounts)
+        .filter(|(_, account)| jurisdiction.tax_treatment(&account.classification).taxable_growth)
+        .flat_map(|(ledger, _)| ledger.sales)
+        .filter(|sale| sale.sold.year == year)
+        .collect();
+    sales.sort_by_key(|sale| sale.sold);
+    sales
+}
+
+/// Lays out the year's sales with short-term and long-term totals
+pub fn realized_report(sales: &[Sale], year: i32) -> String {
+    if sales.is_empty() {
+        return format!("No sales of securities in {}\n", year);
+    }
+    let mut disp = String::new();
+    for (title, long_term) in &[("Short-term", false), ("Long-term", true)] {
+        let part: Vec<&Sale> = sales.iter().filter(|s| s.is_long_term() == *long_term).collect();
+        if part.is_empty() {
+            continue;
+        }
+        disp.push_str(&format!("{} sales in {}\n", title, year));
+        disp.push_str(&format!("{:<22}{:>12}{:>12}{:>14}{:>14}{:>12}{:>14}\n", "Description", "Acquired", "Sold",
+                               "Proceeds", "Cost", "Wash sale", "Gain"));
+        for sale in &part {
+            disp.push_str(&format!("{:<22}{:>12}{:>12}{:>14}{:>14}{:>12}{:>14}\n", sale.description(),
+                                   sale.acquired.to_string(), sale.sold.to_string(),
+                                   sale.proceeds.separated_string_with_fixed_place(2),
+                                   sale.cost.separated_string_with_fixed_place(2),
+                                   sale.wash_sale.separated_string_with_fixed_place(2),
+                                   sale.gain().separated_string_with_fixed_place(2)));
+        }
+        let proceeds: f64 = part.iter().map(|s| s.proceeds).sum();
+        let cost: f64 = part.iter().map(|s| s.cost).sum();
+        let wash_sale: f64 = part.iter().map(|s| s.wash_sale).sum();
+        let gain: f64 = part.iter().map(|s| s.gain()).sum();
+        disp.push_str(&format!("{:<46}{:>14}{:>14}{:>12}{:>14}\n", "Total", proceeds.separated_string_with_fixed_place(2),
+                               cost.separated_string_with_fixed_place(2),
+                               wash_sale.separated_string_with_fixed_place(2),
+                               gain.separated_string_with_fixed_place(2)));
+    }
+    disp
+}
+
+/// Writes the sales as CSV with the columns of Form 8949. Part I holds short-term sales and
+/// Part II long-term ones. Wash sales carry code W and the disallowed loss as the adjustment.
+pub fn form_8949(sales: &[Sale]) -> String {
+    let mut csv = "Part,(a) Description of property,(b) Date acquired,(c) Date sold,(d) Proceeds,\
+                   (e) Cost or other basis,(f) Code,(g) Amount of adjustment,(h) Gain or (loss)\n".to_string();
+    for (part, long_term) in &[("I", false), ("II", true)] {
+        for sale in sales.iter().filter(|s| s.is_long_term() == *long_term) {
+            let (code, adjustment) = if sale.wash_sale > 0.0 {
+                ("W", format!("{:.2}", sale.wash_sale))
+            } else {
+                ("", String::new())
+            };
+            csv.push_str(&format!("{},{},{},{},{:.2},{:.2},{},{},{:.2}\n", part, sale.description(), sale.acquired,
+                                  sale.sold, sale.proceeds, sale.cost, code, adjustment, sale.gain()));
+        }
+    }
+    csv
+}
+
+/// Lays out the gain on every open lot of the account at the end of the date. Lots are valued at
+/// their security's last known price, and at cost when no price is known.
+pub fn unrealized_report(account: &Account, prices: &PriceTable, date: &Date) -> String {
+    let lots = replay(account, date).lots;
+    if lots.is_empty() {
+        return "No open lots\n".to_string();
+    }
+    let mut disp = format!("{:<8}{:>12}{:>12}{:>14}{:>14}{:>14}  {}\n", "Ticker", "Acquired", "Shares", "Cost", "Value",
+                           "Gain", "Term");
+    let mut totals = (0.0, 0.0);
+    let mut missing = Vec::new();
+    for lot in &lots {
+        let value = match prices.price_on(&lot.ticker, date) {
+            Some(quote) => lot.shares * quote.close,
+            None => {
+                if !missing.contains(&lot.ticker) {
+                    missing.push(lot.ticker.clone());
+                }
+                lot.cost
+            }
+        };
+        let term = if lot.is_long_term(date) { "Long" } else { "Short" };
+        disp.push_str(&format!("{:<8}{:>12}{:>12.4}{:>14}{:>14}{:>14}  {}\n", lot.ticker, lot.acquired.to_string(),
+                               lot.shares, lot.cost.separated_string_with_fixed_place(2),
+                               value.separated_string_with_fixed_place(2),
+                               (value - lot.cost).separated_string_with_fixed_place(2), term));
+        totals.0 += lot.cost;
+        totals.1 += value;
+    }
+    disp.push_str(&format!("{:<32}{:>14}{:>14}{:>14}\n", "Total", totals.0.separated_string_with_fixed_place(2),
+                           totals.1.separated_string_with_fixed_place(2),
+                           (totals.1 - totals.0).separated_string_with_fixed_place(2)));
+    for ticker in missing {
+        disp.push_str(&format!("Warning: no price for {} on or before {}, valued at cost\n", ticker, date));
+    }
+    disp
+}
+
+#[test]
+fn sales_match_oldest_lots_and_split_by_term() {
+    use crate::account::AccountType;
+    use crate::jurisdiction::UnitedStates;
+    let mut account = Account::new(AccountType::Taxable);
+    let buy = |y, m, d, shares, price| Transaction::with_shares(Date::new(y, m, d).unwrap(), TransactionKind::Buy,
+                                                                 AssetClass::Domestic, "VTI", shares, price);
+    account.record(buy(2024, 3, 1, 10.0, 100.0)).unwrap();
+    account.record(buy(2025, 6, 1, 10.0, 150.0)).unwrap();
+    account.record(Transaction::with_shares(Date::new(2025, 9, 1).unwrap(), TransactionKind::Sell,
+                                            AssetClass::Domestic, "VTI", 15.0, 160.0)).unwrap();
+    let sales = realized(&[&account], 2025, &UnitedStates);
+    assert_eq!(2, sales.len());
+    assert!(sales[0].is_long_term());
+    assert_eq!(600.0, sales[0].gain());
+    assert!(!sales[1].is_long_term());
+    assert_eq!(5.0, sales[1].shares);
+    assert_eq!(50.0, sales[1].gain());
+    let lots = replay(&account, &Date::new(2025, 12, 31).unwrap()).lots;
+    assert_eq!(1, lots.len());
+    assert_eq!(750.0, lots[0].cost);
+    assert!(realized(&[&account], 2024, &UnitedStates).is_empty());
+}
+
+#[test]
+fn wash_sales_defer_the_loss_to_the_replacement_lot() {
+    use crate::account::AccountType;
+    use crate::jurisdiction::UnitedStates;
+    let mut account = Account::new(AccountType::Taxable);
+    let trade = |m, d, kind, shares, price| Transaction::with_shares(Date::new(2025, m, d).unwrap(), kind,
+                                                                      AssetClass::Bond, "BND", shares, price);
+    account.record(trade(1, 2, TransactionKind::Buy, 10.0, 80.0)).unwrap();
+    account.record(trade(5, 1, TransactionKind::Sell, 10.0, 70.0)).unwrap();
+    account.record(trade(5, 20, TransactionKind::Buy, 4.0, 72.0)).unwrap();
+    let sales = realized(&[&account], 2025, &UnitedStates);
+    assert_eq!(1, sales.len());
+    assert!((sales[0].wash_sale - 40.0).abs() < 1e-9);
+    assert!((sales[0].gain() + 60.0).abs() < 1e-9);
+    let lots = replay(&account, &Date::new(2025, 12, 31).unwrap()).lots;
+    assert!((lots[0].cost - (288.0 + 40.0)).abs() < 1e-9);
+    // The 119 days the sold shares were held carry over to the replacement
+    assert_eq!(Date::new(2025, 1, 21).unwrap(), lots[0].acquired);
+    let csv = form_8949(&sales);
+    assert!(csv.lines().nth(1).unwrap().starts_with("I,10.0000 sh BND,2025-01-02,2025-05-01,700.00,800.00,W,40.00,-60.00"));
+}
+
+#[test]
+fn long_term_starts_after_the_anniversary() {
+    let acquired = Date::new(2023, 3, 1).unwrap();
+    assert!(!held_over_a_year(&acquired, &Date::new(2024, 3, 1).unwrap()));
+    assert!(held_over_a_year(&acquired, &Date::new(2024, 3, 2).unwrap()));
+    let leap_day = Date::new(2024, 2, 29).unwrap();
+    assert!(!held_over_a_year(&leap_day, &Date::new(2025, 2, 28).unwrap()));
+    assert!(held_over_a_year(&leap_day, &Date::new(2025, 3, 1).unwrap()));
+}
+
+#[test]
+fn december_losses_bought_back_in_january_in_an_ira_are_wash_sales() {
+    use crate::account::AccountType;
+    use crate::jurisdiction::UnitedStates;
+    let trade = |date, kind, shares, price| Transaction::with_shares(date, kind, AssetClass::Domestic, "VTI", shares,
+                                                                     price);
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.record(trade(Date::new(2025, 6, 2).unwrap(), TransactionKind::Buy, 10.0, 100.0)).unwrap();
+    taxable.record(trade(Date::new(2025, 12, 15).unwrap(), TransactionKind::Sell, 10.0, 80.0)).unwrap();
+    let mut ira = Account::new(AccountType::Traditional);
+    ira.record(trade(Date::new(2026, 1, 5).unwrap(), TransactionKind::Buy, 10.0, 82.0)).unwrap();
+    assert_eq!(-200.0, realized(&[&taxable], 2025, &UnitedStates)[0].gain());
+    let sales = realized(&[&taxable, &ira], 2025, &UnitedStates);
+    assert_eq!(1, sales.len());
+    assert_eq!(200.0, sales[0].wash_sale);
+    // The loss is lost for good rather than added to the IRA's shares
+    let ledgers = replay_all(&[&taxable, &ira], &Date::new(2026, 1, 31).unwrap(), &UnitedStates);
+    assert_eq!(820.0, ledgers[1].lots.iter().map(|l| l.cost).sum::<f64>());
+}
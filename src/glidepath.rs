@@ -0,0 +1,146 @@
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+
+/// What the points of a glide path are keyed by
+#[derive(Clone, PartialEq)]
+pub enum Basis {
+    Age,
+    Year,
+}
+
+/// A target allocation to hold at an age or in a year
+pub struct GlidePoint {
+    pub at: f64,
+    pub allocation: Account,
+}
+
+/// A target allocation that moves between points as the user ages or the years pass. Between
+/// two points the target is interpolated, before the first and after the last it stays put.
+pub struct GlidePath {
+    pub basis: Basis,
+    points: Vec<GlidePoint>,
+}
+
+impl GlidePath {
+    pub fn new(basis: Basis) -> GlidePath {
+        GlidePath {
+            basis,
+            points: Vec::new(),
+        }
+    }
+
+    /// Adds a point to the path. The allocation must add up to 100.
+    pub fn add_point(&mut self, at: f64, allocation: Account) -> Result<(), String> {
+        if !allocation.is_full_allocation() {
+            return Err(format!("Allocation at {} should be 100.00, but was {}", at, allocation.get_total_value()));
+        }
+        self.points.retain(|p| p.at != at);
+        let position = self.points.iter().position(|p| p.at > at).unwrap_or(self.points.len());
+        self.points.insert(position, GlidePoint { at, allocation });
+        Ok(())
+    }
+
+    /// Loads a glide path from a CSV file
+    pub fn load(path: &str) -> Result<GlidePath, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read glide path file {}: {}", path, why))?;
+        GlidePath::parse(&text)
+    }
+
+    /// Parses CSV text whose header starts with "age" or "year" followed by asset classes, and
+    /// whose rows hold the percentage of each asset class at that age or year
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let path = GlidePath::parse("age,domestic,bond\n30,90,10\n65,40,60\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<GlidePath, String> {
+        let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+        let header: Vec<&str> = match lines.next() {
+            Some((_, header)) => header.split(',').map(|f| f.trim()).collect(),
+            None => return Err("Glide path file is empty".to_string()),
+        };
+        let basis = match header[0].to_lowercase().as_str() {
+            "age" => Basis::Age,
+            "year" => Basis::Year,
+            _ => return Err("First column of a glide path must be age or year".to_string()),
+        };
+        let classes = header[1..].iter()
+            .map(|name| AssetClass::parse(name))
+            .collect::<Result<Vec<AssetClass>, String>>()?;
+        let mut path = GlidePath::new(basis);
+        for (number, line) in lines {
+            let fields = line.split(',')
+                .map(|f| f.trim().parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f.trim())))
+                .collect::<Result<Vec<f64>, String>>()?;
+            if fields.len() != classes.len() + 1 {
+                return Err(format!("Line {}: expected {} columns but found {}", number + 1,
+                                   classes.len() + 1, fields.len()));
+            }
+            let mut allocation = Account::new(AccountType::Allocation);
+            for (class, percent) in classes.iter().zip(&fields[1..]) {
+                allocation.add_asset(Asset::new(class.clone(), *percent));
+            }
+            path.add_point(fields[0], allocation).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+        }
+        if path.points.is_empty() {
+            return Err("Glide path has no points".to_string());
+        }
+        Ok(path)
+    }
+
+    /// Returns the target allocation at an age or year, or nothing if the path has no points
+    pub fn target_at(&self, at: f64) -> Option<Account> {
+        let after = self.points.iter().position(|p| p.at > at);
+        let (before, after) = match after {
+            Some(0) => return Some(self.points[0].allocation.snapshot()),
+            Some(index) => (&self.points[index - 1], &self.points[index]),
+            None => return self.points.last().map(|p| p.allocation.snapshot()),
+        };
+        let fraction = (at - before.at) / (after.at - before.at);
+        let mut target = Account::new(AccountType::Allocation);
+        for class in AssetClass::all() {
+            let start = before.allocation.get_asset_value(class.clone());
+            let end = after.allocation.get_asset_value(class.clone());
+            target.add_asset(Asset::new(class, start + (end - start) * fraction));
+        }
+        Some(target)
+    }
+
+    /// Returns the target allocation for a date. Age based paths need the user's birth date.
+    pub fn target_on(&self, date: &Date, birth_date: Option<&Date>) -> Option<Account> {
+        let at = match self.basis {
+            Basis::Age => birth_date?.days_until(date) as f64 / 365.25,
+            Basis::Year => date.year as f64 + (date.days_since_epoch()
+                - Date { year: date.year, month: 1, day: 1 }.days_since_epoch()) as f64 / 365.25,
+        };
+        self.target_at(at)
+    }
+}
+
+#[test]
+fn glide_path_interpolates_between_points() {
+    let path = GlidePath::parse("age,domestic,bond\n30,90,10\n60,30,70\n").unwrap();
+    let target = path.target_at(45.0).unwrap();
+    assert_eq!(60.0, target.get_asset_value(AssetClass::Domestic));
+    assert_eq!(40.0, target.get_asset_value(AssetClass::Bond));
+    assert_eq!(100.0, target.get_total_value());
+}
+
+#[test]
+fn glide_path_holds_ends_flat() {
+    let path = GlidePath::parse("year,domestic,bond\n2030,80,20\n2040,50,50\n").unwrap();
+    assert_eq!(80.0, path.target_at(2000.0).unwrap().get_asset_value(AssetClass::Domestic));
+    assert_eq!(50.0, path.target_at(2050.0).unwrap().get_asset_value(AssetClass::Domestic));
+    let mid = path.target_on(&Date::new(2035, 1, 1).unwrap(), None).unwrap();
+    assert_eq!(65.0, mid.get_asset_value(AssetClass::Domestic));
+}
+
+#[test]
+fn glide_path_points_must_sum_to_100() {
+    assert!(GlidePath::parse("age,domestic,bond\n30,90,20\n").is_err());
+    assert!(GlidePath::parse("height,domestic,bond\n30,90,10\n").is_err());
+}
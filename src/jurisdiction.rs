@@ -0,0 +1,258 @@
+use std::io;
+use crate::account::{Access, AccountType, TaxTreatment};
+use crate::date::Date;
+use crate::limits::{ContributionLimits, Limit};
+use crate::tax::{Brackets, TaxTable, TaxTables};
+use crate::waterfall::{Step, Waterfall};
+
+/// A country's tax rules as far as the tool needs them: which account wrappers exist, what they are
+/// called and how they are taxed, their contribution limits, when the tax year starts and the
+/// usual order to fill them in. Reports name and treat accounts through the active jurisdiction,
+/// so placement advice follows whichever wrappers it offers.
+pub trait Jurisdiction {
+    fn name(&self) -> &'static str;
+
+    /// Account wrappers that can be set up, in the order they are offered
+    fn account_types(&self) -> Vec<AccountType>;
+
+    /// Returns what the wrapper is called locally
+    fn account_name(&self, account_type: &AccountType) -> String {
+        account_type.to_string()
+    }
+
+    /// Returns how the wrapper is taxed
+    fn tax_treatment(&self, account_type: &AccountType) -> TaxTreatment {
+        standard_treatment(account_type)
+    }
+
+    /// Month and day the tax year starts on
+    fn tax_year_start(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    /// Returns the tax year a date falls in, named by the calendar year it starts in
+    fn tax_year(&self, date: &Date) -> i32 {
+        let (month, day) = self.tax_year_start();
+        if (date.month, date.day) < (month, day) { date.year - 1 } else { date.year }
+    }
+
+    /// Contribution limits to use when no file is given
+    fn contribution_limits(&self) -> ContributionLimits;
+
+    /// Order to fill accounts in when no waterfall file is given, taking the amount an employer
+    /// matches first where the jurisdiction's employer plans are matched
+    fn waterfall(&self, employer_match: f64) -> Waterfall;
+
+    /// Tax tables to use when no file is given
+    fn tax_tables(&self) -> TaxTables;
+}
+
+pub struct UnitedStates;
+
+impl Jurisdiction for UnitedStates {
+    fn name(&self) -> &'static str {
+        "United States"
+    }
+
+    fn account_types(&self) -> Vec<AccountType> {
+        vec![AccountType::Taxable, AccountType::Plan401k, AccountType::Roth401k, AccountType::Traditional,
+             AccountType::Roth, AccountType::Hsa, AccountType::Education529, AccountType::Sep, AccountType::Simple, AccountType::InheritedIra,
+             AccountType::Deferred457]
+    }
+
+    fn contribution_limits(&self) -> ContributionLimits {
+        ContributionLimits::standard()
+    }
+
+    fn waterfall(&self, employer_match: f64) -> Waterfall {
+        Waterfall::standard(employer_match)
+    }
+
+    fn tax_tables(&self) -> TaxTables {
+        TaxTables::standard()
+    }
+}
+
+/// The UK's tax year starts on 6 April. The SIPP limit is the annual allowance before any taper
+/// for high incomes. Income tax uses the rest of UK rates with the personal allowance as the
+/// deduction, not tapered for high incomes, and gains are taxed at the capital gains rates
+/// without the annual exempt amount.
+pub struct UnitedKingdom;
+
+impl Jurisdiction for UnitedKingdom {
+    fn name(&self) -> &'static str {
+        "United Kingdom"
+    }
+
+    fn account_types(&self) -> Vec<AccountType> {
+        vec![AccountType::Taxable, AccountType::Isa, AccountType::Sipp]
+    }
+
+    fn account_name(&self, account_type: &AccountType) -> String {
+        match account_type {
+            AccountType::Taxable => "General Investment Account".to_string(),
+            _ => account_type.to_string(),
+        }
+    }
+
+    fn tax_year_start(&self) -> (u32, u32) {
+        (4, 6)
+    }
+
+    fn contribution_limits(&self) -> ContributionLimits {
+        let mut limits = Vec::new();
+        for year in 2023..=2026 {
+            limits.push(flat_limit(year, AccountType::Isa, 20_000.0));
+            limits.push(flat_limit(year, AccountType::Sipp, 60_000.0));
+        }
+        ContributionLimits::new(limits).starting(self.tax_year_start())
+    }
+
+    fn waterfall(&self, _employer_match: f64) -> Waterfall {
+        steps(&[AccountType::Sipp, AccountType::Isa, AccountType::Taxable])
+    }
+
+    fn tax_tables(&self) -> TaxTables {
+        tax_table(2026, &[(0.0, 0.20), (37_700.0, 0.40), (112_570.0, 0.45)], 12_570.0,
+                  &[(0.0, 0.18), (37_700.0, 0.24)])
+    }
+}
+
+/// Canada's tax year is the calendar year. The RRSP limit is the dollar cap, the share of earned
+/// income it is also limited to and unused TFSA room carried forward are left out. Income tax is
+/// federal only, with the basic personal amount taken as a deduction, and half of each gain is
+/// taxed as income.
+pub struct Canada;
+
+impl Jurisdiction for Canada {
+    fn name(&self) -> &'static str {
+        "Canada"
+    }
+
+    fn account_types(&self) -> Vec<AccountType> {
+        vec![AccountType::Taxable, AccountType::Tfsa, AccountType::Rrsp]
+    }
+
+    fn account_name(&self, account_type: &AccountType) -> String {
+        match account_type {
+            AccountType::Taxable => "Non-registered Account".to_string(),
+            _ => account_type.to_string(),
+        }
+    }
+
+    fn contribution_limits(&self) -> ContributionLimits {
+        let mut limits = Vec::new();
+        for (year, tfsa, rrsp) in [
+            (2023, 6_500.0, 30_780.0),
+            (2024, 7_000.0, 31_560.0),
+            (2025, 7_000.0, 32_490.0),
+            (2026, 7_000.0, 33_810.0),
+        ].iter() {
+            limits.push(flat_limit(*year, AccountType::Tfsa, *tfsa));
+            limits.push(flat_limit(*year, AccountType::Rrsp, *rrsp));
+        }
+        ContributionLimits::new(limits)
+    }
+
+    fn waterfall(&self, _employer_match: f64) -> Waterfall {
+        steps(&[AccountType::Tfsa, AccountType::Rrsp, AccountType::Taxable])
+    }
+
+    fn tax_tables(&self) -> TaxTables {
+        let ordinary = [(0.0, 0.14), (58_523.0, 0.205), (117_045.0, 0.26), (181_440.0, 0.29), (258_482.0, 0.33)];
+        let gains: Vec<(f64, f64)> = ordinary.iter().map(|(start, rate)| (*start, rate / 2.0)).collect();
+        tax_table(2026, &ordinary, 16_452.0, &gains)
+    }
+}
+
+/// Returns how an account type is usually taxed. Allocations are not accounts and are treated as
+/// taxable.
+fn standard_treatment(account_type: &AccountType) -> TaxTreatment {
+    let (pre_tax_contributions, taxable_growth, taxed_withdrawals, accepts_contributions,
+         required_distributions, access) = match account_type {
+        AccountType::Taxable | AccountType::Allocation => (false, true, false, true, false, Access::Anytime),
+        AccountType::Traditional | AccountType::Plan401k | AccountType::Sep | AccountType::Simple =>
+            (true, false, true, true, true, Access::FromAge(59.5)),
+        AccountType::Roth | AccountType::Roth401k => (false, false, false, true, false, Access::FromAge(59.5)),
+        // After 65 other withdrawals are only taxed as income, not penalized
+        AccountType::Hsa => (true, false, false, true, false, Access::QualifiedExpenses("medical expenses")),
+        AccountType::Education529 =>
+            (false, false, false, true, false, Access::QualifiedExpenses("education expenses")),
+        AccountType::InheritedIra => (true, false, true, false, true, Access::Anytime),
+        // Withdrawals after leaving the employer are not penalized at any age
+        AccountType::Deferred457 => (true, false, true, true, true, Access::Anytime),
+        AccountType::Isa | AccountType::Tfsa => (false, false, false, true, false, Access::Anytime),
+        AccountType::Sipp => (true, false, true, true, false, Access::FromAge(55.0)),
+        // Withdrawals are allowed at any time, and minimum withdrawals start once it becomes a RRIF
+        AccountType::Rrsp => (true, false, true, true, true, Access::Anytime),
+    };
+    TaxTreatment {
+        pre_tax_contributions,
+        taxable_growth,
+        taxed_withdrawals,
+        accepts_contributions,
+        required_distributions,
+        access,
+    }
+}
+
+fn flat_limit(year: i32, account_type: AccountType, amount: f64) -> Limit {
+    Limit { year, types: vec![account_type], amount, catch_up_age: 0, catch_up: 0.0, phase_out: None,
+            year_start: (1, 1) }
+}
+
+fn tax_table(year: i32, ordinary: &[(f64, f64)], deduction: f64, gains: &[(f64, f64)]) -> TaxTables {
+    let mut table = TaxTable::new(year, Brackets { rates: ordinary.to_vec() }, deduction);
+    table.gains = Brackets { rates: gains.to_vec() };
+    TaxTables { tables: vec![table] }
+}
+
+fn steps(account_types: &[AccountType]) -> Waterfall {
+    Waterfall {
+        steps: account_types.iter().map(|t| Step { account_type: t.clone(), cap: None }).collect(),
+    }
+}
+
+/// Returns every jurisdiction the tool knows about
+pub fn all() -> Vec<Box<dyn Jurisdiction>> {
+    vec![Box::new(UnitedStates), Box::new(UnitedKingdom), Box::new(Canada)]
+}
+
+/// Ask user of the program which country's accounts and tax rules to use
+pub fn request_jurisdiction() -> Box<dyn Jurisdiction> {
+    let mut jurisdictions = all();
+    loop {
+        println!("Which country's accounts do you hold?");
+        for (i, jurisdiction) in jurisdictions.iter().enumerate() {
+            print!("{}. {}\t", i + 1, jurisdiction.name());
+        }
+        println!();
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        let choice: usize = choice.trim().parse().unwrap_or(0);
+        if (1..=jurisdictions.len()).contains(&choice) {
+            return jurisdictions.swap_remove(choice - 1);
+        }
+    }
+}
+
+#[test]
+fn account_types_carry_their_tax_treatment() {
+    assert!(AccountType::parse("529").unwrap() == AccountType::Education529);
+    assert_eq!("Tax-free", UnitedStates.tax_treatment(&AccountType::Hsa).bucket());
+    assert_eq!("Tax-deferred", UnitedStates.tax_treatment(&AccountType::parse("457(b)").unwrap()).bucket());
+    assert!(!UnitedStates.tax_treatment(&AccountType::InheritedIra).accepts_contributions);
+    assert_eq!("General Investment Account", UnitedKingdom.account_name(&AccountType::Taxable));
+}
+
+#[test]
+fn uk_tax_year_starts_in_april() {
+    let uk = UnitedKingdom;
+    assert_eq!(2025, uk.tax_year(&Date::new(2026, 4, 5).unwrap()));
+    assert_eq!(2026, uk.tax_year(&Date::new(2026, 4, 6).unwrap()));
+    assert_eq!(2026, Canada.tax_year(&Date::new(2026, 1, 1).unwrap()));
+    let limits = uk.contribution_limits();
+    assert_eq!(20_000.0, limits.for_account(&AccountType::Isa, 2026)[0].amount_for(None, None));
+    assert!(limits.for_account(&AccountType::Roth, 2026).is_empty());
+}
@@ -0,0 +1,339 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::date::Date;
+use crate::jurisdiction::Jurisdiction;
+use crate::transaction::TransactionKind;
+
+/// Smallest reduced limit allowed while income is inside a phase-out range
+const MINIMUM_PHASED_LIMIT: f64 = 200.0;
+
+/// The most that can be contributed in a tax year to the account types sharing the limit, the
+/// extra catch-up amount allowed from the year the user reaches the catch-up age, and the range of
+/// income over which the limit shrinks to nothing
+pub struct Limit {
+    pub year: i32,
+    pub types: Vec<AccountType>,
+    pub amount: f64,
+    pub catch_up_age: i32,
+    pub catch_up: f64,
+    pub phase_out: Option<(f64, f64)>,
+    /// Month and day the tax year starts on
+    pub year_start: (u32, u32),
+}
+
+impl Limit {
+    /// Names the account types sharing the limit as the jurisdiction calls them
+    pub fn name(&self, jurisdiction: &dyn Jurisdiction) -> String {
+        self.types.iter().map(|t| jurisdiction.account_name(t)).collect::<Vec<String>>().join(" + ")
+    }
+
+    /// Returns the limit for a user who is the given age at the end of the tax year and earns the
+    /// given income. Without an age the catch-up amount is left out, without an income the
+    /// phase-out is ignored. Inside the phase-out range the limit shrinks in proportion, rounded
+    /// up to the next $10 and never below $200.
+    pub fn amount_for(&self, age: Option<i32>, income: Option<f64>) -> f64 {
+        let amount = match age {
+            Some(age) if age >= self.catch_up_age => self.amount + self.catch_up,
+            _ => self.amount,
+        };
+        match (self.phase_out, income) {
+            (Some((_, end)), Some(income)) if income >= end => 0.0,
+            (Some((start, end)), Some(income)) if income > start => {
+                let reduced = (amount * (end - income) / (end - start) / 10.0).ceil() * 10.0;
+                reduced.max(MINIMUM_PHASED_LIMIT)
+            }
+            _ => amount,
+        }
+    }
+
+    /// Returns how much was contributed in a tax year to the accounts the limit covers
+    pub fn contributed(&self, accounts: &[Account], year: i32) -> f64 {
+        accounts.iter()
+            .filter(|account| self.types.contains(&account.classification))
+            .flat_map(|account| account.transactions())
+            .filter(|t| t.kind == TransactionKind::Contribution && self.tax_year(&t.date) == year)
+            .map(|t| t.asset.value)
+            .sum()
+    }
+
+    /// Returns the tax year a date falls in, named by the calendar year it starts in
+    pub fn tax_year(&self, date: &Date) -> i32 {
+        if (date.month, date.day) < self.year_start { date.year - 1 } else { date.year }
+    }
+
+    /// Returns how much more can be contributed in a tax year
+    pub fn room(&self, accounts: &[Account], year: i32, age: Option<i32>, income: Option<f64>) -> f64 {
+        (self.amount_for(age, income) - self.contributed(accounts, year)).max(0.0)
+    }
+}
+
+/// Who is saving and when: the tax year, the age they turn that year and their income, which
+/// together decide how much room each contribution limit leaves
+pub struct Contributor {
+    pub year: i32,
+    pub age: Option<i32>,
+    pub income: Option<f64>,
+}
+
+/// Annual contribution limits for each tax year. Account types without a limit, such as taxable
+/// accounts, take any amount. An account type may fall under several limits, such as a limit it
+/// shares with other account types and a phase-out of its own, and must stay within all of them.
+pub struct ContributionLimits {
+    limits: Vec<Limit>,
+}
+
+impl ContributionLimits {
+    pub fn new(limits: Vec<Limit>) -> ContributionLimits {
+        ContributionLimits { limits }
+    }
+
+    /// Starts every limit's tax year on the month and day
+    pub fn starting(mut self, year_start: (u32, u32)) -> ContributionLimits {
+        for limit in &mut self.limits {
+            limit.year_start = year_start;
+        }
+        self
+    }
+
+    /// Limits to use when no file is given, for a single filer with self-only HSA coverage.
+    /// Deferrals to 401(k) plans share one limit and contributions to traditional and Roth IRAs
+    /// share another, with the Roth IRA also phased out by income. The SEP limit is the dollar
+    /// cap, the share of compensation it is also limited to is left out.
+    pub fn standard() -> ContributionLimits {
+        let mut limits = Vec::new();
+        let mut add = |year: i32, types: &[AccountType], amount: f64, catch_up_age: i32, catch_up: f64,
+                       phase_out: Option<(f64, f64)>| {
+            limits.push(Limit { year, types: types.to_vec(), amount, catch_up_age, catch_up, phase_out,
+                                year_start: (1, 1) });
+        };
+        for (year, plan, plan_catch_up, ira, ira_catch_up, roth_phase_out) in [
+            (2023, 22_500.0, 7_500.0, 6_500.0, 1_000.0, (138_000.0, 153_000.0)),
+            (2024, 23_000.0, 7_500.0, 7_000.0, 1_000.0, (146_000.0, 161_000.0)),
+            (2025, 23_500.0, 7_500.0, 7_000.0, 1_000.0, (150_000.0, 165_000.0)),
+            (2026, 24_500.0, 8_000.0, 7_500.0, 1_100.0, (153_000.0, 168_000.0)),
+        ].iter() {
+            add(*year, &[AccountType::Plan401k, AccountType::Roth401k], *plan, 50, *plan_catch_up, None);
+            add(*year, &[AccountType::Deferred457], *plan, 50, *plan_catch_up, None);
+            add(*year, &[AccountType::Traditional, AccountType::Roth], *ira, 50, *ira_catch_up, None);
+            add(*year, &[AccountType::Roth], *ira, 50, *ira_catch_up, Some(*roth_phase_out));
+        }
+        for (year, hsa, simple, simple_catch_up, sep) in [
+            (2023, 3_850.0, 15_500.0, 3_500.0, 66_000.0),
+            (2024, 4_150.0, 16_000.0, 3_500.0, 69_000.0),
+            (2025, 4_300.0, 16_500.0, 3_500.0, 70_000.0),
+            (2026, 4_400.0, 17_000.0, 4_000.0, 72_000.0),
+        ].iter() {
+            add(*year, &[AccountType::Hsa], *hsa, 55, 1_000.0, None);
+            add(*year, &[AccountType::Simple], *simple, 50, *simple_catch_up, None);
+            add(*year, &[AccountType::Sep], *sep, 50, 0.0, None);
+        }
+        ContributionLimits { limits }
+    }
+
+    /// Loads limits from a CSV file
+    pub fn load(path: &str) -> Result<ContributionLimits, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read contribution limit file {}: {}", path, why))?;
+        ContributionLimits::parse(&text)
+    }
+
+    /// Parses lines of year,account types,limit,catch-up age,catch-up, optionally followed by the
+    /// income where the phase-out starts and ends. Account types that share a limit are joined
+    /// with a +, and an account type may appear in more than one limit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let limits = ContributionLimits::parse("year,accounts,limit,catch_up_age,catch_up\n\
+    ///                                         2024,traditional+roth,7000,50,1000\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<ContributionLimits, String> {
+        let mut limits: Vec<Limit> = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 5 && fields.len() != 7 {
+                return Err(format!("Line {}: expected year,accounts,limit,catch_up_age,catch_up[,phase_out_start,phase_out_end] but found {}",
+                                   number + 1, line));
+            }
+            if number == 0 && fields[0].eq_ignore_ascii_case("year") {
+                continue;
+            }
+            let year: i32 = fields[0].parse().map_err(|_| format!("Line {}: invalid year {}", number + 1, fields[0]))?;
+            let types = fields[1].split('+')
+                .map(AccountType::parse)
+                .collect::<Result<Vec<AccountType>, String>>()
+                .map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            let numbers = fields[2..].iter()
+                .map(|f| f.parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            if numbers.iter().any(|n| *n < 0.0) {
+                return Err(format!("Line {}: limits cannot be negative", number + 1));
+            }
+            if limits.iter().any(|l| l.year == year && l.types.len() == types.len()
+                                 && l.types.iter().all(|t| types.contains(t))) {
+                return Err(format!("Line {}: the same account types can only have one limit per year", number + 1));
+            }
+            let phase_out = match numbers.get(3..5) {
+                Some([start, end]) if start < end => Some((*start, *end)),
+                Some(_) => return Err(format!("Line {}: the phase-out must start below where it ends", number + 1)),
+                None => None,
+            };
+            limits.push(Limit { year, types, amount: numbers[0], catch_up_age: numbers[1] as i32, catch_up: numbers[2],
+                                phase_out, year_start: (1, 1) });
+        }
+        Ok(ContributionLimits { limits })
+    }
+
+    /// Returns the limits on an account type in a tax year. Years after the last configured year
+    /// use the last configured year's amounts.
+    pub fn for_account(&self, account_type: &AccountType, year: i32) -> Vec<&Limit> {
+        let latest = self.limits.iter()
+            .filter(|l| l.year <= year && l.types.contains(account_type))
+            .map(|l| l.year)
+            .max();
+        self.limits.iter()
+            .filter(|l| Some(l.year) == latest && l.types.contains(account_type))
+            .collect()
+    }
+
+    /// Returns the limit on an account type in a tax year that leaves the least room, and that room
+    pub fn tightest(&self, accounts: &[Account], account_type: &AccountType, year: i32, age: Option<i32>,
+                    income: Option<f64>) -> Option<(&Limit, f64)> {
+        self.for_account(account_type, year).into_iter()
+            .map(|limit| (limit, limit.room(accounts, year, age, income)))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+    }
+
+    /// Returns the limits that apply in a tax year
+    pub fn for_year(&self, year: i32) -> Vec<&Limit> {
+        let mut found: Vec<&Limit> = Vec::new();
+        for limit in &self.limits {
+            for limit in limit.types.iter().flat_map(|t| self.for_account(t, year)) {
+                if !found.iter().any(|f| std::ptr::eq(*f, limit)) {
+                    found.push(limit);
+                }
+            }
+        }
+        found
+    }
+
+    /// Returns a warning if the contributor putting the amount in an account type would go over its
+    /// limit for the tax year, or if the account type does not take contributions at all
+    pub fn check(&self, accounts: &[Account], account_type: &AccountType, amount: f64, contributor: &Contributor,
+                 jurisdiction: &dyn Jurisdiction) -> Option<String> {
+        let Contributor { year, age, income } = *contributor;
+        let name = jurisdiction.account_name(account_type);
+        if !jurisdiction.tax_treatment(account_type).accepts_contributions {
+            return Some(format!("Warning: {} accounts do not accept contributions", name));
+        }
+        let (limit, room) = self.tightest(accounts, account_type, year, age, income)?;
+        if amount <= room {
+            return None;
+        }
+        Some(format!("Warning: contributing {} to {} would exceed the {} {} limit of {} by {}",
+                     amount.separated_string_with_fixed_place(2), name, year, limit.name(jurisdiction),
+                     limit.amount_for(age, income).separated_string_with_fixed_place(2),
+                     (amount - room).separated_string_with_fixed_place(2)))
+    }
+}
+
+/// Shows how much of each limit has been used in a tax year and how much room is left
+pub fn report(limits: &ContributionLimits, accounts: &[Account], year: i32, age: Option<i32>,
+              income: Option<f64>, jurisdiction: &dyn Jurisdiction) -> String {
+    let mut disp = format!("Contribution limits for {}\n", year);
+    disp.push_str(&format!("{:<45}{:>14}{:>14}{:>14}\n", "Accounts", "Limit", "Contributed", "Remaining"));
+    for limit in limits.for_year(year) {
+        let contributed = limit.contributed(accounts, year);
+        let amount = limit.amount_for(age, income);
+        disp.push_str(&format!("{:<45}{:>14}{:>14}{:>14}\n", limit.name(jurisdiction),
+                               amount.separated_string_with_fixed_place(2),
+                               contributed.separated_string_with_fixed_place(2),
+                               (amount - contributed).max(0.0).separated_string_with_fixed_place(2)));
+    }
+    if age.is_none() {
+        disp.push_str("Catch-up contributions are left out until a birth date is given\n");
+    }
+    if income.is_none() {
+        disp.push_str("Income phase-outs are left out until an income is given\n");
+    }
+    disp
+}
+
+#[cfg(test)]
+fn roth_with_contributions(amounts: &[(i32, f64)]) -> Account {
+    use crate::asset::AssetClass;
+    use crate::transaction::Transaction;
+    let mut account = Account::new(AccountType::Roth);
+    for (year, amount) in amounts {
+        let date = Date::new(*year, 3, 1).unwrap();
+        account.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Bond, *amount)).unwrap();
+    }
+    account
+}
+
+#[test]
+fn contributions_count_against_the_tax_year() {
+    use crate::jurisdiction::UnitedStates;
+    let accounts = vec![roth_with_contributions(&[(2023, 6500.0), (2024, 5000.0)])];
+    let limits = ContributionLimits::standard();
+    let limit = limits.for_account(&AccountType::Roth, 2024)[0];
+    assert_eq!(5000.0, limit.contributed(&accounts, 2024));
+    assert_eq!(2000.0, limit.room(&accounts, 2024, Some(40), None));
+    assert_eq!(3000.0, limit.room(&accounts, 2024, Some(50), None));
+    let aged = |age| Contributor { year: 2024, age, income: None };
+    assert!(limits.check(&accounts, &AccountType::Roth, 2500.0, &aged(Some(40)), &UnitedStates).is_some());
+    assert!(limits.check(&accounts, &AccountType::Roth, 2500.0, &aged(Some(55)), &UnitedStates).is_none());
+    assert!(limits.check(&accounts, &AccountType::Taxable, 1e9, &aged(None), &UnitedStates).is_none());
+    assert!(limits.check(&accounts, &AccountType::InheritedIra, 1.0, &aged(None), &UnitedStates).is_some());
+    assert_eq!(5_150.0, limits.for_account(&AccountType::Hsa, 2024)[0].amount_for(Some(55), None));
+}
+
+#[test]
+fn iras_share_a_limit_apart_from_employer_plans() {
+    use crate::asset::AssetClass;
+    use crate::transaction::Transaction;
+    let date = Date::new(2024, 3, 1).unwrap();
+    let mut plan = Account::new(AccountType::Plan401k);
+    plan.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Bond, 20_000.0)).unwrap();
+    let mut ira = Account::new(AccountType::Traditional);
+    ira.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Bond, 4_000.0)).unwrap();
+    let accounts = vec![plan, ira];
+    let limits = ContributionLimits::standard();
+    let room = |account_type| limits.tightest(&accounts, &account_type, 2024, Some(40), Some(100_000.0)).unwrap().1;
+    assert_eq!(3_000.0, room(AccountType::Roth));
+    assert_eq!(3_000.0, room(AccountType::Traditional));
+    assert_eq!(3_000.0, room(AccountType::Roth401k));
+    // High earners lose the Roth IRA but can still put money in a traditional IRA
+    let high = limits.tightest(&accounts, &AccountType::Roth, 2024, Some(40), Some(200_000.0)).unwrap().1;
+    assert_eq!(0.0, high);
+    assert_eq!(3, limits.for_year(2024).iter().filter(|l| l.types.contains(&AccountType::Roth)
+                                                     || l.types.contains(&AccountType::Plan401k)).count());
+}
+
+#[test]
+fn shared_limits_are_configurable_per_year() {
+    let limits = ContributionLimits::parse("year,accounts,limit,catch_up_age,catch_up\n\
+                                            2024,traditional+roth,7000,50,1000\n").unwrap();
+    let accounts = vec![roth_with_contributions(&[(2030, 4000.0)])];
+    // Later years fall back to the last configured year
+    let limit = limits.for_account(&AccountType::Traditional, 2030)[0];
+    assert_eq!(2024, limit.year);
+    assert_eq!(3000.0, limit.room(&accounts, 2030, None, None));
+    assert_eq!(1, limits.for_year(2030).len());
+    assert!(ContributionLimits::parse("2024,roth,7000,50,1000\n2024,roth,6500,50,1000\n").is_err());
+    assert!(ContributionLimits::parse("2024,roth,7000,50,1000\n2024,roth+traditional,7000,50,1000\n").is_ok());
+}
+
+#[test]
+fn limits_phase_out_with_income() {
+    let limits = ContributionLimits::standard();
+    let limit = limits.for_account(&AccountType::Roth, 2024).into_iter().find(|l| l.phase_out.is_some()).unwrap();
+    assert_eq!(7000.0, limit.amount_for(Some(40), Some(100_000.0)));
+    // A third of the way through the phase-out leaves two thirds of the limit, rounded up to $10
+    assert_eq!(4670.0, limit.amount_for(Some(40), Some(151_000.0)));
+    assert_eq!(200.0, limit.amount_for(Some(40), Some(160_900.0)));
+    assert_eq!(0.0, limit.amount_for(Some(40), Some(161_000.0)));
+    assert!(ContributionLimits::parse("2024,roth,7000,50,1000,161000,146000\n").is_err());
+}
@@ -0,0 +1,338 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, TaxTreatment};
+use crate::asset::{Asset, AssetClass};
+use crate::jurisdiction::Jurisdiction;
+
+/// Share of foreign dividends withheld abroad. Taxable accounts get it back as a foreign tax
+/// credit, tax-advantaged accounts lose it.
+const FOREIGN_WITHHOLDING: f64 = 0.10;
+
+/// How an asset class is expected to grow and pay out, as annual fractions
+pub struct ClassAssumptions {
+    pub class: AssetClass,
+    pub expected_return: f64,
+    pub dividend_yield: f64,
+    /// Fraction of the dividends taxed at the qualified dividend rate
+    pub qualified_share: f64,
+    /// Whether foreign tax withheld on the dividends can be claimed as a credit
+    pub foreign_credit: bool,
+}
+
+/// Per asset class assumptions used to decide where each asset class is best held
+pub struct LocationAssumptions {
+    pub classes: Vec<ClassAssumptions>,
+}
+
+impl LocationAssumptions {
+    /// Long-run assumptions to use when no file is given
+    pub fn standard() -> LocationAssumptions {
+        let class = |class, expected_return, dividend_yield, qualified_share, foreign_credit| ClassAssumptions {
+            class, expected_return, dividend_yield, qualified_share, foreign_credit,
+        };
+        LocationAssumptions {
+            classes: vec![
+                class(AssetClass::Domestic, 0.07, 0.015, 0.95, false),
+                class(AssetClass::International, 0.075, 0.03, 0.70, true),
+                class(AssetClass::Bond, 0.035, 0.035, 0.0, false),
+                class(AssetClass::RealEstate, 0.065, 0.04, 0.0, false),
+            ],
+        }
+    }
+
+    /// Loads assumptions from a CSV file
+    pub fn load(path: &str) -> Result<LocationAssumptions, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read location assumptions file {}: {}", path, why))?;
+        LocationAssumptions::parse(&text)
+    }
+
+    /// Parses lines of class,return,yield,qualified share,foreign credit (yes or no). Asset
+    /// classes that are left out keep their standard assumptions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let assumptions = LocationAssumptions::parse("class,return,yield,qualified,foreign_credit\n\
+    ///                                               bond,0.04,0.04,0,no\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<LocationAssumptions, String> {
+        let mut assumptions = LocationAssumptions::standard();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 5 {
+                return Err(format!("Line {}: expected class,return,yield,qualified,foreign_credit but found {}",
+                                   number + 1, line));
+            }
+            if number == 0 && fields[0].eq_ignore_ascii_case("class") {
+                continue;
+            }
+            let class = AssetClass::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            let numbers = fields[1..4].iter()
+                .map(|f| f.parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f)))
+                .collect::<Result<Vec<f64>, String>>()?;
+            let foreign_credit = match fields[4].to_lowercase().as_str() {
+                "yes" | "y" | "true" => true,
+                "no" | "n" | "false" => false,
+                _ => return Err(format!("Line {}: foreign_credit must be yes or no", number + 1)),
+            };
+            if !(0.0..=1.0).contains(&numbers[2]) {
+                return Err(format!("Line {}: the qualified share must be between 0 and 1", number + 1));
+            }
+            let entry = ClassAssumptions {
+                class: class.clone(),
+                expected_return: numbers[0],
+                dividend_yield: numbers[1],
+                qualified_share: numbers[2],
+                foreign_credit,
+            };
+            match assumptions.classes.iter_mut().find(|c| c.class == class) {
+                Some(existing) => *existing = entry,
+                None => assumptions.classes.push(entry),
+            }
+        }
+        Ok(assumptions)
+    }
+
+    fn get(&self, class: &AssetClass) -> Option<&ClassAssumptions> {
+        self.classes.iter().find(|c| &c.class == class)
+    }
+}
+
+/// The user's tax rates, as fractions, and how many years the money stays invested
+pub struct TaxRates {
+    pub ordinary: f64,
+    /// Rate on qualified dividends and long-term capital gains
+    pub qualified: f64,
+    /// Rate expected on withdrawals from tax-deferred accounts
+    pub withdrawal: f64,
+    pub years: u32,
+}
+
+/// Returns what one dollar of the asset class held in an account with the tax treatment is worth
+/// after tax at the end of the horizon. In taxable accounts dividends are taxed every year and
+/// reinvested, and gains are taxed when sold at the end.
+pub fn after_tax_growth(assumptions: &ClassAssumptions, treatment: &TaxTreatment, rates: &TaxRates) -> f64 {
+    let dividend_tax = assumptions.qualified_share * rates.qualified
+        + (1.0 - assumptions.qualified_share) * rates.ordinary;
+    let lost_abroad = if assumptions.foreign_credit && !treatment.taxable_growth {
+        FOREIGN_WITHHOLDING * assumptions.dividend_yield
+    } else {
+        0.0
+    };
+    let mut value = 1.0;
+    let mut basis = 1.0;
+    for _ in 0..rates.years {
+        let dividends = value * assumptions.dividend_yield;
+        let kept = if treatment.taxable_growth { dividends * (1.0 - dividend_tax) } else { dividends };
+        value *= 1.0 + assumptions.expected_return - assumptions.dividend_yield - lost_abroad;
+        value += kept;
+        basis += kept;
+    }
+    if treatment.taxable_growth {
+        value - rates.qualified * (value - basis).max(0.0)
+    } else if treatment.taxed_withdrawals {
+        value * (1.0 - rates.withdrawal)
+    } else {
+        value
+    }
+}
+
+/// Returns the expected after-tax value of the accounts at the end of the horizon
+pub fn after_tax_wealth(accounts: &[Account], assumptions: &LocationAssumptions, rates: &TaxRates,
+                        jurisdiction: &dyn Jurisdiction) -> f64 {
+    accounts.iter()
+        .map(|account| {
+            let treatment = jurisdiction.tax_treatment(&account.classification);
+            account.assets.iter()
+                .map(|asset| match assumptions.get(&asset.class) {
+                    Some(class) => asset.value * after_tax_growth(class, &treatment, rates),
+                    None => asset.value,
+                })
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+/// Moves the amounts between sources and destinations that gives the largest total value, where
+/// each dollar moved from source i to destination j is worth value[i][j]. Supply and demand must
+/// add up to the same total. Solved as a minimum cost flow by sending money down the most
+/// valuable remaining path until every source is used up.
+fn transport(supply: &[f64], demand: &[f64], value: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let (sources, sinks) = (supply.len(), demand.len());
+    // Nodes: start, sources, destinations, end
+    let nodes = sources + sinks + 2;
+    let end = nodes - 1;
+    // Each edge is followed by its reverse, so edge i ^ 1 undoes edge i
+    let mut edges: Vec<(usize, usize, f64, f64)> = Vec::new();
+    let add = |edges: &mut Vec<(usize, usize, f64, f64)>, from: usize, to: usize, capacity: f64, cost: f64| {
+        edges.push((from, to, capacity, cost));
+        edges.push((to, from, 0.0, -cost));
+    };
+    let mut links = Vec::new();
+    for (i, (amount, values)) in supply.iter().zip(value).enumerate() {
+        add(&mut edges, 0, 1 + i, *amount, 0.0);
+        for (j, dollar_value) in values.iter().enumerate().take(sinks) {
+            links.push((i, j, edges.len()));
+            add(&mut edges, 1 + i, 1 + sources + j, f64::INFINITY, -dollar_value);
+        }
+    }
+    for (j, amount) in demand.iter().enumerate() {
+        add(&mut edges, 1 + sources + j, end, *amount, 0.0);
+    }
+    loop {
+        let mut cost = vec![f64::INFINITY; nodes];
+        let mut via: Vec<Option<usize>> = vec![None; nodes];
+        cost[0] = 0.0;
+        for _ in 0..nodes {
+            for (index, (from, to, capacity, edge_cost)) in edges.iter().enumerate() {
+                if *capacity > 1e-9 && cost[*from] + edge_cost < cost[*to] - 1e-12 {
+                    cost[*to] = cost[*from] + edge_cost;
+                    via[*to] = Some(index);
+                }
+            }
+        }
+        if via[end].is_none() {
+            break;
+        }
+        let mut path = Vec::new();
+        let mut node = end;
+        while let Some(index) = via[node] {
+            path.push(index);
+            node = edges[index].0;
+        }
+        let amount = path.iter().map(|index| edges[*index].2).fold(f64::INFINITY, f64::min);
+        for index in path {
+            edges[index].2 -= amount;
+            edges[index ^ 1].2 += amount;
+        }
+    }
+    // What was sent down a link is left as capacity on its reverse edge
+    let mut flows = vec![vec![0.0; sinks]; sources];
+    for (i, j, index) in links {
+        flows[i][j] = edges[index ^ 1].2;
+    }
+    flows
+}
+
+/// Whether money in accounts with the two treatments grows, is taxed and can be spent the same way,
+/// so the accounts can stand in for each other. Accounts that can only be spent on qualified
+/// expenses are kept apart from those that can be spent on anything.
+fn interchangeable(a: &TaxTreatment, b: &TaxTreatment) -> bool {
+    a.taxable_growth == b.taxable_growth && a.taxed_withdrawals == b.taxed_withdrawals && a.access == b.access
+}
+
+/// Chooses how much of each asset class every account should hold to get the most after-tax
+/// wealth, keeping each account's total and the portfolio's total in each asset class unchanged.
+/// Interchangeable accounts are grouped, and each asset class placed in a group is split in
+/// proportion to the size of its accounts.
+pub fn optimize(accounts: &[Account], class_totals: &Account, assumptions: &LocationAssumptions,
+                rates: &TaxRates, jurisdiction: &dyn Jurisdiction) -> Vec<Account> {
+    let mut treatments: Vec<TaxTreatment> = Vec::new();
+    let mut sizes: Vec<f64> = Vec::new();
+    for account in accounts {
+        let treatment = jurisdiction.tax_treatment(&account.classification);
+        match treatments.iter().position(|t| interchangeable(t, &treatment)) {
+            Some(index) => sizes[index] += account.get_total_value(),
+            None => {
+                treatments.push(treatment);
+                sizes.push(account.get_total_value());
+            }
+        }
+    }
+    let total: f64 = sizes.iter().sum();
+    let classes = AssetClass::all();
+    let wanted = class_totals.get_total_value();
+    let supply: Vec<f64> = classes.iter()
+        .map(|class| if wanted > 0.0 { total * class_totals.get_asset_value(class.clone()) / wanted } else { 0.0 })
+        .collect();
+    let value: Vec<Vec<f64>> = classes.iter()
+        .map(|class| treatments.iter()
+             .map(|treatment| match assumptions.get(class) {
+                 Some(assumptions) => after_tax_growth(assumptions, treatment, rates),
+                 None => 1.0,
+             })
+             .collect())
+        .collect();
+    let flows = transport(&supply, &sizes, &value);
+    accounts.iter()
+        .map(|account| {
+            let treatment = jurisdiction.tax_treatment(&account.classification);
+            let index = treatments.iter().position(|t| interchangeable(t, &treatment)).unwrap_or(0);
+            let share = if sizes[index] > 0.0 { account.get_total_value() / sizes[index] } else { 0.0 };
+            let mut placed = Account::new(account.classification.clone());
+            for (c, class) in classes.iter().enumerate() {
+                placed.add_asset(Asset::new(class.clone(), flows[c][index] * share));
+            }
+            placed
+        })
+        .collect()
+}
+
+/// Shows what each account holds now and would hold under the optimized placement, and what the
+/// change is expected to be worth after tax at the end of the horizon
+pub fn report(current: &[Account], proposed: &[Account], assumptions: &LocationAssumptions, rates: &TaxRates,
+              jurisdiction: &dyn Jurisdiction) -> String {
+    let mut disp = format!("{:<32}", "Account");
+    for class in AssetClass::all() {
+        disp.push_str(&format!("{:>26}", class));
+    }
+    disp.push('\n');
+    for (now, placed) in current.iter().zip(proposed) {
+        disp.push_str(&format!("{:<32}", now.name));
+        for class in AssetClass::all() {
+            disp.push_str(&format!("{:>26}", format!("{} -> {}",
+                                                     now.get_asset_value(class.clone()).separated_string_with_fixed_place(0),
+                                                     placed.get_asset_value(class).separated_string_with_fixed_place(0))));
+        }
+        disp.push('\n');
+    }
+    let before = after_tax_wealth(current, assumptions, rates, jurisdiction);
+    let after = after_tax_wealth(proposed, assumptions, rates, jurisdiction);
+    disp.push_str(&format!("Expected after-tax wealth in {} years: current placement {}, optimized {}, difference {}\n",
+                           rates.years, before.separated_string_with_fixed_place(2),
+                           after.separated_string_with_fixed_place(2),
+                           (after - before).separated_string_with_fixed_place(2)));
+    disp
+}
+
+#[test]
+fn transport_finds_the_most_valuable_flows() {
+    // Filling the first destination greedily from the first source falls short of the best total
+    let value = vec![vec![3.0, 2.0, 1.0], vec![3.0, 1.0, 1.0], vec![3.0, 3.0, 2.0]];
+    let flows = transport(&[1.0, 1.0, 1.0], &[1.0, 1.0, 1.0], &value);
+    let total: f64 = (0..3).flat_map(|i| (0..3).map(move |j| (i, j))).map(|(i, j)| flows[i][j] * value[i][j]).sum();
+    assert!((total - 7.0).abs() < 1e-9);
+}
+
+#[test]
+fn bonds_go_to_tax_deferred_accounts() {
+    use crate::account::AccountType;
+    use crate::jurisdiction::UnitedStates;
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.add_asset(Asset::new(AssetClass::Bond, 50_000.0));
+    let mut traditional = Account::new(AccountType::Traditional);
+    traditional.add_asset(Asset::new(AssetClass::Domestic, 50_000.0));
+    let accounts = vec![taxable, traditional];
+    let mut totals = Account::new(AccountType::Allocation);
+    totals.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    totals.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    let assumptions = LocationAssumptions::standard();
+    let rates = TaxRates { ordinary: 0.32, qualified: 0.15, withdrawal: 0.22, years: 20 };
+    let proposed = optimize(&accounts, &totals, &assumptions, &rates, &UnitedStates);
+    assert!((proposed[1].get_asset_value(AssetClass::Bond) - 50_000.0).abs() < 1e-6);
+    assert!((proposed[0].get_asset_value(AssetClass::Domestic) - 50_000.0).abs() < 1e-6);
+    assert!(after_tax_wealth(&proposed, &assumptions, &rates, &UnitedStates)
+            > after_tax_wealth(&accounts, &assumptions, &rates, &UnitedStates));
+}
+
+#[test]
+fn health_savings_are_not_pooled_with_roth_accounts() {
+    use crate::account::AccountType;
+    use crate::jurisdiction::UnitedStates;
+    let treatment = |account_type| UnitedStates.tax_treatment(&account_type);
+    assert!(interchangeable(&treatment(AccountType::Roth), &treatment(AccountType::Roth401k)));
+    assert!(!interchangeable(&treatment(AccountType::Roth), &treatment(AccountType::Hsa)));
+    assert!(!interchangeable(&treatment(AccountType::Hsa), &treatment(AccountType::Education529)));
+}
@@ -1,7 +1,37 @@
 mod utils;
 mod user;
 mod account;
+mod aftertax;
 mod asset;
+mod assumptions;
+mod backtest;
+mod charity;
+mod conversion;
+mod date;
+mod gains;
+mod glidepath;
+mod jurisdiction;
+mod limits;
+mod location;
+mod montecarlo;
+mod optimizer;
+mod performance;
+mod prices;
+mod questionnaire;
+mod random;
+mod rebalance;
+mod returns;
+mod risk;
+mod riskparity;
+mod rmd;
+mod snapshot;
+mod spending;
+mod stats;
+mod stress;
+mod tax;
+mod transaction;
+mod waterfall;
+mod withdrawal;
 //use asset::{Asset, AssetClass};
 //use account::{Account, AccountType};
 //use user::User;
@@ -11,22 +41,6 @@ mod asset;
 
 
 
-// Naive way - don't account for current assets
-// calculate new asset amounts
-// 1. Total user value
-// 2. Multiply by target asset allocation
-// 3. Real Estate: Place in Roth. If Roth full then place in traditional. If traditional full then
-//    place in taxable.
-// 4. Bond: Place in traditional. If traditional full then place in roth. If roth full then place
-//    in taxable.
-// 5. Add together domestic and international. Fill accounts back to previous totals with half
-//    domestic and half international.
-
-
-
-
-
-
 /*#[test]
 fn get_total_account_value() {
     let mut account  = Account::new(AccountType::Taxable);
@@ -180,6 +194,14 @@ fn main() {
 
     let first = utils::get_string("first name");
     let last = utils::get_string("last name");
-    let mut user = user::User::new(&first, &last);
+    let mut user = user::User::new(&first, &last, jurisdiction::request_jurisdiction());
+    let history = format!("{}_{}_history.csv", first, last).to_lowercase().replace(' ', "_");
+    if let Err(why) = user.load_history(&history) {
+        println!("{}", why);
+    }
+    let accounts = format!("{}_{}_accounts.csv", first, last).to_lowercase().replace(' ', "_");
+    if let Err(why) = user.load_accounts(&accounts) {
+        println!("{}", why);
+    }
     user.request_action();
 }
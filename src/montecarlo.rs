@@ -0,0 +1,134 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use crate::account::Account;
+use crate::asset::AssetClass;
+use crate::assumptions::MarketAssumptions;
+use crate::backtest::{self, Policy};
+use crate::random::Random;
+use crate::stats;
+
+/// Percentiles of ending wealth shown in the projection report
+const PERCENTILES: [f64; 5] = [0.10, 0.25, 0.50, 0.75, 0.90];
+
+/// How a portfolio is projected forward
+pub struct Projection {
+    pub years: usize,
+    /// Added each year, spread evenly over the months. Negative for withdrawals.
+    pub annual_cash_flow: f64,
+    pub policy: Policy,
+    pub simulations: usize,
+    pub seed: u64,
+}
+
+pub struct ProjectionResult {
+    /// Sorted ending values of every simulation
+    pub endings: Vec<f64>,
+    /// Fraction of simulations that never ran out of money
+    pub success_rate: f64,
+}
+
+impl ProjectionResult {
+    pub fn percentile(&self, fraction: f64) -> f64 {
+        stats::percentile(&self.endings, fraction)
+    }
+}
+
+/// Draws monthly returns for each asset class from the correlated normal distribution described
+/// by the annual assumptions
+pub struct ReturnGenerator<'a> {
+    assumptions: &'a MarketAssumptions,
+    lower: Vec<Vec<f64>>,
+    random: Random,
+}
+
+impl<'a> ReturnGenerator<'a> {
+    pub fn new(assumptions: &'a MarketAssumptions, seed: u64) -> Result<ReturnGenerator<'a>, String> {
+        let monthly: Vec<Vec<f64>> = assumptions.covariance().iter()
+            .map(|row| row.iter().map(|c| c / 12.0).collect())
+            .collect();
+        Ok(ReturnGenerator {
+            assumptions,
+            lower: stats::cholesky(&monthly)?,
+            random: Random::new(seed),
+        })
+    }
+
+    pub fn next_month(&mut self) -> Vec<(AssetClass, f64)> {
+        let draws: Vec<f64> = (0..self.lower.len()).map(|_| self.random.normal()).collect();
+        self.assumptions.classes.iter().enumerate().map(|(i, class)| {
+            let shock: f64 = self.lower[i].iter().zip(&draws).map(|(l, z)| l * z).sum();
+            let r = self.assumptions.expected_returns[i] / 12.0 + shock;
+            (class.clone(), r.max(-1.0))
+        }).collect()
+    }
+}
+
+/// Projects the holdings forward many times using random returns, rebalancing toward the target
+/// with the same monthly step the backtester uses
+pub fn project(holdings: &Account, target: &Account, assumptions: &MarketAssumptions,
+               projection: &Projection) -> Result<ProjectionResult, String> {
+    let mut generator = ReturnGenerator::new(assumptions, projection.seed)?;
+    let mut endings = Vec::new();
+    let mut successes = 0;
+    for _ in 0..projection.simulations {
+        let mut simulated = holdings.snapshot();
+        let mut depleted = false;
+        for month in 0..projection.years * 12 {
+            let returns = generator.next_month();
+            let cash_flow = projection.annual_cash_flow / 12.0;
+            let month = backtest::simulate_month(&mut simulated, &returns, target, cash_flow, &projection.policy,
+                                                 month);
+            if month.shortfall > 0.0 || simulated.get_total_value() <= 0.0 {
+                depleted = true;
+                break;
+            }
+        }
+        if !depleted {
+            successes += 1;
+        }
+        endings.push(simulated.get_total_value());
+    }
+    endings.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let success_rate = if projection.simulations > 0 {
+        successes as f64 / projection.simulations as f64
+    } else {
+        0.0
+    };
+    Ok(ProjectionResult { endings, success_rate })
+}
+
+/// Lays out the probability of success and the percentiles of ending wealth
+pub fn report(result: &ProjectionResult, projection: &Projection) -> String {
+    let mut disp = format!("Monte Carlo projection: {} simulations over {} years, rebalancing {}\n",
+                           projection.simulations, projection.years, projection.policy);
+    disp.push_str(&format!("Probability of success: {:.1}%\n", 100.0 * result.success_rate));
+    disp.push_str(&format!("{:<12}{:>18}\n", "Percentile", "Ending Value"));
+    for fraction in &PERCENTILES {
+        disp.push_str(&format!("{:<12}{:>18}\n", format!("{:.0}th", 100.0 * fraction),
+                               result.percentile(*fraction).separated_string_with_fixed_place(2)));
+    }
+    disp
+}
+
+#[test]
+fn projection_is_reproducible_with_seed() {
+    let target = crate::rebalance::sixty_forty();
+    let holdings = target.multiply(1000.0);
+    let projection = Projection { years: 10, annual_cash_flow: 0.0, policy: Policy::Calendar(12),
+                                  simulations: 200, seed: 11 };
+    let first = project(&holdings, &target, &MarketAssumptions::standard(), &projection).unwrap();
+    let second = project(&holdings, &target, &MarketAssumptions::standard(), &projection).unwrap();
+    assert_eq!(first.endings, second.endings);
+    assert_eq!(1.0, first.success_rate);
+    assert!(first.percentile(0.1) < first.percentile(0.9));
+}
+
+#[test]
+fn heavy_withdrawals_fail() {
+    let target = crate::rebalance::sixty_forty();
+    let holdings = target.multiply(1000.0);
+    let projection = Projection { years: 30, annual_cash_flow: -20_000.0, policy: Policy::Calendar(12),
+                                  simulations: 100, seed: 3 };
+    let result = project(&holdings, &target, &MarketAssumptions::standard(), &projection).unwrap();
+    assert!(result.success_rate < 0.05);
+}
@@ -0,0 +1,227 @@
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::assumptions::MarketAssumptions;
+
+/// Percentage step between the weights tried by the optimizer. Whole percentages keep the result
+/// usable as a target allocation without rounding.
+const STEP: u32 = 1;
+
+/// Lowest and highest percentage each asset class may be given
+pub struct Constraints {
+    pub bounds: Vec<(AssetClass, f64, f64)>,
+}
+
+impl Constraints {
+    /// Lets every asset class take anywhere from 0 to 100 percent
+    pub fn unconstrained() -> Constraints {
+        Constraints {
+            bounds: Vec::new(),
+        }
+    }
+
+    /// Loads constraints from a CSV file
+    pub fn load(path: &str) -> Result<Constraints, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read constraints file {}: {}", path, why))?;
+        Constraints::parse(&text)
+    }
+
+    /// Parses lines of class,min,max in percent. Asset classes that are left out are unconstrained.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let constraints = Constraints::parse("bond,20,60\nreal estate,0,10\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<Constraints, String> {
+        let mut bounds = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 3 {
+                return Err(format!("Line {}: expected class,min,max but found {}", number + 1, line));
+            }
+            if number == 0 && fields[0].eq_ignore_ascii_case("class") {
+                continue;
+            }
+            let class = AssetClass::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            let min: f64 = fields[1].parse().map_err(|_| format!("Line {}: invalid minimum {}", number + 1, fields[1]))?;
+            let max: f64 = fields[2].parse().map_err(|_| format!("Line {}: invalid maximum {}", number + 1, fields[2]))?;
+            if min < 0.0 || max > 100.0 || min > max {
+                return Err(format!("Line {}: bounds must satisfy 0 <= min <= max <= 100", number + 1));
+            }
+            bounds.push((class, min, max));
+        }
+        Ok(Constraints { bounds })
+    }
+
+    /// Returns the lowest and highest percentage allowed for the asset class
+    pub fn get(&self, class: &AssetClass) -> (f64, f64) {
+        self.bounds.iter()
+            .find(|(c, _, _)| c == class)
+            .map(|(_, min, max)| (*min, *max))
+            .unwrap_or((0.0, 100.0))
+    }
+}
+
+/// Weights, in percent, for each asset class of the assumptions and the resulting annual expected
+/// return and volatility
+pub struct Portfolio {
+    pub weights: Vec<f64>,
+    pub expected_return: f64,
+    pub volatility: f64,
+}
+
+impl Portfolio {
+    fn new(weights: Vec<f64>, assumptions: &MarketAssumptions, covariance: &[Vec<f64>]) -> Portfolio {
+        let fractions: Vec<f64> = weights.iter().map(|w| w / 100.0).collect();
+        let expected_return = fractions.iter().zip(&assumptions.expected_returns).map(|(w, r)| w * r).sum();
+        let mut variance = 0.0;
+        for (i, wi) in fractions.iter().enumerate() {
+            for (j, wj) in fractions.iter().enumerate() {
+                variance += wi * wj * covariance[i][j];
+            }
+        }
+        Portfolio {
+            weights,
+            expected_return,
+            volatility: variance.max(0.0).sqrt(),
+        }
+    }
+
+    /// Returns the weights as a target allocation
+    pub fn allocation(&self, assumptions: &MarketAssumptions) -> Account {
+        let mut allocation = Account::new(AccountType::Allocation);
+        for (class, weight) in assumptions.classes.iter().zip(&self.weights) {
+            allocation.add_asset(Asset::new(class.clone(), *weight));
+        }
+        allocation
+    }
+}
+
+/// Tries every combination of whole-percentage weights that adds up to 100 and respects the
+/// constraints
+pub fn feasible_portfolios(assumptions: &MarketAssumptions, constraints: &Constraints) -> Vec<Portfolio> {
+    let covariance = assumptions.covariance();
+    let bounds: Vec<(u32, u32)> = assumptions.classes.iter()
+        .map(|class| {
+            let (min, max) = constraints.get(class);
+            (min.ceil() as u32, max.floor() as u32)
+        })
+        .collect();
+    let mut portfolios = Vec::new();
+    let mut weights = Vec::new();
+    enumerate(&bounds, 100, &mut weights, &mut |w| {
+        portfolios.push(Portfolio::new(w.iter().map(|p| *p as f64).collect(), assumptions, &covariance));
+    });
+    portfolios
+}
+
+fn enumerate(bounds: &[(u32, u32)], remaining: u32, weights: &mut Vec<u32>, found: &mut dyn FnMut(&[u32])) {
+    let index = weights.len();
+    if index == bounds.len() {
+        if remaining == 0 {
+            found(weights);
+        }
+        return;
+    }
+    let (min, max) = bounds[index];
+    if index == bounds.len() - 1 {
+        if (min..=max).contains(&remaining) {
+            weights.push(remaining);
+            found(weights);
+            weights.pop();
+        }
+        return;
+    }
+    let mut weight = min;
+    while weight <= max.min(remaining) {
+        weights.push(weight);
+        enumerate(bounds, remaining - weight, weights, found);
+        weights.pop();
+        weight += STEP;
+    }
+}
+
+/// Returns the portfolios that no other portfolio beats on both return and volatility, from the
+/// least volatile to the highest returning
+pub fn efficient_frontier(assumptions: &MarketAssumptions, constraints: &Constraints) -> Vec<Portfolio> {
+    let mut portfolios = feasible_portfolios(assumptions, constraints);
+    portfolios.sort_by(|a, b| a.volatility.partial_cmp(&b.volatility).unwrap_or(std::cmp::Ordering::Equal));
+    let mut frontier: Vec<Portfolio> = Vec::new();
+    for portfolio in portfolios {
+        let best = frontier.last().map(|p| p.expected_return).unwrap_or(f64::NEG_INFINITY);
+        if portfolio.expected_return > best + 1e-12 {
+            frontier.push(portfolio);
+        }
+    }
+    frontier
+}
+
+/// Returns the highest returning portfolio whose volatility does not exceed the target
+pub fn for_volatility(frontier: Vec<Portfolio>, volatility: f64) -> Option<Portfolio> {
+    frontier.into_iter().rev().find(|p| p.volatility <= volatility)
+}
+
+/// Returns the least volatile portfolio expected to return at least the target
+pub fn for_return(frontier: Vec<Portfolio>, expected_return: f64) -> Option<Portfolio> {
+    frontier.into_iter().find(|p| p.expected_return >= expected_return)
+}
+
+/// Lays out evenly spaced points along the frontier
+pub fn frontier_report(frontier: &[Portfolio], assumptions: &MarketAssumptions, points: usize) -> String {
+    let mut disp = format!("{:>10}{:>12}", "Return", "Volatility");
+    for class in &assumptions.classes {
+        disp.push_str(&format!("{:>15}", class));
+    }
+    disp.push('\n');
+    if frontier.is_empty() {
+        return disp;
+    }
+    let mut shown = Vec::new();
+    for point in 0..points {
+        let index = point * (frontier.len() - 1) / (points - 1).max(1);
+        if shown.last() != Some(&index) {
+            shown.push(index);
+        }
+    }
+    for index in shown {
+        let portfolio = &frontier[index];
+        disp.push_str(&format!("{:>9.2}%{:>11.2}%", 100.0 * portfolio.expected_return, 100.0 * portfolio.volatility));
+        for weight in &portfolio.weights {
+            disp.push_str(&format!("{:>14.0}%", weight));
+        }
+        disp.push('\n');
+    }
+    disp
+}
+
+#[test]
+fn frontier_starts_at_least_volatile_portfolio() {
+    let frontier = efficient_frontier(&MarketAssumptions::standard(), &Constraints::unconstrained());
+    let first = &frontier[0];
+    let last = frontier.last().unwrap();
+    assert!(first.volatility < 0.06);
+    // The highest returning portfolio is all international
+    assert_eq!(vec![0.0, 100.0, 0.0, 0.0], last.weights);
+    for pair in frontier.windows(2) {
+        assert!(pair[0].volatility <= pair[1].volatility);
+        assert!(pair[0].expected_return < pair[1].expected_return);
+    }
+}
+
+#[test]
+fn proposals_respect_constraints_and_targets() {
+    let assumptions = MarketAssumptions::standard();
+    let constraints = Constraints::parse("class,min,max\nbond,20,60\nreal estate,0,10\n").unwrap();
+    let portfolio = for_volatility(efficient_frontier(&assumptions, &constraints), 0.10).unwrap();
+    assert!(portfolio.volatility <= 0.10);
+    let allocation = portfolio.allocation(&assumptions);
+    assert_eq!(100.0, allocation.get_total_value());
+    assert!(allocation.get_asset_value(AssetClass::Bond) >= 20.0);
+    assert!(allocation.get_asset_value(AssetClass::RealEstate) <= 10.0);
+
+    let portfolio = for_return(efficient_frontier(&assumptions, &constraints), 0.05).unwrap();
+    assert!(portfolio.expected_return >= 0.05);
+    assert!(for_return(efficient_frontier(&assumptions, &constraints), 0.20).is_none());
+}
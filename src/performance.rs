@@ -0,0 +1,234 @@
+use crate::account::Account;
+use crate::asset::AssetClass;
+use crate::date::Date;
+use crate::snapshot::Snapshot;
+use crate::transaction::TransactionKind;
+
+/// What a return is measured for
+pub enum Subject {
+    Portfolio,
+    /// The account with this name
+    Account(String),
+    Class(AssetClass),
+}
+
+#[derive(Clone, Copy)]
+pub enum Period {
+    MonthToDate,
+    YearToDate,
+    OneYear,
+    SinceInception,
+}
+
+impl Period {
+    /// Returns the last day before the period begins, so that the closing value of that day is
+    /// the opening value of the period. Since inception has no start date.
+    pub fn start(&self, today: &Date) -> Option<Date> {
+        match self {
+            Period::MonthToDate => Some(Date::from_days(
+                Date { year: today.year, month: today.month, day: 1 }.days_since_epoch() - 1)),
+            Period::YearToDate => Some(Date { year: today.year - 1, month: 12, day: 31 }),
+            Period::OneYear => Some(Date::from_days(today.days_since_epoch() - 365)),
+            Period::SinceInception => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Period::MonthToDate => "MTD",
+            Period::YearToDate => "YTD",
+            Period::OneYear => "1 Year",
+            Period::SinceInception => "Inception",
+        }
+    }
+}
+
+/// Market value of the subject at the end of a date
+pub struct Valuation {
+    pub date: Date,
+    pub value: f64,
+}
+
+/// Money moving into (positive) or out of (negative) the subject on a date
+pub struct CashFlow {
+    pub date: Date,
+    pub amount: f64,
+}
+
+/// Values the subject at each snapshot in the history
+pub fn valuations(history: &[Snapshot], subject: &Subject) -> Vec<Valuation> {
+    history.iter().filter_map(|snapshot| {
+        let value = match subject {
+            Subject::Portfolio => snapshot.get_total_value(),
+            Subject::Account(name) => snapshot.account(name)?.get_total_value(),
+            Subject::Class(class) => snapshot.accounts.iter()
+                .map(|a| a.get_asset_value(class.clone())).sum(),
+        };
+        Some(Valuation { date: snapshot.date, value })
+    }).collect()
+}
+
+/// Finds the money that crossed the boundary of the subject in the account ledgers. Buys and sells
+/// move money in and out of an asset class even though it stays inside the account.
+pub fn cash_flows(accounts: &[Account], subject: &Subject) -> Vec<CashFlow> {
+    let mut flows = Vec::new();
+    for account in accounts {
+        for transaction in account.transactions() {
+            let is_flow = match subject {
+                Subject::Portfolio => transaction.kind.is_external(),
+                Subject::Account(name) => *name == account.name && transaction.kind.is_external(),
+                Subject::Class(class) => transaction.asset == *class
+                    && transaction.kind != TransactionKind::Dividend && !transaction.kind.is_revaluation(),
+            };
+            if is_flow {
+                flows.push(CashFlow { date: transaction.date, amount: transaction.signed_value() });
+            }
+        }
+    }
+    flows.sort_by_key(|f| f.date);
+    flows
+}
+
+/// Drops the valuations outside of the period, keeping the last valuation before the period as
+/// its opening value
+fn in_period(valuations: Vec<Valuation>, start: Option<Date>, end: &Date) -> Vec<Valuation> {
+    let opening = match start {
+        Some(start) => valuations.iter().rposition(|v| v.date <= start).unwrap_or(0),
+        None => 0,
+    };
+    valuations.into_iter().skip(opening).filter(|v| v.date <= *end).collect()
+}
+
+fn flows_between(flows: &[CashFlow], after: &Date, until: &Date) -> f64 {
+    flows.iter().filter(|f| f.date > *after && f.date <= *until).map(|f| f.amount).sum()
+}
+
+/// Chains the return of each stretch between valuations, treating cash flows as arriving at the
+/// end of the stretch they fall in, so the result does not depend on when money was added.
+/// The return is cumulative over the valuations, not annualized.
+///
+/// # Examples
+///
+/// ```
+/// // 100 grows to 110, 100 is added, then 210 grows to 231
+/// assert_eq!(0.21, time_weighted_return(&valuations, &flows).unwrap());
+/// ```
+pub fn time_weighted_return(valuations: &[Valuation], flows: &[CashFlow]) -> Result<f64, String> {
+    let mut growth = 1.0;
+    let mut stretches = 0;
+    for pair in valuations.windows(2) {
+        let (open, close) = (&pair[0], &pair[1]);
+        if open.value <= 0.0 {
+            continue;
+        }
+        let flow = flows_between(flows, &open.date, &close.date);
+        growth *= (close.value - flow) / open.value;
+        stretches += 1;
+    }
+    if stretches == 0 {
+        Err("Need at least two valuations to measure a return".to_string())
+    } else {
+        Ok(growth - 1.0)
+    }
+}
+
+/// Finds the annual rate at which the opening value and the cash flows would have grown into the
+/// closing value (XIRR)
+pub fn money_weighted_return(valuations: &[Valuation], flows: &[CashFlow]) -> Result<f64, String> {
+    let (open, close) = match (valuations.first(), valuations.last()) {
+        (Some(open), Some(close)) if close.date > open.date => (open, close),
+        _ => return Err("Need valuations on two different dates to measure a return".to_string()),
+    };
+    // From the investor's side: money put in is negative, money taken out or left over is positive
+    let mut investor = vec![(open.date, -open.value)];
+    for flow in flows.iter().filter(|f| f.date > open.date && f.date <= close.date) {
+        investor.push((flow.date, -flow.amount));
+    }
+    investor.push((close.date, close.value));
+
+    let npv = |rate: f64| -> f64 {
+        investor.iter()
+            .map(|(date, amount)| amount / (1.0 + rate).powf(open.date.days_until(date) as f64 / 365.0))
+            .sum()
+    };
+    let (mut low, mut high) = (-0.9999, 100.0);
+    if npv(low).signum() == npv(high).signum() {
+        return Err("Cash flows do not have a money-weighted return".to_string());
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    Ok((low + high) / 2.0)
+}
+
+/// Formats time-weighted and money-weighted returns of the subject for each period ending today
+fn report_line(history: &[Snapshot], accounts: &[Account], subject: &Subject, today: &Date) -> String {
+    let flows = cash_flows(accounts, subject);
+    let mut disp = String::new();
+    for period in &[Period::MonthToDate, Period::YearToDate, Period::OneYear, Period::SinceInception] {
+        let values = in_period(valuations(history, subject), period.start(today), today);
+        let twr = match time_weighted_return(&values, &flows) {
+            Ok(r) => format!("{:.2}%", 100.0 * r),
+            Err(_) => "-".to_string(),
+        };
+        let mwr = match money_weighted_return(&values, &flows) {
+            Ok(r) => format!("{:.2}%", 100.0 * r),
+            Err(_) => "-".to_string(),
+        };
+        disp.push_str(&format!("{:>22}", format!("{} / {}", twr, mwr)));
+    }
+    disp
+}
+
+/// Lays out returns for the whole portfolio, each account and each asset class
+pub fn report(history: &[Snapshot], accounts: &[Account], today: &Date) -> String {
+    let mut disp = "Time-weighted (cumulative) / money-weighted (annualized) returns\n".to_string();
+    disp.push_str(&format!("{:<28}", ""));
+    for period in &[Period::MonthToDate, Period::YearToDate, Period::OneYear, Period::SinceInception] {
+        disp.push_str(&format!("{:>22}", period.name()));
+    }
+    disp.push('\n');
+    disp.push_str(&format!("{:<28}{}\n", "Portfolio", report_line(history, accounts, &Subject::Portfolio, today)));
+    for account in accounts {
+        disp.push_str(&format!("{:<28}{}\n", account.name,
+                               report_line(history, accounts, &Subject::Account(account.name.clone()), today)));
+    }
+    for class in AssetClass::all() {
+        disp.push_str(&format!("{:<28}{}\n", class.to_string(),
+                               report_line(history, accounts, &Subject::Class(class), today)));
+    }
+    disp
+}
+
+#[cfg(test)]
+fn valuation(year: i32, month: u32, day: u32, value: f64) -> Valuation {
+    Valuation { date: Date::new(year, month, day).unwrap(), value }
+}
+
+#[test]
+fn time_weighted_return_ignores_timing_of_contributions() {
+    let values = vec![valuation(2019, 1, 1, 100.0), valuation(2019, 7, 1, 210.0), valuation(2020, 1, 1, 231.0)];
+    let flows = vec![CashFlow { date: Date::new(2019, 7, 1).unwrap(), amount: 100.0 }];
+    let twr = time_weighted_return(&values, &flows).unwrap();
+    assert!((twr - 0.21).abs() < 1e-9);
+}
+
+#[test]
+fn money_weighted_return_matches_simple_growth_without_flows() {
+    let values = vec![valuation(2019, 1, 1, 100.0), valuation(2020, 1, 1, 110.0)];
+    let mwr = money_weighted_return(&values, &[]).unwrap();
+    assert!((mwr - 0.10).abs() < 1e-3);
+}
+
+#[test]
+fn period_starts_at_close_of_previous_period() {
+    let today = Date::new(2019, 3, 15).unwrap();
+    assert_eq!(Some(Date::new(2019, 2, 28).unwrap()), Period::MonthToDate.start(&today));
+    assert_eq!(Some(Date::new(2018, 12, 31).unwrap()), Period::YearToDate.start(&today));
+    assert_eq!(None, Period::SinceInception.start(&today));
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::fs;
+use crate::account::Account;
+use crate::asset::Asset;
+use crate::date::Date;
+
+/// Prices older than this many days are reported as stale, which lets weekends and holidays pass
+/// without a warning
+const STALE_AFTER_DAYS: i64 = 4;
+
+/// A closing price and the date it was quoted on
+pub struct Quote {
+    pub date: Date,
+    pub close: f64,
+}
+
+/// Closing prices per ticker, kept in date order
+#[derive(Default)]
+pub struct PriceTable {
+    prices: HashMap<String, Vec<Quote>>,
+}
+
+impl PriceTable {
+    pub fn new() -> PriceTable {
+        PriceTable {
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Loads prices from a CSV file with date, ticker and close columns
+    pub fn load(path: &str) -> Result<PriceTable, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read price file {}: {}", path, why))?;
+        PriceTable::parse(&text)
+    }
+
+    /// Parses CSV text with date, ticker and close columns. A header line is allowed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let table = PriceTable::parse("date,ticker,close\n2019-01-02,VTI,124.13\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<PriceTable, String> {
+        let mut table = PriceTable::new();
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if line.trim().is_empty() || (number == 0 && fields[0].eq_ignore_ascii_case("date")) {
+                continue;
+            }
+            if fields.len() != 3 {
+                return Err(format!("Line {}: expected date,ticker,close but found {}", number + 1, line));
+            }
+            let date = Date::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            let close: f64 = fields[2].parse()
+                .map_err(|_| format!("Line {}: invalid close price {}", number + 1, fields[2]))?;
+            table.add(fields[1], date, close);
+        }
+        Ok(table)
+    }
+
+    /// Adds a closing price, replacing any price already known for that ticker and date
+    pub fn add(&mut self, ticker: &str, date: Date, close: f64) {
+        let quotes = self.prices.entry(ticker.to_uppercase()).or_default();
+        quotes.retain(|q| q.date != date);
+        let position = quotes.iter().position(|q| q.date > date).unwrap_or(quotes.len());
+        quotes.insert(position, Quote { date, close });
+    }
+
+    /// Returns the last known price of the ticker at the end of the date
+    pub fn price_on(&self, ticker: &str, date: &Date) -> Option<&Quote> {
+        self.prices.get(&ticker.to_uppercase())?
+            .iter()
+            .rev()
+            .find(|q| q.date <= *date)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+}
+
+/// Values an account's holdings at the end of a date. Securities are valued at their last known
+/// price, and at what was paid for them when no price is known. Holdings recorded as dollar
+/// amounts keep their recorded value, without any earlier revaluation. Returns the holdings along with a warning for every price
+/// that was stale or missing.
+pub fn value_account(account: &Account, prices: &PriceTable, date: &Date) -> (Account, Vec<String>) {
+    let mut holdings = Account::new(account.classification.clone());
+    for transaction in account.transactions().iter()
+        .filter(|t| t.date <= *date && t.ticker.is_none() && !t.kind.is_revaluation()) {
+        holdings.add_asset(Asset::new(transaction.asset.class.clone(), transaction.signed_value()));
+    }
+    let mut warnings = Vec::new();
+    for position in account.positions_at(date) {
+        let value = match prices.price_on(&position.ticker, date) {
+            Some(quote) => {
+                let age = quote.date.days_until(date);
+                if age > STALE_AFTER_DAYS {
+                    warnings.push(format!("{} priced at its {} close, {} days before {}",
+                                          position.ticker, quote.date, age, date));
+                }
+                position.shares * quote.close
+            }
+            None => {
+                warnings.push(format!("No price for {} on or before {}, valued at cost", position.ticker, date));
+                position.cost
+            }
+        };
+        holdings.add_asset(Asset::new(position.class.clone(), value));
+    }
+    (holdings, warnings)
+}
+
+#[test]
+fn price_table_falls_back_to_last_known_price() {
+    let table = PriceTable::parse("date,ticker,close\n2019-01-02,VTI,124.13\n2019-01-03,vti,121.00\n\n").unwrap();
+    let quote = table.price_on("VTI", &Date::new(2019, 1, 10).unwrap()).unwrap();
+    assert_eq!(121.0, quote.close);
+    assert_eq!(Date::new(2019, 1, 3).unwrap(), quote.date);
+    assert!(table.price_on("VTI", &Date::new(2019, 1, 1).unwrap()).is_none());
+}
+
+#[test]
+fn price_table_reports_line_of_bad_price() {
+    let result = PriceTable::parse("2019-01-02,VTI,124.13\n2019-01-03,VTI,abc\n");
+    assert!(result.err().unwrap().starts_with("Line 2"));
+}
+
+#[test]
+fn stale_and_missing_prices_are_warned_about() {
+    use crate::account::AccountType;
+    use crate::asset::AssetClass;
+    use crate::transaction::{Transaction, TransactionKind};
+    let jan = Date::new(2019, 1, 2).unwrap();
+    let mut account = Account::new(AccountType::Taxable);
+    account.record(Transaction::with_shares(jan, TransactionKind::Buy, AssetClass::Domestic, "VTI", 10.0, 100.0)).unwrap();
+    account.record(Transaction::with_shares(jan, TransactionKind::Buy, AssetClass::Bond, "BND", 10.0, 80.0)).unwrap();
+    account.record(Transaction::new(jan, TransactionKind::Contribution, AssetClass::RealEstate, 50.0)).unwrap();
+    let mut prices = PriceTable::new();
+    prices.add("VTI", jan, 110.0);
+
+    let (holdings, warnings) = value_account(&account, &prices, &Date::new(2019, 1, 31).unwrap());
+    assert_eq!(1100.0, holdings.get_asset_value(AssetClass::Domestic));
+    assert_eq!(800.0, holdings.get_asset_value(AssetClass::Bond));
+    assert_eq!(50.0, holdings.get_asset_value(AssetClass::RealEstate));
+    assert_eq!(2, warnings.len());
+}
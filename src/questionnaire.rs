@@ -0,0 +1,308 @@
+use std::fs;
+use std::io;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RiskTolerance {
+    Conservative,
+    Moderate,
+    Aggressive,
+}
+
+/// How the questionnaire turns answers into a stock/bond split
+#[derive(Clone, PartialEq)]
+pub enum BondRule {
+    /// Hold your age plus the offset in bonds, e.g. an offset of -10 is "age minus ten in bonds"
+    AgeInBonds(i32),
+    /// Hold a fixed share of bonds for each risk tolerance
+    Preset,
+}
+
+/// The answers a user gives to the risk questionnaire
+pub struct Answers {
+    pub age: u32,
+    /// Years until the money is needed
+    pub horizon: u32,
+    pub tolerance: RiskTolerance,
+    /// Whether the portfolio needs to pay out income now
+    pub needs_income: bool,
+}
+
+/// The configurable rules used to turn answers into a proposed allocation
+pub struct Rules {
+    pub bond_rule: BondRule,
+    /// Percent in bonds for conservative, moderate and aggressive investors under the preset rule
+    pub presets: [f64; 3],
+    /// Percentage points of bonds added for conservative and removed for aggressive investors
+    /// under the age rule
+    pub tolerance_shift: f64,
+    /// Least percent in bonds when the money is needed within five years
+    pub short_horizon_bonds: f64,
+    /// Percentage points of bonds added when the portfolio needs to pay income
+    pub income_bonds: f64,
+    /// Fraction of the stocks held internationally
+    pub international_share: f64,
+    /// Fraction of the stocks held in real estate
+    pub real_estate_share: f64,
+}
+
+impl Rules {
+    pub fn standard() -> Rules {
+        Rules {
+            bond_rule: BondRule::AgeInBonds(-10),
+            presets: [70.0, 40.0, 10.0],
+            tolerance_shift: 10.0,
+            short_horizon_bonds: 50.0,
+            income_bonds: 10.0,
+            international_share: 0.3,
+            real_estate_share: 0.1,
+        }
+    }
+
+    /// Loads rules from a file of name,value lines. Names that are left out keep their standard
+    /// value.
+    pub fn load(path: &str) -> Result<Rules, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read rules file {}: {}", path, why))?;
+        Rules::parse(&text)
+    }
+
+    /// Parses name,value lines in any order. The bond offset applies to the age-in-bonds rule,
+    /// which is "age in bonds" with no offset when the offset is left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let rules = Rules::parse("bond_rule,preset\nmoderate_bonds,35\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<Rules, String> {
+        let mut rules = Rules::standard();
+        let mut preset = None;
+        let mut offset = None;
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() != 2 {
+                return Err(format!("Line {}: expected name,value but found {}", number + 1, line));
+            }
+            let (name, value) = (fields[0].to_lowercase(), fields[1]);
+            if name == "bond_rule" {
+                preset = match value.to_lowercase().as_str() {
+                    "age" | "age-in-bonds" => Some(false),
+                    "preset" => Some(true),
+                    _ => return Err(format!("Line {}: bond_rule must be age-in-bonds or preset", number + 1)),
+                };
+                continue;
+            }
+            let number_value: f64 = value.parse()
+                .map_err(|_| format!("Line {}: invalid number {}", number + 1, value))?;
+            match name.as_str() {
+                "bond_offset" => offset = Some((number, number_value as i32)),
+                "conservative_bonds" => rules.presets[0] = number_value,
+                "moderate_bonds" => rules.presets[1] = number_value,
+                "aggressive_bonds" => rules.presets[2] = number_value,
+                "tolerance_shift" => rules.tolerance_shift = number_value,
+                "short_horizon_bonds" => rules.short_horizon_bonds = number_value,
+                "income_bonds" => rules.income_bonds = number_value,
+                "international_share" | "real_estate_share" => {
+                    if !(0.0..=1.0).contains(&number_value) {
+                        return Err(format!("Line {}: {} must be a fraction between 0 and 1", number + 1, fields[0]));
+                    }
+                    if name == "international_share" {
+                        rules.international_share = number_value;
+                    } else {
+                        rules.real_estate_share = number_value;
+                    }
+                }
+                _ => return Err(format!("Line {}: unknown rule {}", number + 1, fields[0])),
+            }
+        }
+        rules.bond_rule = match (preset, offset) {
+            (Some(true), Some((number, _))) =>
+                return Err(format!("Line {}: bond_offset needs the age-in-bonds rule", number + 1)),
+            (Some(true), None) => BondRule::Preset,
+            (_, Some((_, offset))) => BondRule::AgeInBonds(offset),
+            (Some(false), None) => BondRule::AgeInBonds(0),
+            (None, None) => rules.bond_rule,
+        };
+        if rules.international_share + rules.real_estate_share > 1.0 {
+            return Err(format!("international_share and real_estate_share add up to {}, more than all the stocks",
+                               rules.international_share + rules.real_estate_share));
+        }
+        Ok(rules)
+    }
+}
+
+/// Proposes a target allocation in whole percentages that add up to 100
+pub fn propose(answers: &Answers, rules: &Rules) -> Account {
+    let mut bonds = match rules.bond_rule {
+        BondRule::AgeInBonds(offset) => {
+            let shift = match answers.tolerance {
+                RiskTolerance::Conservative => rules.tolerance_shift,
+                RiskTolerance::Moderate => 0.0,
+                RiskTolerance::Aggressive => -rules.tolerance_shift,
+            };
+            answers.age as f64 + offset as f64 + shift
+        }
+        BondRule::Preset => match answers.tolerance {
+            RiskTolerance::Conservative => rules.presets[0],
+            RiskTolerance::Moderate => rules.presets[1],
+            RiskTolerance::Aggressive => rules.presets[2],
+        },
+    };
+    if answers.needs_income {
+        bonds += rules.income_bonds;
+    }
+    if answers.horizon < 5 {
+        bonds = bonds.max(rules.short_horizon_bonds);
+    }
+    let bonds = bonds.clamp(0.0, 100.0).round();
+    let stocks = 100.0 - bonds;
+    // Rounding both shares up could take more than the stocks, so real estate gets at most what
+    // international leaves and domestic stocks take the rest
+    let international = (stocks * rules.international_share).round().min(stocks);
+    let real_estate = (stocks * rules.real_estate_share).round().min(stocks - international);
+    let domestic = (stocks - international - real_estate).max(0.0);
+
+    let mut allocation = Account::new(AccountType::Allocation);
+    allocation.add_asset(Asset::new(AssetClass::Domestic, domestic));
+    allocation.add_asset(Asset::new(AssetClass::International, international));
+    allocation.add_asset(Asset::new(AssetClass::Bond, bonds));
+    allocation.add_asset(Asset::new(AssetClass::RealEstate, real_estate));
+    allocation
+}
+
+fn read_number(question: &str) -> u32 {
+    loop {
+        println!("{}", question);
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)
+            .expect("Failed to read line");
+        match answer.trim().parse() {
+            Ok(number) => return number,
+            Err(_) => println!("Please enter a whole number"),
+        }
+    }
+}
+
+/// Ask user of the program the risk questionnaire. The age is only asked when it is not known.
+pub fn request_answers(age: Option<u32>) -> Result<Answers, String> {
+    let age = match age {
+        Some(age) => age,
+        None => read_number("How old are you?"),
+    };
+    let horizon = read_number("In how many years will you start to need this money?");
+    let tolerance = loop {
+        println!("If your portfolio fell 30% in a year, what would you do?");
+        println!("1. Sell to stop the losses\t2. Hold on\t3. Buy more\t4. Cancel");
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        match choice.trim().parse().unwrap_or(0) {
+            1 => break RiskTolerance::Conservative,
+            2 => break RiskTolerance::Moderate,
+            3 => break RiskTolerance::Aggressive,
+            4 => return Err("Cancelled questionnaire".to_string()),
+            _ => continue,
+        }
+    };
+    let needs_income = loop {
+        println!("Do you need the portfolio to pay you an income now? (y/n)");
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        match choice.trim().to_lowercase().as_str() {
+            "y" | "yes" => break true,
+            "n" | "no" => break false,
+            _ => continue,
+        }
+    };
+    Ok(Answers { age, horizon, tolerance, needs_income })
+}
+
+/// Shows the proposed allocation and lets the user of the program change any asset class's
+/// percentage before accepting it. Only allocations that add up to 100 can be accepted.
+pub fn review_proposal(mut proposal: Account) -> Result<Account, String> {
+    loop {
+        println!("Proposed {}", proposal);
+        println!("1. Accept\t2. Change an asset class\t3. Cancel");
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        match choice.trim().parse().unwrap_or(0) {
+            1 => {
+                if proposal.is_full_allocation() {
+                    return Ok(proposal);
+                }
+                println!("Allocation adds up to {}%, it must add up to 100%", proposal.get_total_value());
+            }
+            2 => {
+                println!("1. Domestic\t2. International\t3. Bonds\t4. Real Estate");
+                let mut class = String::new();
+                io::stdin().read_line(&mut class)
+                    .expect("Failed to read line");
+                let class = match class.trim().parse().unwrap_or(0) {
+                    1 => AssetClass::Domestic,
+                    2 => AssetClass::International,
+                    3 => AssetClass::Bond,
+                    4 => AssetClass::RealEstate,
+                    _ => continue,
+                };
+                let percent = read_number("New percent (0-100) for this asset class?") as f64;
+                let change = percent - proposal.get_asset_value(class.clone());
+                proposal.add_asset(Asset::new(class, change));
+            }
+            3 => return Err("Cancelled target allocation".to_string()),
+            _ => continue,
+        }
+    }
+}
+
+#[test]
+fn age_minus_ten_in_bonds() {
+    let answers = Answers { age: 40, horizon: 25, tolerance: RiskTolerance::Moderate, needs_income: false };
+    let allocation = propose(&answers, &Rules::standard());
+    assert_eq!(30.0, allocation.get_asset_value(AssetClass::Bond));
+    assert_eq!(21.0, allocation.get_asset_value(AssetClass::International));
+    assert_eq!(7.0, allocation.get_asset_value(AssetClass::RealEstate));
+    assert_eq!(42.0, allocation.get_asset_value(AssetClass::Domestic));
+    assert_eq!(100.0, allocation.get_total_value());
+}
+
+#[test]
+fn short_horizon_and_income_raise_bonds() {
+    let answers = Answers { age: 25, horizon: 3, tolerance: RiskTolerance::Aggressive, needs_income: true };
+    let allocation = propose(&answers, &Rules::standard());
+    assert_eq!(50.0, allocation.get_asset_value(AssetClass::Bond));
+}
+
+#[test]
+fn preset_rules_are_configurable() {
+    let rules = Rules::parse("bond_rule,preset\nmoderate_bonds,35\nreal_estate_share,0\n").unwrap();
+    let answers = Answers { age: 60, horizon: 10, tolerance: RiskTolerance::Moderate, needs_income: false };
+    let allocation = propose(&answers, &rules);
+    assert_eq!(35.0, allocation.get_asset_value(AssetClass::Bond));
+    assert_eq!(0.0, allocation.get_asset_value(AssetClass::RealEstate));
+    assert!(Rules::parse("bond_rule,astrology\n").is_err());
+}
+
+#[test]
+fn rules_can_be_given_in_any_order() {
+    let rules = Rules::parse("bond_offset,-20\nbond_rule,age\n").unwrap();
+    assert!(rules.bond_rule == BondRule::AgeInBonds(-20));
+    assert!(Rules::parse("bond_offset,-20\nbond_rule,preset\n").is_err());
+    assert!(Rules::parse("international_share,0.7\nreal_estate_share,0.4\n").is_err());
+    assert!(Rules::parse("real_estate_share,1.5\n").is_err());
+}
+
+#[test]
+fn rounded_shares_never_leave_domestic_negative() {
+    let rules = Rules::parse("bond_rule,preset\nmoderate_bonds,45\ninternational_share,0.5\nreal_estate_share,0.5\n")
+        .unwrap();
+    let answers = Answers { age: 60, horizon: 10, tolerance: RiskTolerance::Moderate, needs_income: false };
+    let allocation = propose(&answers, &rules);
+    assert_eq!(28.0, allocation.get_asset_value(AssetClass::International));
+    assert_eq!(27.0, allocation.get_asset_value(AssetClass::RealEstate));
+    assert_eq!(0.0, allocation.get_asset_value(AssetClass::Domestic));
+    assert!(allocation.is_full_allocation());
+}
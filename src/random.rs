@@ -0,0 +1,49 @@
+/// A small seedable pseudo-random number generator (xorshift64*), so simulations can be repeated
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // xorshift never leaves a zero state, so mix the seed into a fixed odd constant
+        Random {
+            state: seed ^ 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Returns a number between 0 and 1, never exactly 0
+    pub fn uniform(&mut self) -> f64 {
+        ((self.next_u64() >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+    }
+
+    /// Returns a draw from the standard normal distribution (Box-Muller)
+    pub fn normal(&mut self) -> f64 {
+        let (u, v) = (self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[test]
+fn same_seed_gives_same_draws() {
+    let mut first = Random::new(42);
+    let mut second = Random::new(42);
+    for _ in 0..10 {
+        assert_eq!(first.next_u64(), second.next_u64());
+    }
+    assert!(Random::new(1).next_u64() != Random::new(2).next_u64());
+}
+
+#[test]
+fn normal_draws_have_unit_spread() {
+    let mut random = Random::new(7);
+    let draws: Vec<f64> = (0..20_000).map(|_| random.normal()).collect();
+    assert!(crate::stats::mean(&draws).abs() < 0.05);
+    assert!((crate::stats::std_dev(&draws) - 1.0).abs() < 0.05);
+}
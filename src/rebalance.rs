@@ -0,0 +1,121 @@
+use crate::account::Account;
+use crate::asset::{Asset, AssetClass};
+
+/// Trades smaller than this many dollars are not worth placing
+pub const MINIMUM_TRADE: f64 = 0.01;
+
+/// Returns how many dollars of each asset class the holdings have above (+) or below (-) the share
+/// the target allocation gives it
+pub fn drift(holdings: &Account, target: &Account) -> Account {
+    let total = holdings.get_total_value();
+    let mut drift = Account::new(holdings.classification.clone());
+    for class in AssetClass::all() {
+        let wanted = 0.01 * total * target.get_asset_value(class.clone());
+        drift.add_asset(Asset::new(class.clone(), holdings.get_asset_value(class) - wanted));
+    }
+    drift
+}
+
+/// Returns the dollars of each asset class to buy (+) or sell (-) to bring the holdings to the
+/// target allocation
+pub fn trades_to_target(holdings: &Account, target: &Account) -> Account {
+    drift(holdings, target).multiply(-1.0)
+}
+
+/// Splits new money (or a withdrawal, when negative) across asset classes so the holdings move
+/// toward the target allocation without selling anything else. Money goes to the asset classes
+/// furthest below target, withdrawals come from those furthest above.
+///
+/// # Examples
+///
+/// ```
+/// // 60/40 target, holding 50 domestic and 50 bonds: all 20 goes to domestic
+/// let trades = contribution_trades(&holdings, &target, 20.0);
+/// assert_eq!(20.0, trades.get_asset_value(AssetClass::Domestic));
+/// ```
+pub fn contribution_trades(holdings: &Account, target: &Account, amount: f64) -> Account {
+    let total = holdings.get_total_value() + amount;
+    let mut gaps = Vec::new();
+    for class in AssetClass::all() {
+        let gap = 0.01 * total * target.get_asset_value(class.clone()) - holdings.get_asset_value(class.clone());
+        // Only gaps pointing the same way as the money can be closed by it
+        gaps.push((class, if gap * amount > 0.0 { gap.abs() } else { 0.0 }));
+    }
+    let sum: f64 = gaps.iter().map(|(_, gap)| gap).sum();
+    let mut trades = Account::new(holdings.classification.clone());
+    for (class, gap) in gaps {
+        let share = if sum > 0.0 {
+            gap / sum
+        } else {
+            0.01 * target.get_asset_value(class.clone())
+        };
+        trades.add_asset(Asset::new(class, amount * share));
+    }
+    trades
+}
+
+/// Buys and sells the trades within the holdings
+pub fn apply(holdings: &mut Account, trades: &Account) {
+    for trade in &trades.assets {
+        holdings.add_asset(Asset::new(trade.class.clone(), trade.value));
+    }
+}
+
+/// Returns the number of trades worth placing and the dollars bought by them
+pub fn trade_count_and_volume(trades: &Account) -> (usize, f64) {
+    let placed: Vec<&Asset> = trades.assets.iter().filter(|t| t.value.abs() >= MINIMUM_TRADE).collect();
+    let bought = placed.iter().filter(|t| t.value > 0.0).map(|t| t.value).sum();
+    (placed.len(), bought)
+}
+
+/// Returns the largest gap, in percentage points, between an asset class's share of the holdings
+/// and its target
+pub fn largest_drift(holdings: &Account, target: &Account) -> f64 {
+    let total = holdings.get_total_value();
+    if total <= 0.0 {
+        return 0.0;
+    }
+    drift(holdings, target).assets.iter()
+        .map(|d| (100.0 * d.value / total).abs())
+        .fold(0.0, f64::max)
+}
+
+/// A 60% domestic, 40% bond target allocation shared by the tests
+#[cfg(test)]
+pub fn sixty_forty() -> Account {
+    use crate::account::AccountType;
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 60.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 40.0));
+    target
+}
+
+#[cfg(test)]
+fn half_and_half() -> Account {
+    use crate::account::AccountType;
+    let mut holdings = Account::new(AccountType::Taxable);
+    holdings.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    holdings.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    holdings
+}
+
+#[test]
+fn rebalancing_brings_holdings_to_target() {
+    let (mut holdings, target) = (half_and_half(), sixty_forty());
+    let trades = trades_to_target(&holdings, &target);
+    assert_eq!((2, 10.0), trade_count_and_volume(&trades));
+    apply(&mut holdings, &trades);
+    assert_eq!(60.0, holdings.get_asset_value(AssetClass::Domestic));
+    assert_eq!(40.0, holdings.get_asset_value(AssetClass::Bond));
+    assert_eq!(0.0, largest_drift(&holdings, &target));
+}
+
+#[test]
+fn contributions_go_to_underweight_classes() {
+    let (holdings, target) = (half_and_half(), sixty_forty());
+    let trades = contribution_trades(&holdings, &target, 20.0);
+    assert_eq!(20.0, trades.get_asset_value(AssetClass::Domestic));
+    assert_eq!(0.0, trades.get_asset_value(AssetClass::Bond));
+    let withdrawal = contribution_trades(&holdings, &target, -10.0);
+    assert_eq!(-10.0, withdrawal.get_asset_value(AssetClass::Bond));
+}
@@ -0,0 +1,164 @@
+use std::fs;
+use crate::asset::AssetClass;
+use crate::date::Date;
+use crate::stats;
+
+/// The return of each asset class over one period, as a fraction (0.01 is 1%)
+pub struct PeriodReturns {
+    pub date: Date,
+    pub returns: Vec<(AssetClass, f64)>,
+}
+
+/// Returns the asset class's return among the returns of a period, or no return if it is missing
+pub fn return_of(returns: &[(AssetClass, f64)], class: &AssetClass) -> f64 {
+    returns.iter().find(|(c, _)| c == class).map(|(_, r)| *r).unwrap_or(0.0)
+}
+
+/// A history of asset class returns loaded from a local CSV file
+pub struct ReturnHistory {
+    pub classes: Vec<AssetClass>,
+    pub periods: Vec<PeriodReturns>,
+}
+
+impl ReturnHistory {
+    /// Loads a CSV file of returns
+    pub fn load(path: &str) -> Result<ReturnHistory, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read return file {}: {}", path, why))?;
+        ReturnHistory::parse(&text)
+    }
+
+    /// Loads a CSV file of prices and turns them into returns
+    pub fn load_prices(path: &str) -> Result<ReturnHistory, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read price history file {}: {}", path, why))?;
+        ReturnHistory::parse_prices(&text)
+    }
+
+    /// Parses CSV text whose header names a date column followed by asset classes, and whose rows
+    /// hold each period's returns as fractions
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let history = ReturnHistory::parse("date,domestic,bond\n2019-01-31,0.085,0.011\n").unwrap();
+    /// assert_eq!(0.085, return_of(&history.periods[0].returns, &AssetClass::Domestic));
+    /// ```
+    pub fn parse(text: &str) -> Result<ReturnHistory, String> {
+        let (classes, rows) = parse_table(text)?;
+        let periods = rows.into_iter()
+            .map(|(date, values)| PeriodReturns { date, returns: classes.iter().cloned().zip(values).collect() })
+            .collect();
+        Ok(ReturnHistory { classes, periods })
+    }
+
+    /// Parses CSV text laid out like a return file, but holding the price (or index level) of each
+    /// asset class on each date. Every row after the first becomes the return since the row before.
+    pub fn parse_prices(text: &str) -> Result<ReturnHistory, String> {
+        let (classes, rows) = parse_table(text)?;
+        if rows.iter().any(|(_, prices)| prices.iter().any(|p| *p <= 0.0)) {
+            return Err("Prices must be positive".to_string());
+        }
+        let periods = rows.windows(2)
+            .map(|pair| {
+                let returns = classes.iter().cloned()
+                    .zip(pair[0].1.iter().zip(&pair[1].1).map(|(before, after)| after / before - 1.0))
+                    .collect();
+                PeriodReturns { date: pair[1].0, returns }
+            })
+            .collect();
+        Ok(ReturnHistory { classes, periods })
+    }
+
+    /// Estimates how many periods make up a year from the spacing of the dates, assuming monthly
+    /// periods when there are too few dates to tell
+    pub fn periods_per_year(&self) -> f64 {
+        match (self.periods.first(), self.periods.last()) {
+            (Some(first), Some(last)) if self.periods.len() > 1 => {
+                let days = first.date.days_until(&last.date) as f64;
+                365.25 * (self.periods.len() - 1) as f64 / days
+            }
+            _ => 12.0,
+        }
+    }
+
+    /// Returns every period's return for one asset class
+    pub fn series(&self, class: &AssetClass) -> Vec<f64> {
+        self.periods.iter().map(|p| return_of(&p.returns, class)).collect()
+    }
+
+    /// Estimates the annualized covariance of returns between the asset classes
+    pub fn covariance(&self, classes: &[AssetClass]) -> Vec<Vec<f64>> {
+        let series: Vec<Vec<f64>> = classes.iter().map(|c| self.series(c)).collect();
+        let means: Vec<f64> = series.iter().map(|s| stats::mean(s)).collect();
+        let count = self.periods.len().max(2) as f64 - 1.0;
+        let scale = self.periods_per_year();
+        series.iter().enumerate()
+            .map(|(i, a)| series.iter().enumerate()
+                 .map(|(j, b)| {
+                     let sum: f64 = a.iter().zip(b).map(|(x, y)| (x - means[i]) * (y - means[j])).sum();
+                     scale * sum / count
+                 })
+                 .collect())
+            .collect()
+    }
+}
+
+/// A date and the number in each column of a CSV row
+type Row = (Date, Vec<f64>);
+
+/// Reads a header of date followed by asset classes, and rows of a date followed by a number per
+/// asset class in increasing date order
+fn parse_table(text: &str) -> Result<(Vec<AssetClass>, Vec<Row>), String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => header,
+        None => return Err("File is empty".to_string()),
+    };
+    let classes = header.split(',')
+        .skip(1)
+        .map(AssetClass::parse)
+        .collect::<Result<Vec<AssetClass>, String>>()?;
+    let mut rows: Vec<Row> = Vec::new();
+    for (number, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != classes.len() + 1 {
+            return Err(format!("Line {}: expected {} columns but found {}", number + 1,
+                               classes.len() + 1, fields.len()));
+        }
+        let date = Date::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+        if let Some((last, _)) = rows.last() {
+            if *last >= date {
+                return Err(format!("Line {}: dates must be in increasing order", number + 1));
+            }
+        }
+        let values = fields[1..].iter()
+            .map(|f| f.parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        rows.push((date, values));
+    }
+    Ok((classes, rows))
+}
+
+#[test]
+fn return_history_reads_classes_from_header() {
+    let history = ReturnHistory::parse("date,Domestic,Real Estate\n2019-01-31,0.08,0.1\n2019-02-28,-0.02,0.03\n").unwrap();
+    assert_eq!(2, history.periods[0].returns.len());
+    assert_eq!(0.03, return_of(&history.periods[1].returns, &AssetClass::RealEstate));
+    assert_eq!(0.0, return_of(&history.periods[0].returns, &AssetClass::Bond));
+}
+
+#[test]
+fn return_history_rejects_unknown_class_and_short_rows() {
+    assert!(ReturnHistory::parse("date,gold\n2019-01-31,0.01\n").is_err());
+    assert!(ReturnHistory::parse("date,bond,domestic\n2019-01-31,0.01\n").is_err());
+}
+
+#[test]
+fn price_history_becomes_returns() {
+    let history = ReturnHistory::parse_prices("date,domestic,bond\n2019-01-31,100,50\n2019-02-28,110,49\n2019-03-31,99,49\n").unwrap();
+    assert_eq!(2, history.periods.len());
+    assert!((history.series(&AssetClass::Domestic)[1] + 0.1).abs() < 1e-12);
+    assert!((history.series(&AssetClass::Bond)[0] + 0.02).abs() < 1e-12);
+    assert!(history.covariance(&[AssetClass::Bond])[0][0] > 0.0);
+}
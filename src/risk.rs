@@ -0,0 +1,132 @@
+use crate::account::Account;
+use crate::asset::AssetClass;
+use crate::returns::{return_of, ReturnHistory};
+use crate::stats;
+
+/// Confidence level of the value-at-risk and expected shortfall
+pub const CONFIDENCE: f64 = 0.95;
+
+/// Annual return of cash used by the Sharpe ratio when the user does not give one
+pub const DEFAULT_RISK_FREE_RATE: f64 = 0.02;
+
+/// Risk of an allocation held through a return history, rebalanced back to its weights every
+/// period. Value-at-risk and expected shortfall are losses over a single period of the history,
+/// as positive fractions.
+pub struct RiskMetrics {
+    pub volatility: f64,
+    pub value_at_risk: f64,
+    pub expected_shortfall: f64,
+    pub max_drawdown: f64,
+    pub sharpe_ratio: f64,
+}
+
+/// Returns the return of each period of the history for an allocation, or holdings, rebalanced
+/// back to its weights every period
+pub fn portfolio_returns(history: &ReturnHistory, allocation: &Account) -> Vec<f64> {
+    let total = allocation.get_total_value();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    let weights: Vec<(AssetClass, f64)> = allocation.assets.iter()
+        .map(|asset| (asset.class.clone(), asset.value / total))
+        .collect();
+    history.periods.iter()
+        .map(|period| weights.iter().map(|(class, weight)| weight * return_of(&period.returns, class)).sum())
+        .collect()
+}
+
+/// Measures the risk of an allocation over the history, or nothing if the allocation is empty or
+/// the history too short
+pub fn measure(history: &ReturnHistory, allocation: &Account, risk_free_rate: f64) -> Option<RiskMetrics> {
+    let returns = portfolio_returns(history, allocation);
+    if returns.len() < 2 {
+        return None;
+    }
+    let periods_per_year = history.periods_per_year();
+    let volatility = stats::std_dev(&returns) * periods_per_year.sqrt();
+    let mut sorted = returns.clone();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let cutoff = stats::percentile(&sorted, 1.0 - CONFIDENCE);
+    let tail: Vec<f64> = sorted.iter().cloned().filter(|r| *r <= cutoff).collect();
+    let annual_return = stats::mean(&returns) * periods_per_year;
+    Some(RiskMetrics {
+        volatility,
+        value_at_risk: (-cutoff).max(0.0),
+        expected_shortfall: (-stats::mean(&tail)).max(0.0),
+        max_drawdown: stats::max_drawdown(&returns),
+        sharpe_ratio: if volatility > 0.0 { (annual_return - risk_free_rate) / volatility } else { 0.0 },
+    })
+}
+
+/// Names the length of one period of the history
+fn period_name(periods_per_year: f64) -> &'static str {
+    match periods_per_year.round() as u32 {
+        1 => "year",
+        4 => "quarter",
+        12 => "month",
+        50..=53 => "week",
+        250..=366 => "day",
+        _ => "period",
+    }
+}
+
+/// Compares the risk of the current holdings with the risk of the target allocation. Dollar
+/// losses are for a portfolio the size of the holdings.
+pub fn report(history: &ReturnHistory, holdings: &Account, target: &Account, risk_free_rate: f64) -> String {
+    let current = match measure(history, holdings, risk_free_rate) {
+        Some(metrics) => metrics,
+        None => return "Not enough holdings or return history to measure risk\n".to_string(),
+    };
+    let target = match measure(history, target, risk_free_rate) {
+        Some(metrics) => metrics,
+        None => return "Set a target allocation to compare its risk\n".to_string(),
+    };
+    let value = holdings.get_total_value();
+    let period = period_name(history.periods_per_year());
+    let mut disp = format!("Risk over {} {}s, {:.0}% confidence\n", history.periods.len(), period, 100.0 * CONFIDENCE);
+    disp.push_str(&format!("{:<28}{:>12}{:>12}{:>12}\n", "", "Current", "Target", "Difference"));
+    let rows = [
+        ("Annual volatility", current.volatility, target.volatility),
+        ("Value-at-risk", current.value_at_risk, target.value_at_risk),
+        ("Expected shortfall", current.expected_shortfall, target.expected_shortfall),
+        ("Max drawdown", current.max_drawdown, target.max_drawdown),
+    ];
+    for (name, current, target) in rows.iter() {
+        disp.push_str(&format!("{:<28}{:>11.2}%{:>11.2}%{:>11.2}%\n", name, 100.0 * current, 100.0 * target,
+                               100.0 * (current - target)));
+    }
+    let rows = [
+        (format!("Value-at-risk ($ per {})", period), current.value_at_risk, target.value_at_risk),
+        (format!("Shortfall ($ per {})", period), current.expected_shortfall, target.expected_shortfall),
+    ];
+    for (name, current, target) in rows.iter() {
+        disp.push_str(&format!("{:<28}{:>12.2}{:>12.2}{:>12.2}\n", name, value * current, value * target,
+                               value * (current - target)));
+    }
+    disp.push_str(&format!("{:<28}{:>12.2}{:>12.2}{:>12.2}\n", "Sharpe ratio", current.sharpe_ratio,
+                           target.sharpe_ratio, current.sharpe_ratio - target.sharpe_ratio));
+    disp
+}
+
+#[test]
+fn risk_metrics_follow_the_allocation() {
+    use crate::account::AccountType;
+    use crate::asset::Asset;
+    let history = ReturnHistory::parse("date,domestic,bond\n\
+                                        2019-01-31,0.10,0.01\n\
+                                        2019-02-28,-0.20,0.02\n\
+                                        2019-03-31,0.05,0.00\n\
+                                        2019-04-30,0.02,0.01\n").unwrap();
+    let mut stocks = Account::new(AccountType::Allocation);
+    stocks.add_asset(Asset::new(AssetClass::Domestic, 100.0));
+    let metrics = measure(&history, &stocks, 0.0).unwrap();
+    assert!((metrics.value_at_risk - 0.20).abs() < 1e-12);
+    assert!((metrics.max_drawdown - 0.20).abs() < 1e-12);
+
+    let mut balanced = Account::new(AccountType::Allocation);
+    balanced.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    balanced.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    let balanced = measure(&history, &balanced, 0.0).unwrap();
+    assert!((balanced.value_at_risk - 0.09).abs() < 1e-12);
+    assert!(balanced.volatility < metrics.volatility);
+}
@@ -0,0 +1,198 @@
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::returns::ReturnHistory;
+
+/// Iterations allowed for the risk parity weights to settle
+const MAX_ITERATIONS: usize = 10_000;
+
+/// How a target allocation is built from the riskiness of each asset class
+#[derive(Clone, Copy, PartialEq)]
+pub enum Method {
+    /// Each asset class contributes the same share of the portfolio's volatility
+    RiskParity,
+    /// Each asset class is weighted by one over its volatility
+    InverseVolatility,
+}
+
+/// The annualized covariance of the asset classes in a return history
+pub struct RiskModel {
+    pub classes: Vec<AssetClass>,
+    pub covariance: Vec<Vec<f64>>,
+}
+
+impl RiskModel {
+    /// Estimates the covariance of every asset class in the history. Needs at least two periods.
+    pub fn from_history(history: &ReturnHistory) -> Result<RiskModel, String> {
+        if history.periods.len() < 2 {
+            return Err("At least two periods of returns are needed to estimate volatility".to_string());
+        }
+        let covariance = history.covariance(&history.classes);
+        if let Some(index) = covariance.iter().enumerate().position(|(i, row)| row[i] <= 0.0) {
+            return Err(format!("{} has no volatility in the price history", history.classes[index]));
+        }
+        Ok(RiskModel { classes: history.classes.clone(), covariance })
+    }
+
+    /// Returns the annual volatility of each asset class
+    pub fn volatilities(&self) -> Vec<f64> {
+        self.covariance.iter().enumerate().map(|(i, row)| row[i].sqrt()).collect()
+    }
+
+    /// Returns the annual volatility of a portfolio with the given weights
+    pub fn volatility(&self, weights: &[f64]) -> f64 {
+        let marginal = self.marginal(weights);
+        weights.iter().zip(&marginal).map(|(w, m)| w * m).sum::<f64>().max(0.0).sqrt()
+    }
+
+    fn marginal(&self, weights: &[f64]) -> Vec<f64> {
+        self.covariance.iter()
+            .map(|row| row.iter().zip(weights).map(|(c, w)| c * w).sum())
+            .collect()
+    }
+
+    /// Returns each asset class's share of the portfolio's variance. The shares add up to 1.
+    pub fn risk_contributions(&self, weights: &[f64]) -> Vec<f64> {
+        let marginal = self.marginal(weights);
+        let parts: Vec<f64> = weights.iter().zip(&marginal).map(|(w, m)| w * m).collect();
+        let total: f64 = parts.iter().sum();
+        if total <= 0.0 {
+            return vec![0.0; weights.len()];
+        }
+        parts.iter().map(|p| p / total).collect()
+    }
+
+    /// Returns weights, as fractions adding up to 1, proportional to one over each volatility
+    pub fn inverse_volatility(&self) -> Vec<f64> {
+        normalize(self.volatilities().iter().map(|v| 1.0 / v).collect())
+    }
+
+    /// Returns weights, as fractions adding up to 1, under which every asset class contributes the
+    /// same share of the portfolio's variance. Starts from inverse volatility and solves for one
+    /// weight at a time, holding the others fixed, the weight that makes its variance contribution
+    /// match the others. Each step solves a quadratic with a positive root, so weights stay positive
+    /// even when asset classes move against each other.
+    pub fn risk_parity(&self) -> Result<Vec<f64>, String> {
+        let count = self.classes.len();
+        let mut weights = self.inverse_volatility();
+        for _ in 0..MAX_ITERATIONS {
+            let contributions = self.risk_contributions(&weights);
+            if contributions.iter().all(|c| (c - 1.0 / count as f64).abs() < 1e-10) {
+                return Ok(normalize(weights));
+            }
+            let budget = 1.0 / count as f64;
+            for i in 0..count {
+                let variance = self.covariance[i][i];
+                let others: f64 = (0..count).filter(|j| *j != i).map(|j| self.covariance[i][j] * weights[j]).sum();
+                weights[i] = (-others + (others * others + 4.0 * variance * budget).sqrt()) / (2.0 * variance);
+            }
+        }
+        Err("Risk parity weights did not settle, try inverse volatility".to_string())
+    }
+
+    /// Returns the weights for the method
+    pub fn weights(&self, method: Method) -> Result<Vec<f64>, String> {
+        match method {
+            Method::RiskParity => self.risk_parity(),
+            Method::InverseVolatility => Ok(self.inverse_volatility()),
+        }
+    }
+
+    /// Returns the fraction of the holdings or allocation in each asset class of the model
+    pub fn weights_of(&self, account: &Account) -> Vec<f64> {
+        normalize(self.classes.iter().map(|c| account.get_asset_value(c.clone()).max(0.0)).collect())
+    }
+
+    /// Turns weights into a target allocation in whole percentages that add up to 100, giving the
+    /// leftover percentages to the asset classes that lost the most to rounding
+    pub fn allocation(&self, weights: &[f64]) -> Account {
+        let exact: Vec<f64> = weights.iter().map(|w| 100.0 * w).collect();
+        let mut percents: Vec<f64> = exact.iter().map(|p| p.floor()).collect();
+        let mut order: Vec<usize> = (0..exact.len()).collect();
+        order.sort_by(|a, b| (exact[*b] - percents[*b]).partial_cmp(&(exact[*a] - percents[*a]))
+                      .unwrap_or(std::cmp::Ordering::Equal));
+        let short = (100.0 - percents.iter().sum::<f64>()).round() as usize;
+        for index in order.into_iter().take(short) {
+            percents[index] += 1.0;
+        }
+        let mut allocation = Account::new(AccountType::Allocation);
+        for (class, percent) in self.classes.iter().zip(percents) {
+            allocation.add_asset(Asset::new(class.clone(), percent));
+        }
+        allocation
+    }
+}
+
+fn normalize(weights: Vec<f64>) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    if total <= 0.0 {
+        return weights;
+    }
+    weights.iter().map(|w| w / total).collect()
+}
+
+/// Compares the weight and share of risk of each asset class under the current holdings and the
+/// proposed target
+pub fn report(model: &RiskModel, current: &Account, target: &Account) -> String {
+    let current_weights = model.weights_of(current);
+    let target_weights = model.weights_of(target);
+    let current_risk = model.risk_contributions(&current_weights);
+    let target_risk = model.risk_contributions(&target_weights);
+    let volatilities = model.volatilities();
+    let mut disp = format!("{:<15}{:>12}{:>12}{:>14}{:>12}{:>14}\n", "Class", "Volatility",
+                           "Current", "Current risk", "Target", "Target risk");
+    for (i, class) in model.classes.iter().enumerate() {
+        disp.push_str(&format!("{:<15}{:>11.2}%{:>11.2}%{:>13.2}%{:>11.2}%{:>13.2}%\n", class.to_string(),
+                               100.0 * volatilities[i], 100.0 * current_weights[i], 100.0 * current_risk[i],
+                               100.0 * target_weights[i], 100.0 * target_risk[i]));
+    }
+    disp.push_str(&format!("Portfolio volatility: current {:.2}%, target {:.2}%\n",
+                           100.0 * model.volatility(&current_weights), 100.0 * model.volatility(&target_weights)));
+    disp
+}
+
+#[cfg(test)]
+fn test_model() -> RiskModel {
+    RiskModel {
+        classes: vec![AssetClass::Domestic, AssetClass::Bond, AssetClass::RealEstate],
+        covariance: vec![
+            vec![0.0256, 0.0008, 0.0182],
+            vec![0.0008, 0.0025, 0.0019],
+            vec![0.0182, 0.0019, 0.0361],
+        ],
+    }
+}
+
+#[test]
+fn risk_parity_equalizes_risk_contributions() {
+    let model = test_model();
+    let weights = model.risk_parity().unwrap();
+    assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    for contribution in model.risk_contributions(&weights) {
+        assert!((contribution - 1.0 / 3.0).abs() < 1e-6);
+    }
+    // The least volatile asset class carries the most weight
+    assert!(weights[1] > weights[0] && weights[1] > weights[2]);
+}
+
+#[test]
+fn inverse_volatility_rounds_to_whole_percents() {
+    let model = test_model();
+    let weights = model.inverse_volatility();
+    assert!((weights[0] / weights[1] - 0.05 / 0.16).abs() < 1e-9);
+    let allocation = model.allocation(&weights);
+    assert_eq!(100.0, allocation.get_total_value());
+    assert_eq!(allocation.get_asset_value(AssetClass::Bond).fract(), 0.0);
+}
+
+#[test]
+fn risk_parity_handles_asset_classes_that_move_against_each_other() {
+    let model = RiskModel {
+        classes: vec![AssetClass::Domestic, AssetClass::Bond],
+        covariance: vec![vec![0.04, -0.018], vec![-0.018, 0.01]],
+    };
+    let weights = model.risk_parity().unwrap();
+    assert!(weights.iter().all(|w| w.is_finite() && *w > 0.0));
+    for contribution in model.risk_contributions(&weights) {
+        assert!((contribution - 0.5).abs() < 1e-6);
+    }
+}
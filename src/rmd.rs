@@ -0,0 +1,307 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::rebalance;
+use crate::snapshot::Snapshot;
+
+/// An IRS life expectancy table: the divisor the prior year-end balance is split by at each age
+pub struct LifeTable {
+    pub divisors: Vec<(i32, f64)>,
+}
+
+impl LifeTable {
+    /// Uniform Lifetime Table used by owners of their own accounts, in effect from 2022
+    pub fn uniform() -> LifeTable {
+        let divisors = [
+            27.4, 26.5, 25.5, 24.6, 23.7, 22.9, 22.0, 21.1, 20.2, 19.4, 18.5, 17.7, 16.8, 16.0, 15.2,
+            14.4, 13.7, 12.9, 12.2, 11.5, 10.8, 10.1, 9.5, 8.9, 8.4, 7.8, 7.3, 6.8, 6.4, 6.0, 5.6, 5.2,
+            4.9, 4.6, 4.3, 4.1, 3.9, 3.7, 3.5, 3.4, 3.3, 3.1, 3.0, 2.9, 2.8, 2.7, 2.5, 2.3, 2.0,
+        ];
+        LifeTable { divisors: divisors.iter().enumerate().map(|(i, d)| (72 + i as i32, *d)).collect() }
+    }
+
+    /// Single Life Expectancy Table used by beneficiaries of inherited accounts, in effect from 2022
+    pub fn single() -> LifeTable {
+        let divisors = [
+            84.6, 83.7, 82.8, 81.8, 80.8, 79.8, 78.8, 77.9, 76.9, 75.9, 74.9, 73.9, 72.9, 71.9, 70.9,
+            69.9, 69.0, 68.0, 67.0, 66.0, 65.0, 64.1, 63.1, 62.1, 61.1, 60.2, 59.2, 58.2, 57.3, 56.3,
+            55.3, 54.4, 53.4, 52.5, 51.5, 50.5, 49.6, 48.6, 47.7, 46.7, 45.7, 44.8, 43.8, 42.9, 41.9,
+            41.0, 40.0, 39.0, 38.1, 37.1, 36.2, 35.3, 34.3, 33.4, 32.5, 31.6, 30.6, 29.8, 28.9, 28.0,
+            27.1, 26.2, 25.4, 24.5, 23.7, 22.9, 22.0, 21.2, 20.4, 19.6, 18.8, 18.0, 17.2, 16.4, 15.6,
+            14.8, 14.1, 13.3, 12.6, 11.9, 11.2, 10.5, 9.9, 9.3, 8.7, 8.1, 7.6, 7.1, 6.6, 6.1, 5.7,
+            5.3, 4.9, 4.6, 4.3, 4.0, 3.7, 3.4, 3.2, 3.0, 2.8, 2.6, 2.5, 2.3, 2.2, 2.1, 2.1, 2.1, 2.0,
+        ];
+        LifeTable { divisors: divisors.iter().enumerate().map(|(i, d)| (i as i32, *d)).collect() }
+    }
+
+    /// Loads a life expectancy table from a file
+    pub fn load(path: &str) -> Result<LifeTable, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read life expectancy file {}: {}", path, why))?;
+        LifeTable::parse(&text)
+    }
+
+    /// Parses lines of age and divisor, with ages in increasing order. A header line starting with
+    /// "age" is skipped.
+    pub fn parse(text: &str) -> Result<LifeTable, String> {
+        let mut divisors: Vec<(i32, f64)> = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if number == 0 && fields[0].eq_ignore_ascii_case("age") {
+                continue;
+            }
+            if fields.len() != 2 {
+                return Err(format!("Line {}: expected age,divisor but found {}", number + 1, line));
+            }
+            let age = fields[0].parse::<i32>()
+                .map_err(|_| format!("Line {}: invalid age {}", number + 1, fields[0]))?;
+            let divisor = match fields[1].parse::<f64>() {
+                Ok(divisor) if divisor > 0.0 => divisor,
+                _ => return Err(format!("Line {}: divisor must be a positive number", number + 1)),
+            };
+            if divisors.last().map(|(last, _)| age <= *last).unwrap_or(false) {
+                return Err(format!("Line {}: ages must increase", number + 1));
+            }
+            divisors.push((age, divisor));
+        }
+        if divisors.is_empty() {
+            return Err("Life expectancy file has no ages".to_string());
+        }
+        Ok(LifeTable { divisors })
+    }
+
+    /// Returns the divisor for an age. Ages past the end of the table use its last divisor.
+    pub fn divisor(&self, age: i32) -> Option<f64> {
+        match self.divisors.iter().find(|(a, _)| *a == age) {
+            Some((_, divisor)) => Some(*divisor),
+            None => self.divisors.last().filter(|(last, _)| age > *last).map(|(_, divisor)| *divisor),
+        }
+    }
+}
+
+/// The tables for account owners and for beneficiaries
+pub struct LifeTables {
+    pub uniform: LifeTable,
+    pub single: LifeTable,
+}
+
+impl LifeTables {
+    pub fn standard() -> LifeTables {
+        LifeTables { uniform: LifeTable::uniform(), single: LifeTable::single() }
+    }
+}
+
+/// Returns the age required distributions start at for an owner born in the year
+pub fn start_age(birth_year: i32) -> i32 {
+    if birth_year <= 1950 {
+        72
+    } else if birth_year <= 1959 {
+        73
+    } else {
+        75
+    }
+}
+
+/// Who an inherited account came from
+pub struct Inheritance {
+    pub death_year: i32,
+    /// Whether the original owner had already started taking required distributions
+    pub owner_started: bool,
+    /// The beneficiary's age in the year after the owner's death
+    pub first_age: i32,
+}
+
+/// The distribution one account must make in a year
+pub struct Distribution {
+    pub account: usize,
+    pub balance: f64,
+    pub divisor: Option<f64>,
+    pub amount: f64,
+    /// Dollars of each asset class to sell to raise the distribution
+    pub sales: Account,
+    pub note: Option<String>,
+}
+
+/// Account types that follow the owner's own required distribution rules
+fn owner_rules(account_type: &AccountType) -> bool {
+    matches!(account_type, AccountType::Traditional | AccountType::Plan401k | AccountType::Sep | AccountType::Simple
+             | AccountType::Deferred457)
+}
+
+/// Returns each account's balance at the end of the year before, taken from the last snapshot
+/// recorded in that year and matched by account name. Accounts opened after it have no balance
+/// on record.
+pub fn prior_year_end_balances(history: &[Snapshot], accounts: &[Account], year: i32) -> Vec<Option<f64>> {
+    let snapshot = history.iter().rev().find(|s| s.date.year == year - 1);
+    accounts.iter()
+        .map(|account| snapshot.and_then(|s| s.account(&account.name)).map(|a| a.get_total_value()))
+        .collect()
+}
+
+/// Returns the distribution an account must make in the year and why, or None if it needs none
+fn required(account: &Account, balance: f64, year: i32, birth_year: i32, inheritance: Option<&Inheritance>,
+            tables: &LifeTables) -> Option<(Option<f64>, f64, Option<String>)> {
+    if owner_rules(&account.classification) {
+        let age = year - birth_year;
+        if age < start_age(birth_year) {
+            return None;
+        }
+        let divisor = tables.uniform.divisor(age)?;
+        let note = if age == start_age(birth_year) {
+            Some(format!("First distribution may wait until April 1, {}", year + 1))
+        } else {
+            None
+        };
+        Some((Some(divisor), balance / divisor, note))
+    } else if account.classification == AccountType::InheritedIra {
+        let inheritance = inheritance?;
+        let first_year = inheritance.death_year + 1;
+        if year < first_year {
+            return None;
+        }
+        let deadline = if inheritance.death_year >= 2020 { Some(inheritance.death_year + 10) } else { None };
+        if deadline == Some(year) {
+            return Some((None, account.get_total_value(), Some("Ten-year deadline: the account must be emptied".to_string())));
+        }
+        if let (Some(deadline), false) = (deadline, inheritance.owner_started) {
+            return Some((None, 0.0, Some(format!("No yearly distribution, but the account must be emptied by {}", deadline))));
+        }
+        let divisor = tables.single.divisor(inheritance.first_age)? - (year - first_year) as f64;
+        if divisor <= 1.0 {
+            return Some((None, account.get_total_value(), Some("Life expectancy used up: the account must be emptied".to_string())));
+        }
+        Some((Some(divisor), balance / divisor, deadline.map(|d| format!("Must be emptied by {}", d))))
+    } else {
+        None
+    }
+}
+
+/// Splits a sale across the account's asset classes, selling from the classes the household
+/// holds most above target first. Classes the account does not hold enough of are topped up
+/// from the rest of the account in proportion to what is left.
+fn sales(account: &Account, household: &Account, target: &Account, amount: f64) -> Account {
+    let wanted = if target.is_full_allocation() {
+        rebalance::contribution_trades(household, target, -amount).multiply(-1.0)
+    } else {
+        account.multiply(amount / account.get_total_value().max(rebalance::MINIMUM_TRADE))
+    };
+    let mut sold = Account::new(account.classification.clone());
+    let mut left = amount;
+    for class in AssetClass::all() {
+        let sale = wanted.get_asset_value(class.clone()).min(account.get_asset_value(class.clone())).max(0.0);
+        sold.add_asset(Asset::new(class, sale));
+        left -= sale;
+    }
+    let spare: f64 = account.get_total_value() - sold.get_total_value();
+    if left >= rebalance::MINIMUM_TRADE && spare > 0.0 {
+        for class in AssetClass::all() {
+            let room = account.get_asset_value(class.clone()) - sold.get_asset_value(class.clone());
+            sold.add_asset(Asset::new(class, left.min(spare) * room / spare));
+        }
+    }
+    sold
+}
+
+/// Works out every account's required distribution for the year from its prior year-end balance,
+/// falling back on today's balance when none was recorded, and what to sell in each account to
+/// raise it while moving the household toward the target
+pub fn plan(accounts: &[Account], balances: &[Option<f64>], year: i32, birth_year: i32,
+            inheritances: &[(usize, Inheritance)], tables: &LifeTables, target: &Account) -> Vec<Distribution> {
+    let mut household = Account::new(AccountType::Taxable);
+    for account in accounts {
+        rebalance::apply(&mut household, account);
+    }
+    let mut distributions = Vec::new();
+    for (index, account) in accounts.iter().enumerate() {
+        let inheritance = inheritances.iter().find(|(i, _)| *i == index).map(|(_, inheritance)| inheritance);
+        let balance = balances.get(index).copied().flatten();
+        let (divisor, amount, note) = match required(account, balance.unwrap_or(account.get_total_value()), year,
+                                                     birth_year, inheritance, tables) {
+            Some(required) => required,
+            None => continue,
+        };
+        let amount = amount.min(account.get_total_value());
+        let sold = sales(account, &household, target, amount);
+        rebalance::apply(&mut household, &sold.multiply(-1.0));
+        let note = match (balance, note) {
+            (None, Some(note)) => Some(format!("{}; no balance recorded for the end of {}, used today's", note, year - 1)),
+            (None, None) => Some(format!("No balance recorded for the end of {}, used today's", year - 1)),
+            (Some(_), note) => note,
+        };
+        distributions.push(Distribution {
+            account: index,
+            balance: balance.unwrap_or(account.get_total_value()),
+            divisor,
+            amount,
+            sales: sold,
+            note,
+        });
+    }
+    distributions
+}
+
+/// Lays out each account's required distribution and what to sell to raise it
+pub fn report(distributions: &[Distribution], accounts: &[Account], year: i32) -> String {
+    if distributions.is_empty() {
+        return format!("No required minimum distributions in {}", year);
+    }
+    let mut disp = format!("{:<22}{:>16}{:>9}{:>14}", "Account", "Balance", "Divisor", "RMD");
+    for class in AssetClass::all() {
+        disp.push_str(&format!("{:>15}", format!("Sell {}", class)));
+    }
+    disp.push('\n');
+    for distribution in distributions {
+        let divisor = distribution.divisor.map(|d| format!("{:.1}", d)).unwrap_or_else(|| "-".to_string());
+        disp.push_str(&format!("{:<22}{:>16}{:>9}{:>14}",
+                               accounts[distribution.account].name,
+                               distribution.balance.separated_string_with_fixed_place(2), divisor,
+                               distribution.amount.separated_string_with_fixed_place(2)));
+        for class in AssetClass::all() {
+            disp.push_str(&format!("{:>15}", distribution.sales.get_asset_value(class).separated_string_with_fixed_place(2)));
+        }
+        disp.push('\n');
+    }
+    let total: f64 = distributions.iter().map(|d| d.amount).sum();
+    disp.push_str(&format!("{:<22}{:>16}{:>9}{:>14}\n", "Total", "", "", total.separated_string_with_fixed_place(2)));
+    for distribution in distributions {
+        if let Some(note) = &distribution.note {
+            disp.push_str(&format!("{}: {}\n", accounts[distribution.account].name, note));
+        }
+    }
+    disp.push_str(&format!("Distributions are due by December 31, {}\n", year));
+    disp
+}
+
+#[test]
+fn owner_distributions_start_at_their_age() {
+    let tables = LifeTables::standard();
+    assert_eq!(Some(26.5), tables.uniform.divisor(73));
+    assert_eq!(Some(2.0), tables.uniform.divisor(125));
+    assert_eq!(75, start_age(1960));
+    let mut traditional = Account::new(AccountType::Traditional);
+    traditional.add_asset(Asset::new(AssetClass::Bond, 265_000.0));
+    let accounts = vec![traditional];
+    let target = Account::new(AccountType::Allocation);
+    assert!(plan(&accounts, &[None], 2026, 1954, &[], &tables, &target).is_empty());
+    let distributions = plan(&accounts, &[Some(265_000.0)], 2027, 1954, &[], &tables, &target);
+    assert_eq!(10_000.0, distributions[0].amount);
+    assert_eq!(10_000.0, distributions[0].sales.get_asset_value(AssetClass::Bond));
+}
+
+#[test]
+fn inherited_distributions_shrink_the_divisor_and_sell_toward_target() {
+    let tables = LifeTables::standard();
+    let mut inherited = Account::new(AccountType::InheritedIra);
+    inherited.add_asset(Asset::new(AssetClass::Domestic, 50_000.0));
+    inherited.add_asset(Asset::new(AssetClass::Bond, 50_000.0));
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 40.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 60.0));
+    let inheritance = Inheritance { death_year: 2015, owner_started: true, first_age: 50 };
+    let distributions = plan(&[inherited], &[Some(100_000.0)], 2018, 1966, &[(0, inheritance)], &tables, &target);
+    let divisor = 36.2 - 2.0;
+    assert!((distributions[0].amount - 100_000.0 / divisor).abs() < 1e-6);
+    assert_eq!(0.0, distributions[0].sales.get_asset_value(AssetClass::Bond));
+}
@@ -0,0 +1,301 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+use crate::transaction::{Transaction, TransactionKind};
+
+/// The state of a User's portfolio on a given date: what each account held, the resulting asset
+/// allocation and the target it was measured against
+pub struct Snapshot {
+    pub date: Date,
+    pub accounts: Vec<Account>,
+    pub allocation: Account,
+    pub target: Account,
+}
+
+impl Snapshot {
+    /// Captures a copy of the accounts, current allocation and target allocation
+    pub fn new(date: Date, accounts: &[Account], allocation: &Account, target: &Account) -> Snapshot {
+        Snapshot {
+            date,
+            accounts: accounts.iter().map(|a| a.snapshot()).collect(),
+            allocation: allocation.snapshot(),
+            target: target.snapshot(),
+        }
+    }
+
+    /// Returns the account with the given name as it was on the snapshot's date
+    pub fn account(&self, name: &str) -> Option<&Account> {
+        self.accounts.iter().find(|a| a.name == name)
+    }
+
+    /// Returns the combined value of all accounts in the snapshot
+    pub fn get_total_value(&self) -> f64 {
+        self.accounts.iter().map(|a| a.get_total_value()).sum()
+    }
+
+    /// Returns the share of the portfolio held in an asset class and how far that share was from
+    /// the target. Positive drift means the asset class was above target.
+    pub fn share_and_drift(&self, class: &AssetClass) -> (f64, f64) {
+        let share = self.allocation.get_asset_value(class.clone());
+        (share, share - self.target.get_asset_value(class.clone()))
+    }
+}
+
+/// Adds a snapshot to a history kept in date order. Only the latest snapshot of a day is kept.
+pub fn add_to_history(history: &mut Vec<Snapshot>, snapshot: Snapshot) {
+    history.retain(|s| s.date != snapshot.date);
+    let position = history.iter().position(|s| s.date > snapshot.date).unwrap_or(history.len());
+    history.insert(position, snapshot);
+}
+
+/// Writes the history to a CSV file with a line per account, current allocation and target of
+/// each snapshot
+pub fn save(history: &[Snapshot], path: &str) -> Result<(), String> {
+    fs::write(path, to_csv(history))
+        .map_err(|why| format!("Could not write history file {}: {}", path, why))
+}
+
+/// Formats the history as CSV of date, kind and name followed by a value per asset class. The kind
+/// is the account type for accounts, or allocation or target.
+pub fn to_csv(history: &[Snapshot]) -> String {
+    let mut csv = "date,kind,name".to_string();
+    for class in AssetClass::all() {
+        csv.push_str(&format!(",{}", class));
+    }
+    csv.push('\n');
+    for snapshot in history {
+        let mut rows: Vec<(&str, &str, &Account)> = snapshot.accounts.iter()
+            .map(|a| (a.classification.key(), a.name.as_str(), a))
+            .collect();
+        rows.push(("allocation", "", &snapshot.allocation));
+        rows.push(("target", "", &snapshot.target));
+        for (kind, name, account) in rows {
+            csv.push_str(&format!("{},{},{}", snapshot.date, kind, name));
+            for class in AssetClass::all() {
+                csv.push_str(&format!(",{}", account.get_asset_value(class)));
+            }
+            csv.push('\n');
+        }
+    }
+    csv
+}
+
+/// Loads a history saved by `save`
+pub fn load(path: &str) -> Result<Vec<Snapshot>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|why| format!("Could not read history file {}: {}", path, why))?;
+    parse(&text)
+}
+
+/// Parses CSV text of date, kind and name followed by a value per asset class. Lines sharing a
+/// date make up one snapshot. A header line is allowed.
+pub fn parse(text: &str) -> Result<Vec<Snapshot>, String> {
+    let classes = AssetClass::all();
+    let mut history: Vec<Snapshot> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if line.trim().is_empty() || (number == 0 && fields[0].eq_ignore_ascii_case("date")) {
+            continue;
+        }
+        if fields.len() != 3 + classes.len() {
+            return Err(format!("Line {}: expected date,kind,name and {} values but found {}", number + 1,
+                               classes.len(), line));
+        }
+        let date = Date::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+        let mut account = match fields[1] {
+            "allocation" | "target" => Account::new(AccountType::Allocation),
+            kind => Account::new(AccountType::parse(kind).map_err(|why| format!("Line {}: {}", number + 1, why))?),
+        };
+        account.name = fields[2].to_string();
+        for (class, field) in classes.iter().zip(&fields[3..]) {
+            let value: f64 = field.parse()
+                .map_err(|_| format!("Line {}: invalid value {}", number + 1, field))?;
+            account.add_asset(Asset::new(class.clone(), value));
+        }
+        if history.last().map(|s| s.date) != Some(date) {
+            if matches!(history.last(), Some(last) if last.date > date) {
+                return Err(format!("Line {}: snapshots must be in date order", number + 1));
+            }
+            history.push(Snapshot::new(date, &[], &Account::new(AccountType::Allocation),
+                                       &Account::new(AccountType::Allocation)));
+        }
+        let snapshot = history.last_mut().unwrap();
+        match fields[1] {
+            "allocation" => snapshot.allocation = account,
+            "target" => snapshot.target = account,
+            _ => snapshot.accounts.push(account),
+        }
+    }
+    Ok(history)
+}
+
+/// Writes every account's ledger to a CSV file, so the accounts and their lots can be rebuilt
+pub fn save_accounts(accounts: &[Account], path: &str) -> Result<(), String> {
+    fs::write(path, accounts_to_csv(accounts))
+        .map_err(|why| format!("Could not write accounts file {}: {}", path, why))
+}
+
+/// Formats the accounts as CSV of name and account type followed by a transaction of its ledger:
+/// date, kind, asset class, value, and the ticker and shares of transactions in a security. An
+/// account without a ledger has a single line with the transaction left blank.
+pub fn accounts_to_csv(accounts: &[Account]) -> String {
+    let mut csv = "name,type,date,kind,class,value,ticker,shares\n".to_string();
+    for account in accounts {
+        let name = &account.name;
+        let kind = account.classification.key();
+        if account.transactions().is_empty() {
+            csv.push_str(&format!("{},{},,,,,,\n", name, kind));
+        }
+        for transaction in account.transactions() {
+            csv.push_str(&format!("{},{},{},{},{},{},{},{}\n", name, kind, transaction.date, transaction.kind.key(),
+                                  transaction.asset.class, transaction.asset.value,
+                                  transaction.ticker.as_deref().unwrap_or(""), transaction.shares));
+        }
+    }
+    csv
+}
+
+/// Loads accounts saved by `save_accounts`
+pub fn load_accounts(path: &str) -> Result<Vec<Account>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|why| format!("Could not read accounts file {}: {}", path, why))?;
+    parse_accounts(&text)
+}
+
+/// Rebuilds accounts by recording each line's transaction in the account it names, in the order
+/// the lines are given. A header line is allowed.
+pub fn parse_accounts(text: &str) -> Result<Vec<Account>, String> {
+    let mut accounts: Vec<Account> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if line.trim().is_empty() || (number == 0 && fields[0].eq_ignore_ascii_case("name")) {
+            continue;
+        }
+        if fields.len() != 8 {
+            return Err(format!("Line {}: expected name,type,date,kind,class,value,ticker,shares but found {}",
+                               number + 1, line));
+        }
+        let error = |why: String| format!("Line {}: {}", number + 1, why);
+        let account_type = AccountType::parse(fields[1]).map_err(error)?;
+        let index = match accounts.iter().position(|a| a.name == fields[0]) {
+            Some(index) => index,
+            None => {
+                let mut account = Account::new(account_type);
+                account.name = fields[0].to_string();
+                accounts.push(account);
+                accounts.len() - 1
+            }
+        };
+        if fields[2].is_empty() {
+            continue;
+        }
+        let date = Date::parse(fields[2]).map_err(error)?;
+        let kind = TransactionKind::parse(fields[3]).map_err(error)?;
+        let class = AssetClass::parse(fields[4]).map_err(error)?;
+        let value: f64 = fields[5].parse().map_err(|_| error(format!("invalid value {}", fields[5])))?;
+        let shares: f64 = fields[7].parse().map_err(|_| error(format!("invalid shares {}", fields[7])))?;
+        let transaction = match fields[6] {
+            "" => Transaction::new(date, kind, class, value),
+            ticker if shares > 0.0 => Transaction::with_shares(date, kind, class, ticker, shares, value / shares),
+            _ => return Err(error("transactions in a security need a number of shares".to_string())),
+        };
+        accounts[index].record(transaction).map_err(error)?;
+    }
+    Ok(accounts)
+}
+
+/// Lays out how the portfolio's value and each asset class's share, with its drift from target in
+/// parentheses, changed over the history
+pub fn history_report(history: &[Snapshot]) -> String {
+    let classes = AssetClass::all();
+    let mut disp = format!("{:<12}{:>16}", "Date", "Value");
+    for class in &classes {
+        disp.push_str(&format!("{:>20}", class));
+    }
+    disp.push('\n');
+    for snapshot in history {
+        disp.push_str(&format!("{:<12}{:>16}", snapshot.date.to_string(),
+                               snapshot.get_total_value().separated_string_with_fixed_place(2)));
+        for class in &classes {
+            let (share, drift) = snapshot.share_and_drift(class);
+            disp.push_str(&format!("{:>20}", format!("{:.2} ({:+.2})", share, drift)));
+        }
+        disp.push('\n');
+    }
+    disp
+}
+
+#[cfg(test)]
+fn allocation(domestic: f64, bond: f64) -> Account {
+    use crate::account::AccountType;
+    use crate::asset::Asset;
+    let mut allocation = Account::new(AccountType::Allocation);
+    allocation.add_asset(Asset::new(AssetClass::Domestic, domestic));
+    allocation.add_asset(Asset::new(AssetClass::Bond, bond));
+    allocation
+}
+
+#[test]
+fn snapshot_reports_drift_from_target() {
+    let date = Date::new(2019, 1, 2).unwrap();
+    let snapshot = Snapshot::new(date, &[], &allocation(65.0, 35.0), &allocation(60.0, 40.0));
+    assert_eq!((65.0, 5.0), snapshot.share_and_drift(&AssetClass::Domestic));
+    assert_eq!((35.0, -5.0), snapshot.share_and_drift(&AssetClass::Bond));
+}
+
+#[test]
+fn history_keeps_date_order_and_latest_snapshot_per_day() {
+    let mut history = Vec::new();
+    let jan = Date::new(2019, 1, 2).unwrap();
+    let feb = Date::new(2019, 2, 1).unwrap();
+    add_to_history(&mut history, Snapshot::new(feb, &[], &allocation(50.0, 50.0), &allocation(60.0, 40.0)));
+    add_to_history(&mut history, Snapshot::new(jan, &[], &allocation(55.0, 45.0), &allocation(60.0, 40.0)));
+    add_to_history(&mut history, Snapshot::new(feb, &[], &allocation(70.0, 30.0), &allocation(60.0, 40.0)));
+    assert_eq!(2, history.len());
+    assert_eq!(jan, history[0].date);
+    assert_eq!((70.0, 10.0), history[1].share_and_drift(&AssetClass::Domestic));
+}
+
+#[test]
+fn history_survives_a_round_trip_through_csv() {
+    use crate::account::AccountType;
+    let mut roth = Account::new(AccountType::Roth);
+    roth.name = "Work Roth".to_string();
+    roth.add_asset(crate::asset::Asset::new(AssetClass::Bond, 250.5));
+    let jan = Date::new(2019, 1, 2).unwrap();
+    let feb = Date::new(2019, 2, 1).unwrap();
+    let history = vec![Snapshot::new(jan, &[roth.snapshot()], &allocation(0.0, 100.0), &allocation(60.0, 40.0)),
+                       Snapshot::new(feb, &[roth], &allocation(0.0, 100.0), &allocation(60.0, 40.0))];
+    let loaded = parse(&to_csv(&history)).unwrap();
+    assert_eq!(2, loaded.len());
+    let account = loaded[1].account("Work Roth").unwrap();
+    assert!(account.classification == AccountType::Roth);
+    assert_eq!(250.5, account.get_total_value());
+    assert_eq!((0.0, -60.0), loaded[1].share_and_drift(&AssetClass::Domestic));
+    assert!(parse("2019-01-02,checking,a,1,2,3,4\n").err().unwrap().starts_with("Line 1"));
+}
+
+#[test]
+fn accounts_and_their_lots_survive_a_round_trip_through_csv() {
+    use crate::gains;
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.name = "Brokerage".to_string();
+    let bought = Date::new(2020, 3, 2).unwrap();
+    taxable.record(Transaction::with_shares(bought, TransactionKind::Buy, AssetClass::Domestic, "VTI", 10.0, 150.0))
+        .unwrap();
+    taxable.record(Transaction::new(bought, TransactionKind::MarketGain, AssetClass::Domestic, 500.0)).unwrap();
+    let mut roth = Account::new(AccountType::Roth);
+    roth.name = "Roth IRA 1".to_string();
+    let loaded = parse_accounts(&accounts_to_csv(&[taxable, roth])).unwrap();
+    assert_eq!(2, loaded.len());
+    assert!(loaded[1].classification == AccountType::Roth);
+    assert_eq!(2_000.0, loaded[0].get_total_value());
+    assert_eq!(1_500.0, loaded[0].cost_basis().unwrap().get_total_value());
+    let lots = gains::replay(&loaded[0], &Date::new(2026, 1, 2).unwrap()).lots;
+    assert_eq!(bought, lots[0].acquired);
+    assert_eq!(10.0, lots[0].shares);
+    assert!(parse_accounts("a,taxable,2020-01-02,buy,domestic,100,VTI,0\n").is_err());
+}
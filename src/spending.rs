@@ -0,0 +1,270 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fmt;
+use crate::account::Account;
+use crate::asset::AssetClass;
+use crate::assumptions::MarketAssumptions;
+use crate::montecarlo::ReturnGenerator;
+use crate::returns::{self, ReturnHistory};
+use crate::stats;
+
+/// Percentiles shown for income and ending wealth
+const PERCENTILES: [f64; 3] = [0.10, 0.50, 0.90];
+
+/// A rule for how much to withdraw each year. Rates are fractions of the starting portfolio
+/// unless noted otherwise.
+pub enum Strategy {
+    /// The same real amount every year
+    Fixed { rate: f64 },
+    /// Guyton-Klinger guardrails: the real amount is kept, but not raised for inflation after a
+    /// losing year while the rate is above the initial rate, cut when the rate drifts above the
+    /// initial rate by more than the band and raised when it drifts below by more than the band
+    Guardrails { rate: f64, band: f64, adjustment: f64 },
+    /// Variable percentage withdrawal: the portfolio is spread over the years left as an annuity
+    /// earning the expected real return
+    VariablePercentage { real_return: f64 },
+    /// A fixed share of the current portfolio, kept between a floor and a ceiling on the first
+    /// year's real amount
+    FloorAndCeiling { rate: f64, floor: f64, ceiling: f64 },
+}
+
+impl Strategy {
+    /// The usual settings of each strategy for an initial withdrawal rate
+    pub fn standard(rate: f64, real_return: f64) -> Vec<Strategy> {
+        vec![
+            Strategy::Fixed { rate },
+            Strategy::Guardrails { rate, band: 0.2, adjustment: 0.1 },
+            Strategy::VariablePercentage { real_return },
+            Strategy::FloorAndCeiling { rate, floor: 0.85, ceiling: 1.25 },
+        ]
+    }
+
+    /// Returns the real amount to withdraw at the start of a year
+    fn withdrawal(&self, wealth: f64, last: Option<f64>, initial: f64, last_return: f64, inflation: f64,
+                  years_left: usize) -> f64 {
+        match self {
+            Strategy::Fixed { .. } => initial,
+            Strategy::Guardrails { rate, band, adjustment } => {
+                let last = match last {
+                    Some(last) => last,
+                    None => return initial,
+                };
+                let current_rate = if wealth > 0.0 { last / wealth } else { f64::INFINITY };
+                // Skipping the raise for inflation shrinks the real amount
+                let mut amount = if last_return < 0.0 && current_rate > *rate { last / (1.0 + inflation) } else { last };
+                let current_rate = if wealth > 0.0 { amount / wealth } else { f64::INFINITY };
+                if current_rate > rate * (1.0 + band) {
+                    amount *= 1.0 - adjustment;
+                } else if current_rate < rate * (1.0 - band) {
+                    amount *= 1.0 + adjustment;
+                }
+                amount
+            }
+            Strategy::VariablePercentage { real_return } => {
+                let years = years_left.max(1) as f64;
+                if real_return.abs() < 1e-9 {
+                    wealth / years
+                } else {
+                    wealth * real_return / (1.0 - (1.0 + real_return).powf(-years)) / (1.0 + real_return)
+                }
+            }
+            Strategy::FloorAndCeiling { rate, floor, ceiling } => {
+                (rate * wealth).clamp(floor * initial, ceiling * initial)
+            }
+        }
+    }
+
+    /// Returns the first year's real withdrawal from the starting portfolio
+    fn initial(&self, wealth: f64, years: usize) -> f64 {
+        match self {
+            Strategy::Fixed { rate } | Strategy::Guardrails { rate, .. } | Strategy::FloorAndCeiling { rate, .. } =>
+                rate * wealth,
+            Strategy::VariablePercentage { .. } => self.withdrawal(wealth, None, 0.0, 0.0, 0.0, years),
+        }
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Strategy::Fixed { rate } => write!(f, "Fixed {:.1}%", 100.0 * rate),
+            Strategy::Guardrails { rate, .. } => write!(f, "Guardrails {:.1}%", 100.0 * rate),
+            Strategy::VariablePercentage { .. } => write!(f, "Variable percentage"),
+            Strategy::FloorAndCeiling { rate, .. } => write!(f, "Floor and ceiling {:.1}%", 100.0 * rate),
+        }
+    }
+}
+
+/// Returns the share of the portfolio in each asset class: the target's when it adds up to 100,
+/// otherwise what the holdings hold now
+pub fn weights(holdings: &Account, target: &Account) -> Vec<(AssetClass, f64)> {
+    let (base, total) = if target.is_full_allocation() {
+        (target, 100.0)
+    } else {
+        (holdings, holdings.get_total_value())
+    };
+    AssetClass::all().into_iter()
+        .map(|class| {
+            let weight = if total > 0.0 { base.get_asset_value(class.clone()) / total } else { 0.0 };
+            (class, weight)
+        })
+        .collect()
+}
+
+/// Draws yearly nominal returns of a portfolio rebalanced to the weights every year
+pub fn simulated_paths(assumptions: &MarketAssumptions, weights: &[(AssetClass, f64)], years: usize,
+                       simulations: usize, seed: u64) -> Result<Vec<Vec<f64>>, String> {
+    let mut generator = ReturnGenerator::new(assumptions, seed)?;
+    let mut paths = Vec::new();
+    for _ in 0..simulations {
+        let mut path = Vec::new();
+        for _ in 0..years {
+            let mut growth: Vec<(AssetClass, f64)> = weights.iter().map(|(c, _)| (c.clone(), 1.0)).collect();
+            for _ in 0..12 {
+                let returns = generator.next_month();
+                for (class, value) in growth.iter_mut() {
+                    *value *= 1.0 + returns::return_of(&returns, class);
+                }
+            }
+            path.push(weights.iter().zip(&growth).map(|((_, w), (_, g))| w * (g - 1.0)).sum());
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Cuts the history into every run of whole years it holds, starting at each period in turn, and
+/// returns the yearly nominal returns of a portfolio rebalanced to the weights every period
+pub fn historical_paths(history: &ReturnHistory, weights: &[(AssetClass, f64)], years: usize) -> Result<Vec<Vec<f64>>, String> {
+    let per_year = history.periods_per_year().round().max(1.0) as usize;
+    let returns: Vec<f64> = history.periods.iter()
+        .map(|p| weights.iter().map(|(c, w)| w * returns::return_of(&p.returns, c)).sum())
+        .collect();
+    let length = years * per_year;
+    if years == 0 || returns.len() < length {
+        return Err(format!("The history holds {} periods, fewer than the {} needed for {} years",
+                           returns.len(), length, years));
+    }
+    Ok((0..=returns.len() - length)
+        .map(|start| {
+            returns[start..start + length].chunks(per_year)
+                .map(|year| year.iter().fold(1.0, |growth, r| growth * (1.0 + r)) - 1.0)
+                .collect()
+        })
+        .collect())
+}
+
+/// How a strategy fared across every path, in today's dollars
+pub struct Evaluation {
+    pub name: String,
+    /// Sorted income of every year of every path
+    pub incomes: Vec<f64>,
+    /// Sorted lowest yearly income of each path
+    pub lowest: Vec<f64>,
+    /// Sorted ending wealth of each path
+    pub endings: Vec<f64>,
+    /// Fraction of paths that ran out of money before the end
+    pub failure_rate: f64,
+}
+
+fn sort(values: &mut [f64]) {
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+}
+
+/// Runs the strategy down every path of yearly nominal returns. Money is withdrawn at the start of
+/// each year and the rest earns that year's return less inflation.
+pub fn evaluate(strategy: &Strategy, wealth: f64, paths: &[Vec<f64>], inflation: f64) -> Evaluation {
+    let mut incomes = Vec::new();
+    let mut lowest = Vec::new();
+    let mut endings = Vec::new();
+    let mut failures = 0;
+    for path in paths {
+        let mut value = wealth;
+        let initial = strategy.initial(wealth, path.len());
+        let mut last = None;
+        let mut last_return = 0.0;
+        let mut failed = false;
+        let mut low = f64::INFINITY;
+        for (year, nominal) in path.iter().enumerate() {
+            let wanted = strategy.withdrawal(value, last, initial, last_return, inflation, path.len() - year).max(0.0);
+            let taken = wanted.min(value);
+            if taken < wanted - 0.005 {
+                failed = true;
+            }
+            value -= taken;
+            incomes.push(taken);
+            low = low.min(taken);
+            last = Some(wanted);
+            last_return = *nominal;
+            value *= (1.0 + nominal) / (1.0 + inflation);
+        }
+        if failed {
+            failures += 1;
+        }
+        lowest.push(if low.is_finite() { low } else { 0.0 });
+        endings.push(value);
+    }
+    sort(&mut incomes);
+    sort(&mut lowest);
+    sort(&mut endings);
+    let failure_rate = if paths.is_empty() { 0.0 } else { failures as f64 / paths.len() as f64 };
+    Evaluation { name: strategy.to_string(), incomes, lowest, endings, failure_rate }
+}
+
+/// Lays out the spread of yearly income, the lowest income of a typical path, the failure rate
+/// and the spread of ending wealth of each strategy
+pub fn report(evaluations: &[Evaluation], paths: usize) -> String {
+    let mut disp = format!("Withdrawal strategies over {} return paths, in today's dollars\n", paths);
+    disp.push_str(&format!("{:<26}", "Strategy"));
+    for fraction in &PERCENTILES {
+        disp.push_str(&format!("{:>14}", format!("Income {:.0}th", 100.0 * fraction)));
+    }
+    disp.push_str(&format!("{:>14}{:>9}", "Median low", "Fails"));
+    for fraction in &PERCENTILES {
+        disp.push_str(&format!("{:>16}", format!("Ending {:.0}th", 100.0 * fraction)));
+    }
+    disp.push('\n');
+    for evaluation in evaluations {
+        disp.push_str(&format!("{:<26}", evaluation.name));
+        for fraction in &PERCENTILES {
+            disp.push_str(&format!("{:>14}", stats::percentile(&evaluation.incomes, *fraction).separated_string_with_fixed_place(0)));
+        }
+        disp.push_str(&format!("{:>14}{:>8.1}%", stats::percentile(&evaluation.lowest, 0.5).separated_string_with_fixed_place(0),
+                               100.0 * evaluation.failure_rate));
+        for fraction in &PERCENTILES {
+            disp.push_str(&format!("{:>16}", stats::percentile(&evaluation.endings, *fraction).separated_string_with_fixed_place(0)));
+        }
+        disp.push('\n');
+    }
+    disp
+}
+
+#[test]
+fn fixed_withdrawals_fail_where_variable_ones_adapt() {
+    // Flat returns, then a crash
+    let paths = vec![vec![0.0, 0.0, -0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]];
+    let fixed = evaluate(&Strategy::Fixed { rate: 0.15 }, 100.0, &paths, 0.0);
+    assert_eq!(1.0, fixed.failure_rate);
+    assert_eq!(15.0, fixed.incomes[9]);
+    let variable = evaluate(&Strategy::VariablePercentage { real_return: 0.0 }, 100.0, &paths, 0.0);
+    assert_eq!(0.0, variable.failure_rate);
+    assert!(variable.endings[0].abs() < 1e-9);
+    assert!((variable.incomes[9] - 10.0).abs() < 1e-9);
+    let floor = evaluate(&Strategy::FloorAndCeiling { rate: 0.1, floor: 0.9, ceiling: 1.1 }, 100.0, &paths, 0.0);
+    // The floor holds spending up until the money runs out
+    assert_eq!(1.0, floor.failure_rate);
+    assert!(floor.incomes.iter().filter(|i| **i > 0.0).all(|i| *i >= 9.0));
+}
+
+#[test]
+fn guardrails_cut_after_losses_and_raise_after_gains() {
+    let strategy = Strategy::Guardrails { rate: 0.05, band: 0.2, adjustment: 0.1 };
+    let cut = strategy.withdrawal(50.0, Some(5.0), 5.0, -0.5, 0.03, 20);
+    assert!((cut - 5.0 / 1.03 * 0.9).abs() < 1e-9);
+    let raised = strategy.withdrawal(200.0, Some(5.0), 5.0, 1.0, 0.03, 20);
+    assert!((raised - 5.5).abs() < 1e-9);
+    let history = ReturnHistory::parse("date,domestic\n2000-01-31,0.1\n2000-07-31,0.1\n2001-01-31,0.1\n2001-07-31,0.1\n").unwrap();
+    let paths = historical_paths(&history, &[(AssetClass::Domestic, 1.0)], 1).unwrap();
+    assert_eq!(3, paths.len());
+    assert!((paths[0][0] - 0.21).abs() < 1e-9);
+}
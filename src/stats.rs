@@ -0,0 +1,89 @@
+/// Returns the average of the values, or zero when there are none
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Returns the sample standard deviation of the values
+pub fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let avg = mean(values);
+    let sum_of_squares: f64 = values.iter().map(|v| (v - avg).powi(2)).sum();
+    (sum_of_squares / (values.len() - 1) as f64).sqrt()
+}
+
+/// Returns the largest fall from a peak, as a positive fraction, when the returns are compounded
+///
+/// # Examples
+///
+/// ```
+/// // Growing 10%, then losing half, then recovering 20% leaves a 50% drawdown
+/// assert_eq!(0.5, max_drawdown(&[0.1, -0.5, 0.2]));
+/// ```
+pub fn max_drawdown(returns: &[f64]) -> f64 {
+    let mut index = 1.0;
+    let mut peak = 1.0;
+    let mut drawdown: f64 = 0.0;
+    for r in returns {
+        index *= 1.0 + r;
+        peak = f64::max(peak, index);
+        drawdown = drawdown.max(1.0 - index / peak);
+    }
+    drawdown
+}
+
+/// Returns the value below which the given fraction of the sorted values fall
+pub fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = (fraction * (sorted.len() - 1) as f64).round() as usize;
+    sorted[index.min(sorted.len() - 1)]
+}
+
+/// Factors a symmetric positive definite matrix into a lower triangular matrix L where L * L' is
+/// the original matrix (Cholesky decomposition)
+pub fn cholesky(matrix: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, String> {
+    let n = matrix.len();
+    let mut lower = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum: f64 = (0..j).map(|k| lower[i][k] * lower[j][k]).sum();
+            if i == j {
+                let diagonal = matrix[i][i] - sum;
+                if diagonal <= 0.0 {
+                    return Err("Matrix is not positive definite".to_string());
+                }
+                lower[i][j] = diagonal.sqrt();
+            } else {
+                lower[i][j] = (matrix[i][j] - sum) / lower[j][j];
+            }
+        }
+    }
+    Ok(lower)
+}
+
+#[test]
+fn std_dev_of_sample() {
+    assert_eq!(0.0, std_dev(&[1.0]));
+    assert!((std_dev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]) - 2.138_089_935).abs() < 1e-6);
+}
+
+#[test]
+fn drawdown_measured_from_peak() {
+    assert!((max_drawdown(&[0.1, -0.5, 0.2]) - 0.5).abs() < 1e-9);
+    assert_eq!(0.0, max_drawdown(&[0.01, 0.02]));
+}
+
+#[test]
+fn cholesky_factors_correlation_matrix() {
+    let lower = cholesky(&[vec![1.0, 0.6], vec![0.6, 1.0]]).unwrap();
+    assert_eq!(1.0, lower[0][0]);
+    assert!((lower[1][0] - 0.6).abs() < 1e-12);
+    assert!((lower[1][1] - 0.8).abs() < 1e-12);
+    assert!(cholesky(&[vec![1.0, 2.0], vec![2.0, 1.0]]).is_err());
+}
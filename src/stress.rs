@@ -0,0 +1,161 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::returns::return_of;
+
+/// A named shock to each asset class, as the fraction of value gained or lost. Asset classes that
+/// are left out are unchanged.
+pub struct Scenario {
+    pub name: String,
+    pub shocks: Vec<(AssetClass, f64)>,
+}
+
+impl Scenario {
+    /// Returns the shock to an asset class
+    pub fn shock(&self, class: &AssetClass) -> f64 {
+        return_of(&self.shocks, class)
+    }
+
+    /// Returns a copy of the account after the shock
+    pub fn apply(&self, account: &Account) -> Account {
+        let mut shocked = Account::new(account.classification.clone());
+        for asset in &account.assets {
+            shocked.add_asset(Asset::new(asset.class.clone(), asset.value * (1.0 + self.shock(&asset.class))));
+        }
+        shocked
+    }
+}
+
+/// Scenarios to use when no file is given: calendar year 2008, calendar year 2022 and a
+/// hypothetical bout of stagflation
+pub fn standard() -> Vec<Scenario> {
+    let scenario = |name: &str, shocks: [f64; 4]| Scenario {
+        name: name.to_string(),
+        shocks: AssetClass::all().into_iter().zip(shocks.iter().cloned()).collect(),
+    };
+    vec![
+        scenario("2008 financial crisis", [-0.37, -0.43, 0.05, -0.38]),
+        scenario("2022 rate shock", [-0.19, -0.16, -0.13, -0.25]),
+        scenario("Stagflation", [-0.20, -0.18, -0.10, -0.05]),
+    ]
+}
+
+/// Loads scenarios from a CSV file
+pub fn load(path: &str) -> Result<Vec<Scenario>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|why| format!("Could not read scenario file {}: {}", path, why))?;
+    parse(&text)
+}
+
+/// Parses CSV text whose header names a scenario column followed by asset classes, and whose rows
+/// hold each scenario's name and the shock to each asset class as a fraction
+///
+/// # Examples
+///
+/// ```
+/// let scenarios = parse("scenario,domestic,bond\nCrash,-0.5,0.05\n").unwrap();
+/// assert_eq!(-0.5, scenarios[0].shock(&AssetClass::Domestic));
+/// ```
+pub fn parse(text: &str) -> Result<Vec<Scenario>, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header = match lines.next() {
+        Some((_, header)) => header,
+        None => return Err("Scenario file is empty".to_string()),
+    };
+    let classes = header.split(',')
+        .skip(1)
+        .map(AssetClass::parse)
+        .collect::<Result<Vec<AssetClass>, String>>()?;
+    let mut scenarios = Vec::new();
+    for (number, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+        if fields.len() != classes.len() + 1 {
+            return Err(format!("Line {}: expected {} columns but found {}", number + 1,
+                               classes.len() + 1, fields.len()));
+        }
+        let shocks = fields[1..].iter()
+            .map(|f| f.parse::<f64>().map_err(|_| format!("Line {}: invalid number {}", number + 1, f)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        if shocks.iter().any(|s| *s < -1.0) {
+            return Err(format!("Line {}: an asset class cannot lose more than all of its value", number + 1));
+        }
+        scenarios.push(Scenario {
+            name: fields[0].to_string(),
+            shocks: classes.iter().cloned().zip(shocks).collect(),
+        });
+    }
+    if scenarios.is_empty() {
+        return Err("Scenario file has no scenarios".to_string());
+    }
+    Ok(scenarios)
+}
+
+/// Returns the percentage of the holdings in each asset class
+fn shares(holdings: &Account) -> Account {
+    let total = holdings.get_total_value();
+    let mut shares = Account::new(AccountType::Allocation);
+    for class in AssetClass::all() {
+        let value = holdings.get_asset_value(class.clone());
+        shares.add_asset(Asset::new(class, if total > 0.0 { 100.0 * value / total } else { 0.0 }));
+    }
+    shares
+}
+
+/// Shows the dollar loss of each account and of the portfolio under the scenario, and how far the
+/// shock would move each asset class's share of the portfolio and from the target
+pub fn report(scenario: &Scenario, accounts: &[Account], target: &Account) -> String {
+    let mut disp = format!("Scenario: {}\n", scenario.name);
+    disp.push_str(&format!("{:<20}{:>16}{:>16}{:>16}{:>10}\n", "Account", "Before", "After", "Change", "Change"));
+    let mut before = Account::new(AccountType::Taxable);
+    let mut after = Account::new(AccountType::Taxable);
+    for account in accounts {
+        let shocked = scenario.apply(account);
+        let (start, end) = (account.get_total_value(), shocked.get_total_value());
+        disp.push_str(&format!("{:<20}{:>16}{:>16}{:>16}{:>9.2}%\n",
+                               account.name,
+                               start.separated_string_with_fixed_place(2),
+                               end.separated_string_with_fixed_place(2),
+                               (end - start).separated_string_with_fixed_place(2),
+                               if start > 0.0 { 100.0 * (end - start) / start } else { 0.0 }));
+        for asset in &account.assets {
+            before.add_asset(Asset::new(asset.class.clone(), asset.value));
+        }
+        for asset in &shocked.assets {
+            after.add_asset(Asset::new(asset.class.clone(), asset.value));
+        }
+    }
+    let (start, end) = (before.get_total_value(), after.get_total_value());
+    disp.push_str(&format!("{:<20}{:>16}{:>16}{:>16}{:>9.2}%\n\n", "Total",
+                           start.separated_string_with_fixed_place(2),
+                           end.separated_string_with_fixed_place(2),
+                           (end - start).separated_string_with_fixed_place(2),
+                           if start > 0.0 { 100.0 * (end - start) / start } else { 0.0 }));
+    disp.push_str(&format!("{:<20}{:>10}{:>10}{:>10}{:>10}{:>16}\n", "Asset class", "Shock", "Before",
+                           "After", "Target", "Drift after"));
+    let (before, after) = (shares(&before), shares(&after));
+    for class in AssetClass::all() {
+        let share = after.get_asset_value(class.clone());
+        disp.push_str(&format!("{:<20}{:>9.2}%{:>9.2}%{:>9.2}%{:>9.2}%{:>16}\n", class.to_string(),
+                               100.0 * scenario.shock(&class), before.get_asset_value(class.clone()), share,
+                               target.get_asset_value(class.clone()),
+                               format!("{:+.2}", share - target.get_asset_value(class.clone()))));
+    }
+    disp
+}
+
+#[test]
+fn scenario_shocks_each_asset_class() {
+    let scenarios = parse("scenario,domestic,bond\nCrash,-0.5,0.1\n").unwrap();
+    let mut account = Account::new(AccountType::Roth);
+    account.add_asset(Asset::new(AssetClass::Domestic, 6000.0));
+    account.add_asset(Asset::new(AssetClass::Bond, 4000.0));
+    account.add_asset(Asset::new(AssetClass::RealEstate, 1000.0));
+    let shocked = scenarios[0].apply(&account);
+    assert_eq!(3000.0, shocked.get_asset_value(AssetClass::Domestic));
+    assert!((shocked.get_asset_value(AssetClass::Bond) - 4400.0).abs() < 1e-9);
+    assert_eq!(1000.0, shocked.get_asset_value(AssetClass::RealEstate));
+    assert!(parse("scenario,domestic\nWipeout,-1.5\n").is_err());
+    assert_eq!(3, standard().len());
+}
@@ -0,0 +1,533 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fmt;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::AssetClass;
+use crate::date::Date;
+use crate::gains::{self, Lot};
+use crate::jurisdiction::Jurisdiction;
+use crate::prices::PriceTable;
+use crate::rebalance;
+
+/// Progressive tax brackets as the income each rate starts at, lowest first
+#[derive(Clone)]
+pub struct Brackets {
+    pub rates: Vec<(f64, f64)>,
+}
+
+impl Brackets {
+    /// Creates brackets from the income each rate starts at. The first bracket must start at zero
+    /// and each one must start above the last.
+    pub fn new(rates: Vec<(f64, f64)>) -> Result<Brackets, String> {
+        if rates.first().map(|(start, _)| *start != 0.0).unwrap_or(true) {
+            return Err("The first bracket must start at 0".to_string());
+        }
+        if rates.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+            return Err("Each bracket must start above the one before".to_string());
+        }
+        Ok(Brackets { rates })
+    }
+
+    /// A single bracket taxing all income at one rate
+    pub fn flat(rate: f64) -> Brackets {
+        Brackets { rates: vec![(0.0, rate)] }
+    }
+
+    /// Returns the tax owed on the income
+    pub fn tax(&self, income: f64) -> f64 {
+        let mut tax = 0.0;
+        for (i, (start, rate)) in self.rates.iter().enumerate() {
+            let end = self.rates.get(i + 1).map(|(end, _)| *end).unwrap_or(f64::INFINITY);
+            if income > *start {
+                tax += rate * (income.min(end) - start);
+            }
+        }
+        tax
+    }
+
+    /// Returns the rate on the last dollar of the income, the rate of the highest bracket it reaches
+    pub fn rate_on_last_dollar(&self, income: f64) -> f64 {
+        self.rates.iter()
+            .rev()
+            .find(|(start, _)| income > *start)
+            .map(|(_, rate)| *rate)
+            .unwrap_or(0.0)
+    }
+
+    /// Returns the income at which the rate would first go above the given rate
+    pub fn top_of(&self, rate: f64) -> f64 {
+        self.rates.iter()
+            .find(|(_, r)| *r > rate + 1e-9)
+            .map(|(start, _)| *start)
+            .unwrap_or(f64::INFINITY)
+    }
+
+    fn scaled(&self, factor: f64) -> Brackets {
+        Brackets { rates: self.rates.iter().map(|(start, rate)| (start * factor, *rate)).collect() }
+    }
+}
+
+/// A year's income, split by how it is taxed
+#[derive(Clone, Copy, Default)]
+pub struct Income {
+    /// Wages, interest, short-term gains, withdrawals from tax-deferred accounts and conversions
+    pub ordinary: f64,
+    /// Qualified dividends and long-term capital gains
+    pub qualified: f64,
+    /// The part of ordinary income that comes from investments, such as interest and short-term
+    /// gains
+    pub investment: f64,
+}
+
+impl Income {
+    pub fn ordinary(amount: f64) -> Income {
+        Income { ordinary: amount, ..Income::default() }
+    }
+
+    /// Income from net capital gains. A net loss of one term offsets gains of the other, and a
+    /// net loss overall adds nothing.
+    pub fn capital_gains(short_term: f64, long_term: f64) -> Income {
+        let ordinary = (short_term + long_term.min(0.0)).max(0.0);
+        Income { ordinary, qualified: (long_term + short_term.min(0.0)).max(0.0), investment: ordinary }
+    }
+
+    pub fn plus(&self, other: &Income) -> Income {
+        Income {
+            ordinary: self.ordinary + other.ordinary,
+            qualified: self.qualified + other.qualified,
+            investment: self.investment + other.investment,
+        }
+    }
+}
+
+/// Tax owed, by the part of the tax code it comes from
+#[derive(Clone, Copy, Default)]
+pub struct TaxBreakdown {
+    pub ordinary: f64,
+    pub gains: f64,
+    /// Net investment income tax
+    pub niit: f64,
+    pub state: f64,
+}
+
+impl TaxBreakdown {
+    pub fn total(&self) -> f64 {
+        self.ordinary + self.gains + self.niit + self.state
+    }
+
+    fn minus(&self, other: &TaxBreakdown) -> TaxBreakdown {
+        TaxBreakdown {
+            ordinary: self.ordinary - other.ordinary,
+            gains: self.gains - other.gains,
+            niit: self.niit - other.niit,
+            state: self.state - other.state,
+        }
+    }
+}
+
+impl fmt::Display for TaxBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut disp = String::new();
+        for (name, amount) in &[("Ordinary income", self.ordinary), ("Dividends and gains", self.gains),
+                                ("Net investment income", self.niit), ("State", self.state),
+                                ("Total", self.total())] {
+            disp.push_str(&format!("{:<24}{:>16}\n", name, amount.separated_string_with_fixed_place(2)));
+        }
+        write!(f, "{}", disp)
+    }
+}
+
+/// One year's tax rules. Ordinary income above the deduction is taxed by the ordinary brackets.
+/// Qualified income is stacked on top of it and taxed by the gains brackets, so it fills them
+/// from where ordinary income left off. The net investment income tax applies to the smaller of
+/// investment income and income above the threshold. The state taxes all income by its own
+/// brackets.
+#[derive(Clone)]
+pub struct TaxTable {
+    pub year: i32,
+    pub ordinary: Brackets,
+    pub deduction: f64,
+    pub gains: Brackets,
+    pub niit_rate: f64,
+    pub niit_threshold: f64,
+    pub state: Brackets,
+}
+
+impl TaxTable {
+    /// A table that taxes gains as ordinary income, with no net investment income tax and no state
+    pub fn new(year: i32, ordinary: Brackets, deduction: f64) -> TaxTable {
+        TaxTable {
+            year,
+            gains: ordinary.clone(),
+            ordinary,
+            deduction,
+            niit_rate: 0.0,
+            niit_threshold: 0.0,
+            state: Brackets::flat(0.0),
+        }
+    }
+
+    /// Returns the tax owed on a year's income
+    pub fn tax(&self, income: &Income) -> TaxBreakdown {
+        let total = income.ordinary + income.qualified;
+        let ordinary_taxable = (income.ordinary - self.deduction).max(0.0);
+        let taxable = (total - self.deduction).max(0.0);
+        let investment = income.qualified + income.investment;
+        TaxBreakdown {
+            ordinary: self.ordinary.tax(ordinary_taxable),
+            gains: self.gains.tax(taxable) - self.gains.tax(ordinary_taxable),
+            niit: self.niit_rate * investment.min((total - self.niit_threshold).max(0.0)),
+            state: self.state.tax(total),
+        }
+    }
+
+    /// Returns the extra tax owed when the change is added to the base income
+    pub fn incremental(&self, base: &Income, change: &Income) -> TaxBreakdown {
+        self.tax(&base.plus(change)).minus(&self.tax(base))
+    }
+
+    /// Returns the ordinary income, before the deduction, at which the ordinary rate would first
+    /// go above the given rate
+    pub fn top_of_bracket(&self, rate: f64) -> f64 {
+        self.ordinary.top_of(rate) + self.deduction
+    }
+
+    /// Returns the rate on the last dollar of ordinary income
+    pub fn rate_on_last_dollar(&self, ordinary: f64) -> f64 {
+        self.ordinary.rate_on_last_dollar(ordinary - self.deduction)
+    }
+
+    /// Returns the table with the brackets and deduction grown by the factor, as they are when
+    /// indexed to inflation
+    pub fn indexed(&self, factor: f64) -> TaxTable {
+        TaxTable {
+            year: self.year,
+            ordinary: self.ordinary.scaled(factor),
+            deduction: self.deduction * factor,
+            gains: self.gains.scaled(factor),
+            niit_rate: self.niit_rate,
+            // Set in law without indexing
+            niit_threshold: self.niit_threshold,
+            state: self.state.scaled(factor),
+        }
+    }
+}
+
+/// The year, kind and rates of the bracket lines read so far
+type BracketLines = (i32, String, Vec<(f64, f64)>);
+
+/// Tax tables for a run of years
+pub struct TaxTables {
+    pub tables: Vec<TaxTable>,
+}
+
+impl TaxTables {
+    /// US federal tables for a single filer in 2025 and 2026, without any state tax
+    pub fn standard() -> TaxTables {
+        let table = |year, ordinary: Vec<(f64, f64)>, deduction, gains: Vec<(f64, f64)>| TaxTable {
+            year,
+            ordinary: Brackets { rates: ordinary },
+            deduction,
+            gains: Brackets { rates: gains },
+            niit_rate: 0.038,
+            niit_threshold: 200_000.0,
+            state: Brackets::flat(0.0),
+        };
+        TaxTables {
+            tables: vec![
+                table(2025, vec![(0.0, 0.10), (11_925.0, 0.12), (48_475.0, 0.22), (103_350.0, 0.24),
+                                 (197_300.0, 0.32), (250_525.0, 0.35), (626_350.0, 0.37)],
+                      15_750.0, vec![(0.0, 0.0), (48_350.0, 0.15), (533_400.0, 0.20)]),
+                table(2026, vec![(0.0, 0.10), (12_400.0, 0.12), (50_400.0, 0.22), (105_700.0, 0.24),
+                                 (201_775.0, 0.32), (256_225.0, 0.35), (640_600.0, 0.37)],
+                      16_100.0, vec![(0.0, 0.0), (49_450.0, 0.15), (545_500.0, 0.20)]),
+            ],
+        }
+    }
+
+    /// Loads tax tables from a file
+    pub fn load(path: &str) -> Result<TaxTables, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read tax table file {}: {}", path, why))?;
+        TaxTables::parse(&text)
+    }
+
+    /// Parses lines of year and kind followed by the kind's fields, with rates in percent:
+    /// `ordinary`, `gains` and `state` lines give the income a bracket starts at and its rate,
+    /// `deduction` lines the amount and `niit` lines the threshold and rate. Every year needs
+    /// ordinary brackets. Without gains brackets gains are taxed as ordinary income, and without
+    /// state brackets there is no state tax. A header line starting with "year" is skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let tables = TaxTables::parse("2026,ordinary,0,10\n2026,ordinary,12400,12\n2026,deduction,16100\n2026,state,0,4.5\n").unwrap();
+    /// ```
+    pub fn parse(text: &str) -> Result<TaxTables, String> {
+        let mut tables: Vec<TaxTable> = Vec::new();
+        // Brackets are gathered for every year before being checked
+        let mut brackets: Vec<BracketLines> = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if number == 0 && fields[0].eq_ignore_ascii_case("year") {
+                continue;
+            }
+            let wanted = match fields.get(1).map(|kind| kind.to_lowercase()) {
+                Some(ref kind) if kind == "deduction" => 3,
+                Some(_) => 4,
+                None => 0,
+            };
+            if fields.len() != wanted {
+                return Err(format!("Line {}: expected year,kind followed by its amounts but found {}", number + 1, line));
+            }
+            let year = fields[0].parse::<i32>()
+                .map_err(|_| format!("Line {}: invalid year {}", number + 1, fields[0]))?;
+            let amount = fields[2].parse::<f64>()
+                .map_err(|_| format!("Line {}: invalid amount {}", number + 1, fields[2]))?;
+            let rate = match fields.get(3).map(|rate| rate.parse::<f64>()) {
+                None => 0.0,
+                Some(Ok(rate)) if (0.0..=100.0).contains(&rate) => rate / 100.0,
+                Some(_) => return Err(format!("Line {}: rate must be a percentage from 0 to 100", number + 1)),
+            };
+            let kind = fields[1].to_lowercase();
+            let index = match tables.iter().position(|t| t.year == year) {
+                Some(index) => index,
+                None => {
+                    tables.push(TaxTable::new(year, Brackets::flat(0.0), 0.0));
+                    tables.len() - 1
+                }
+            };
+            match kind.as_str() {
+                "deduction" => tables[index].deduction = amount,
+                "niit" => {
+                    tables[index].niit_threshold = amount;
+                    tables[index].niit_rate = rate;
+                }
+                "ordinary" | "gains" | "state" => match brackets.iter_mut().find(|(y, k, _)| *y == year && *k == kind) {
+                    Some((_, _, rates)) => rates.push((amount, rate)),
+                    None => brackets.push((year, kind, vec![(amount, rate)])),
+                },
+                _ => return Err(format!("Line {}: unknown kind {}, expected ordinary, gains, state, deduction or niit",
+                                        number + 1, fields[1])),
+            }
+        }
+        for table in tables.iter_mut() {
+            let year = table.year;
+            let mut of_kind = |kind: &str| -> Result<Option<Brackets>, String> {
+                match brackets.iter().position(|(y, k, _)| *y == year && k == kind) {
+                    Some(index) => Brackets::new(brackets.remove(index).2)
+                        .map(Some)
+                        .map_err(|why| format!("{} {} brackets: {}", year, kind, why)),
+                    None => Ok(None),
+                }
+            };
+            table.ordinary = of_kind("ordinary")?.ok_or_else(|| format!("{} has no ordinary brackets", table.year))?;
+            table.gains = of_kind("gains")?.unwrap_or_else(|| table.ordinary.clone());
+            if let Some(state) = of_kind("state")? {
+                table.state = state;
+            }
+        }
+        if tables.is_empty() {
+            return Err("Tax table file has no years".to_string());
+        }
+        tables.sort_by_key(|t| t.year);
+        Ok(TaxTables { tables })
+    }
+
+    /// Returns the table for the latest year up to the given one, or the earliest table when the
+    /// year comes before all of them
+    pub fn for_year(&self, year: i32) -> &TaxTable {
+        self.tables.iter()
+            .rev()
+            .find(|t| t.year <= year)
+            .unwrap_or(&self.tables[0])
+    }
+
+    /// Replaces the state brackets of every year
+    pub fn set_state(&mut self, state: Brackets) {
+        for table in self.tables.iter_mut() {
+            table.state = state.clone();
+        }
+    }
+}
+
+/// Returns the net gains realized by rebalancing the combined holdings to the target, with warnings
+/// for holdings whose gain could not be found. Each sale comes first from accounts that are not
+/// taxed on growth, then from taxable accounts in turn. Within a taxable account the oldest lots
+/// of the asset class are sold first, valued at their security's last known price, and each
+/// lot's gain is short-term or long-term by how long it has been held. Holdings outside any lot
+/// are counted at the account's cost basis as long-term, and holdings of accounts without a
+/// ledger are counted as having no gain.
+pub fn realized_by_rebalancing(accounts: &[Account], target: &Account, prices: &PriceTable, date: &Date,
+                               jurisdiction: &dyn Jurisdiction) -> (Income, Vec<String>) {
+    let mut holdings = Account::new(AccountType::Taxable);
+    for account in accounts {
+        rebalance::apply(&mut holdings, account);
+    }
+    let trades = rebalance::trades_to_target(&holdings, target);
+    let mut short_term = 0.0;
+    let mut long_term = 0.0;
+    let mut warnings: Vec<String> = Vec::new();
+    let mut warn = |warning: String| if !warnings.contains(&warning) { warnings.push(warning) };
+    let ledgers = gains::replay_all(&accounts.iter().collect::<Vec<&Account>>(), date, jurisdiction);
+    let lots: Vec<Vec<Lot>> = ledgers.into_iter().map(|ledger| ledger.lots).collect();
+    for class in AssetClass::all() {
+        let mut to_sell = -trades.get_asset_value(class.clone());
+        if to_sell < rebalance::MINIMUM_TRADE {
+            continue;
+        }
+        let sheltered: f64 = accounts.iter()
+            .filter(|a| !jurisdiction.tax_treatment(&a.classification).taxable_growth)
+            .map(|a| a.get_asset_value(class.clone()))
+            .sum();
+        to_sell -= sheltered.min(to_sell);
+        for (account, lots) in accounts.iter().zip(&lots) {
+            if to_sell < rebalance::MINIMUM_TRADE {
+                break;
+            }
+            if !jurisdiction.tax_treatment(&account.classification).taxable_growth {
+                continue;
+            }
+            let value = account.get_asset_value(class.clone());
+            let sold = value.min(to_sell);
+            to_sell -= sold;
+            let mut unsold = sold;
+            for lot in lots.iter().filter(|l| l.class == class) {
+                if unsold <= 0.0 {
+                    break;
+                }
+                let lot_value = match prices.price_on(&lot.ticker, date) {
+                    Some(quote) => lot.shares * quote.close,
+                    None => {
+                        warn(format!("No price for {}, its lots are counted at cost", lot.ticker));
+                        lot.cost
+                    }
+                };
+                if lot_value <= 0.0 {
+                    continue;
+                }
+                let part = lot_value.min(unsold);
+                let gain = part * (lot_value - lot.cost) / lot_value;
+                if lot.is_long_term(date) {
+                    long_term += gain;
+                } else {
+                    short_term += gain;
+                }
+                unsold -= part;
+            }
+            if unsold < rebalance::MINIMUM_TRADE {
+                continue;
+            }
+            match account.cost_basis() {
+                Some(basis) if value > 0.0 => {
+                    let basis = basis.get_asset_value(class.clone());
+                    long_term += unsold * ((value - basis) / value).max(0.0);
+                }
+                _ => warn(format!("No cost basis for {} {}, its sales are counted without gain",
+                                  jurisdiction.account_name(&account.classification), class)),
+            }
+        }
+    }
+    (Income::capital_gains(short_term, long_term), warnings)
+}
+
+#[test]
+fn brackets_tax_each_slice_at_its_rate() {
+    let brackets = Brackets::new(vec![(0.0, 0.1), (10_000.0, 0.2), (50_000.0, 0.3)]).unwrap();
+    assert_eq!(1_000.0 + 8_000.0 + 3_000.0, brackets.tax(60_000.0));
+    assert_eq!(0.1, brackets.rate_on_last_dollar(10_000.0));
+    assert_eq!(0.2, brackets.rate_on_last_dollar(10_001.0));
+    assert_eq!(50_000.0, brackets.top_of(0.2));
+    assert!(Brackets::new(vec![(100.0, 0.1)]).is_err());
+    assert!(Brackets::new(vec![(0.0, 0.1), (0.0, 0.2)]).is_err());
+}
+
+#[test]
+fn losses_of_one_term_offset_gains_of_the_other() {
+    let income = Income::capital_gains(5_000.0, -2_000.0);
+    assert_eq!(3_000.0, income.ordinary);
+    assert_eq!(3_000.0, income.investment);
+    assert_eq!(0.0, income.qualified);
+    let income = Income::capital_gains(-1_000.0, 4_000.0);
+    assert_eq!(0.0, income.ordinary);
+    assert_eq!(3_000.0, income.qualified);
+    assert_eq!(0.0, Income::capital_gains(-1_000.0, -1_000.0).ordinary);
+}
+
+#[test]
+fn gains_stack_on_ordinary_income() {
+    let tables = TaxTables::parse("year,kind,amount,rate\n2026,ordinary,0,10\n2026,ordinary,20000,20\n\
+                                   2026,gains,0,0\n2026,gains,30000,15\n2026,deduction,10000\n\
+                                   2026,niit,50000,3.8\n2026,state,0,5\n").unwrap();
+    let table = tables.for_year(2030);
+    let base = Income::ordinary(30_000.0);
+    // 10,000 of the gain fills the 0% gains bracket above 20,000 of taxable ordinary income
+    let change = Income { qualified: 30_000.0, ..Income::default() };
+    let tax = table.incremental(&base, &change);
+    assert_eq!(0.0, tax.ordinary);
+    assert!((tax.gains - 0.15 * 20_000.0).abs() < 1e-9);
+    assert!((tax.niit - 0.038 * 10_000.0).abs() < 1e-9);
+    assert!((tax.state - 0.05 * 30_000.0).abs() < 1e-9);
+    assert_eq!(30_000.0, table.top_of_bracket(0.1));
+    assert!(TaxTables::parse("2026,gains,0,15\n").is_err());
+    assert!(TaxTables::parse("2026,ordinary,0,10\n2026,bonus,0,10\n").is_err());
+}
+
+#[test]
+fn rebalancing_sells_sheltered_holdings_first() {
+    use crate::asset::Asset;
+    use crate::date::Date;
+    use crate::jurisdiction::UnitedStates;
+    use crate::transaction::{Transaction, TransactionKind};
+    let mut taxable = Account::new(AccountType::Taxable);
+    let date = Date::new(2019, 1, 2).unwrap();
+    taxable.record(Transaction::new(date, TransactionKind::Contribution, AssetClass::Domestic, 50_000.0)).unwrap();
+    let mut valued = Account::new(AccountType::Taxable);
+    valued.add_asset(Asset::new(AssetClass::Domestic, 100_000.0));
+    taxable.revalue(&valued, date).unwrap();
+    let mut roth = Account::new(AccountType::Roth);
+    roth.add_asset(Asset::new(AssetClass::Domestic, 20_000.0));
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    // 60,000 of domestic must go: 20,000 from the Roth, 40,000 from taxable at half gain
+    let today = Date::new(2026, 1, 2).unwrap();
+    let (income, warnings) = realized_by_rebalancing(&[taxable, roth], &target, &PriceTable::new(), &today,
+                                                     &UnitedStates);
+    assert!((income.qualified - 20_000.0).abs() < 1e-6);
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn rebalancing_splits_lot_gains_by_term_and_warns_without_basis() {
+    use crate::asset::Asset;
+    use crate::date::Date;
+    use crate::jurisdiction::UnitedStates;
+    use crate::transaction::{Transaction, TransactionKind};
+    let buy = |date, shares, price| Transaction::with_shares(date, TransactionKind::Buy, AssetClass::Domestic, "VTI",
+                                                             shares, price);
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.record(buy(Date::new(2024, 3, 1).unwrap(), 50.0, 100.0)).unwrap();
+    taxable.record(buy(Date::new(2025, 11, 3).unwrap(), 50.0, 150.0)).unwrap();
+    let today = Date::new(2026, 1, 2).unwrap();
+    let mut prices = PriceTable::new();
+    prices.add("VTI", today, 200.0);
+    let mut valued = Account::new(AccountType::Taxable);
+    valued.add_asset(Asset::new(AssetClass::Domestic, 20_000.0));
+    taxable.revalue(&valued, today).unwrap();
+    let mut bonds = Account::new(AccountType::Roth);
+    bonds.add_asset(Asset::new(AssetClass::Bond, 4_000.0));
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 20.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 80.0));
+    // 15,200 of domestic must go: all of the first lot, held over a year, then 5,200 of the second
+    let (income, warnings) = realized_by_rebalancing(&[taxable.snapshot(), bonds.snapshot()], &target, &prices,
+                                                     &today, &UnitedStates);
+    assert_eq!(0.0, income.qualified + income.ordinary);
+    assert_eq!(1, warnings.len());
+    let (income, warnings) = realized_by_rebalancing(&[taxable, bonds], &target, &prices, &today, &UnitedStates);
+    assert!((income.qualified - 5_000.0).abs() < 1e-6);
+    assert!((income.ordinary - 5_200.0 * 0.25).abs() < 1e-6);
+    assert!(warnings.is_empty());
+}
@@ -0,0 +1,168 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fmt;
+use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+
+#[derive(Clone, PartialEq)]
+pub enum TransactionKind {
+    Contribution,
+    Withdrawal,
+    Buy,
+    Sell,
+    Dividend,
+    TransferIn,
+    TransferOut,
+    /// Rise in market value found when the holdings were priced
+    MarketGain,
+    /// Fall in market value found when the holdings were priced
+    MarketLoss,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self {
+            TransactionKind::Contribution => "Contribution".fmt(f),
+            TransactionKind::Withdrawal => "Withdrawal".fmt(f),
+            TransactionKind::Buy => "Buy".fmt(f),
+            TransactionKind::Sell => "Sell".fmt(f),
+            TransactionKind::Dividend => "Dividend".fmt(f),
+            TransactionKind::TransferIn => "Transfer In".fmt(f),
+            TransactionKind::TransferOut => "Transfer Out".fmt(f),
+            TransactionKind::MarketGain => "Market Gain".fmt(f),
+            TransactionKind::MarketLoss => "Market Loss".fmt(f),
+        }
+    }
+}
+
+impl TransactionKind {
+    /// Returns a short name for the kind that `parse` reads back, for saving to files
+    pub fn key(&self) -> &'static str {
+        match self {
+            TransactionKind::Contribution => "contribution",
+            TransactionKind::Withdrawal => "withdrawal",
+            TransactionKind::Buy => "buy",
+            TransactionKind::Sell => "sell",
+            TransactionKind::Dividend => "dividend",
+            TransactionKind::TransferIn => "transfer_in",
+            TransactionKind::TransferOut => "transfer_out",
+            TransactionKind::MarketGain => "market_gain",
+            TransactionKind::MarketLoss => "market_loss",
+        }
+    }
+
+    /// Reads a transaction kind from its key or its displayed name, ignoring case
+    pub fn parse(name: &str) -> Result<TransactionKind, String> {
+        match name.trim().to_lowercase().replace(' ', "_").as_str() {
+            "contribution" => Ok(TransactionKind::Contribution),
+            "withdrawal" => Ok(TransactionKind::Withdrawal),
+            "buy" => Ok(TransactionKind::Buy),
+            "sell" => Ok(TransactionKind::Sell),
+            "dividend" => Ok(TransactionKind::Dividend),
+            "transfer_in" => Ok(TransactionKind::TransferIn),
+            "transfer_out" => Ok(TransactionKind::TransferOut),
+            "market_gain" => Ok(TransactionKind::MarketGain),
+            "market_loss" => Ok(TransactionKind::MarketLoss),
+            _ => Err(format!("Unknown transaction kind {}", name.trim())),
+        }
+    }
+
+    /// Whether the transaction takes money out of the account's holdings
+    pub fn is_outflow(&self) -> bool {
+        matches!(self, TransactionKind::Withdrawal | TransactionKind::Sell | TransactionKind::TransferOut
+                 | TransactionKind::MarketLoss)
+    }
+
+    /// Whether the transaction records a change in market value rather than money moving, so it
+    /// changes the holdings but not their cost basis
+    pub fn is_revaluation(&self) -> bool {
+        matches!(self, TransactionKind::MarketGain | TransactionKind::MarketLoss)
+    }
+
+    /// Whether money crosses the boundary of the account, as opposed to moving between holdings
+    /// or being earned inside the account
+    pub fn is_external(&self) -> bool {
+        matches!(self, TransactionKind::Contribution | TransactionKind::Withdrawal
+                 | TransactionKind::TransferIn | TransactionKind::TransferOut)
+    }
+}
+
+/// A single dated entry in an account's ledger. The asset value is always positive, the kind
+/// decides whether it is added to or removed from the account. Transactions in a security also
+/// record its ticker and the number of shares so the holding can be valued from prices later.
+pub struct Transaction {
+    pub date: Date,
+    pub kind: TransactionKind,
+    pub asset: Asset,
+    pub ticker: Option<String>,
+    pub shares: f64,
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut disp = format!("{}  {:<14}{:<15}{:>12}", self.date, self.kind, self.asset.class,
+                               self.asset.value.separated_string_with_fixed_place(2));
+        if let Some(ticker) = &self.ticker {
+            disp.push_str(&format!("  {} x {:.4}", ticker, self.shares));
+        }
+        disp.fmt(f)
+    }
+}
+
+impl Transaction {
+    /// Creates a Transaction of the given kind moving value of an asset class on a date
+    pub fn new(date: Date, kind: TransactionKind, class: AssetClass, value: f64) -> Transaction {
+        Transaction {
+            date,
+            kind,
+            asset: Asset::new(class, value),
+            ticker: None,
+            shares: 0.0,
+        }
+    }
+
+    /// Creates a Transaction in shares of a security bought, sold or transferred at a price
+    pub fn with_shares(date: Date, kind: TransactionKind, class: AssetClass, ticker: &str, shares: f64,
+                       price: f64) -> Transaction {
+        Transaction {
+            date,
+            kind,
+            asset: Asset::new(class, shares * price),
+            ticker: Some(ticker.to_uppercase()),
+            shares,
+        }
+    }
+
+    /// Returns the change in holdings caused by the transaction, negative for outflows
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let sale = Transaction::new(date, TransactionKind::Sell, AssetClass::Bond, 25.0);
+    /// assert_eq!(-25.0, sale.signed_value());
+    /// ```
+    pub fn signed_value(&self) -> f64 {
+        if self.kind.is_outflow() {
+            -self.asset.value
+        } else {
+            self.asset.value
+        }
+    }
+}
+
+#[test]
+fn outflows_reduce_holdings() {
+    let date = Date::new(2019, 1, 2).unwrap();
+    let sale = Transaction::new(date, TransactionKind::Sell, AssetClass::Bond, 25.0);
+    let dividend = Transaction::new(date, TransactionKind::Dividend, AssetClass::Bond, 2.5);
+    assert_eq!(-25.0, sale.signed_value());
+    assert_eq!(2.5, dividend.signed_value());
+}
+
+#[test]
+fn only_contributions_withdrawals_and_transfers_are_external() {
+    assert!(TransactionKind::Contribution.is_external());
+    assert!(TransactionKind::TransferOut.is_external());
+    assert!(!TransactionKind::Buy.is_external());
+    assert!(!TransactionKind::Dividend.is_external());
+}
@@ -1,15 +1,61 @@
 extern crate separator;
 use separator::FixedPlaceSeparatable;
+use std::fs;
+use std::path::Path;
 use std::io;
 use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
 use super::asset::{Asset,AssetClass};
 use super::account::{self, AccountType, Account};
+use super::aftertax::{self, TaxAdjustment};
+use super::date::Date;
+use super::gains;
+use super::glidepath::{Basis, GlidePath};
+use super::jurisdiction::Jurisdiction;
+use super::limits::{self, ContributionLimits, Contributor};
+use super::location::{self, LocationAssumptions, TaxRates};
+use super::assumptions::MarketAssumptions;
+use super::backtest::{self, Policy};
+use super::charity;
+use super::conversion::{self, ConversionInputs};
+use super::montecarlo::{self, Projection};
+use super::optimizer::{self, Constraints};
+use super::performance;
+use super::prices::{self, PriceTable};
+use super::questionnaire::{self, Rules};
+use super::rebalance;
+use super::returns::ReturnHistory;
+use super::risk;
+use super::rmd::{self, Inheritance, LifeTable, LifeTables};
+use super::riskparity::{self, Method, RiskModel};
+use super::snapshot::{self, Snapshot};
+use super::spending::{self, Strategy};
+use super::transaction::TransactionKind;
+use super::waterfall::{self, Waterfall};
+use super::withdrawal::{self, Decumulation, Order};
+use super::stress::{self, Scenario};
+use super::tax::{self, Brackets, Income, TaxTables};
 pub struct User {
     fname: String,
     lname: String,
     pub accounts: Vec<Account>,
     allocation: Account,
     target: Account,
+    history: Vec<Snapshot>,
+    /// File the history is saved to after every snapshot
+    history_path: Option<String>,
+    /// File the accounts and their ledgers are saved to after every snapshot
+    accounts_path: Option<String>,
+    prices: PriceTable,
+    birth_date: Option<Date>,
+    glide_path: Option<GlidePath>,
+    returns: Option<ReturnHistory>,
+    risk_free_rate: f64,
+    limits: ContributionLimits,
+    income: Option<f64>,
+    jurisdiction: Box<dyn Jurisdiction>,
+    tax_adjustment: Option<TaxAdjustment>,
+    taxes: TaxTables,
 }
 
 impl fmt::Display for User {
@@ -17,29 +63,85 @@ impl fmt::Display for User {
         let mut disp = "\nName: ".to_string();
         disp.push_str(&format!("{} {}\n", self.fname, self.lname));
         for i in &self.accounts {
-            disp.push_str(&format!("{}\n", i));
+            disp.push_str(&format!("{}\n", i.describe(self.jurisdiction.as_ref())));
+        }
+        disp.push_str(&format!("Target {}\n", self.effective_target()));
+        if let Some(adjustment) = &self.tax_adjustment {
+            disp.push_str(&format!("Current (after tax) {}\n", self.allocation));
+            disp.push_str(&aftertax::report(&self.accounts, &self.effective_target(), adjustment,
+                                            self.jurisdiction.as_ref()));
+        } else {
+            disp.push_str(&format!("Current {}\n", self.allocation));
         }
-        disp.push_str(&format!("Target {}\n", self.target));
-        disp.push_str(&format!("Current {}\n", self.allocation));
         disp.push_str(&format!("{}", self.display_account_allocation()));
+        if let Some(history) = &self.returns {
+            disp.push_str(&format!("\n{}", risk::report(history, &self.get_holdings(), &self.effective_target(),
+                                                        self.risk_free_rate)));
+        }
         disp.fmt(f)
     }
 }
 
 impl User {
-    /// Creates a new user given a first name and last name
-    pub fn new(fname: &str, lname: &str) -> User {
+    /// Creates a new user given a first name, last name and the country whose accounts they hold
+    pub fn new(fname: &str, lname: &str, jurisdiction: Box<dyn Jurisdiction>) -> User {
         User {
             fname: fname.to_string(),
             lname: lname.to_string(),
             accounts: Vec::new(),
             allocation: Account::new(AccountType::Allocation),
             target: Account::new(AccountType::Allocation),
+            history: Vec::new(),
+            history_path: None,
+            accounts_path: None,
+            prices: PriceTable::new(),
+            birth_date: None,
+            glide_path: None,
+            returns: None,
+            risk_free_rate: risk::DEFAULT_RISK_FREE_RATE,
+            limits: jurisdiction.contribution_limits(),
+            taxes: jurisdiction.tax_tables(),
+            income: None,
+            jurisdiction,
+            tax_adjustment: None,
+        }
+    }
+
+    /// Loads the User's saved snapshots and saves every later snapshot to the same file. A file
+    /// that does not exist yet starts an empty history.
+    pub fn load_history(&mut self, path: &str) -> Result<(), String> {
+        self.history_path = Some(path.to_string());
+        if Path::new(path).exists() {
+            self.history = snapshot::load(path)?;
         }
+        Ok(())
     }
 
-    /// Adds an account to users set of accounts, then recalculates the users account and asset allocation
-    fn add_account(&mut self, account: Account) {
+    /// Sets the file the user's accounts are saved to, loading the accounts and their ledgers from
+    /// it when it already exists
+    pub fn load_accounts(&mut self, path: &str) -> Result<(), String> {
+        self.accounts_path = Some(path.to_string());
+        if Path::new(path).exists() {
+            self.accounts = snapshot::load_accounts(path)?;
+            self.current_allocation();
+        }
+        Ok(())
+    }
+
+    /// Adds an account to users set of accounts, then recalculates the users account and asset allocation.
+    /// An account without a name, or named like another account, is given a numbered name.
+    fn add_account(&mut self, mut account: Account) {
+        let taken = |name: &str| name.is_empty() || self.accounts.iter().any(|a| a.name == name);
+        if taken(&account.name) {
+            let base = if account.name.is_empty() {
+                self.jurisdiction.account_name(&account.classification)
+            } else {
+                account.name.clone()
+            };
+            let number = (1..).find(|n| !taken(&format!("{} {}", base, n))).unwrap_or(1);
+            account.name = format!("{} {}", base, number);
+            println!("Named the account {}", account.name);
+        }
         self.accounts.push(account);
         self.current_allocation();
     }
@@ -48,41 +150,715 @@ impl User {
     /// The account must have a value of 100.00
     fn target_allocation(&mut self, allocation: Account) -> Result<(), String> {
         let total = allocation.get_total_value();
-        if total == 100.00 {
+        if allocation.is_full_allocation() {
             self.target = allocation;
+            self.record_snapshot();
             Ok(())
         } else {
             Err(format!("Allocation should be 100.00, but was {}", total))
         }
     }
 
+    /// Returns the target allocation for today: the glide path's target when the user follows
+    /// one, otherwise their static target
+    fn effective_target(&self) -> Account {
+        let today = Date::today();
+        self.glide_path.as_ref()
+            .and_then(|path| path.target_on(&today, self.birth_date.as_ref()))
+            .unwrap_or_else(|| self.target.snapshot())
+    }
+
+    /// Ask user of the program for a glide path file, and their birth date when the glide path
+    /// follows their age. Leaving the path blank clears the glide path.
+    fn request_glide_path(&mut self) {
+        let path = request_path("Path of the glide path file (CSV with an age or year column followed by a percentage per asset class)? Leave blank to clear the glide path");
+        if path.is_none() && self.glide_path.is_some() {
+            self.glide_path = None;
+            println!("Cleared the glide path, the target allocation is:\n{}", self.target);
+            self.record_snapshot();
+            return;
+        }
+        let glide_path = match GlidePath::load(&path.unwrap_or_default()) {
+            Ok(glide_path) => glide_path,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        if glide_path.basis == Basis::Age && self.birth_date.is_none() {
+            match request_birth_date() {
+                Ok(date) => self.birth_date = Some(date),
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        self.glide_path = Some(glide_path);
+        println!("Target allocation for today:\n{}", self.effective_target());
+        self.record_snapshot();
+    }
+
     /// Ask user of the program what action they would like to perform for the User
     pub fn request_action(&mut self) {
         loop {
             println!("What would you like to do?");
-            println!("1. Change target allocation\t2. Add account\t3. Display user info\t4. Display off target summary\t5. Quit");
+            println!("1. Change target allocation\t2. Add account\t3. Display user info\t4. Display off target summary");
+            println!("5. Record transaction\t6. Display transaction history\t7. Display allocation history\t8. Display returns");
+            println!("9. Load price file\t10. Display holdings at date\t11. Backtest rebalancing policies\t12. Monte Carlo projection");
+            println!("13. Set or clear glide path\t14. Load return history for risk\t15. Stress test");
+            println!("16. Contribution limits\t17. Plan contributions\t18. Tax-adjusted allocation");
+            println!("19. Optimize asset location\t20. Plan Roth conversions\t21. Required minimum distributions");
+            println!("22. Compare withdrawal orders\t23. Compare withdrawal strategies\t24. Tax tables");
+            println!("25. Capital gains report\t26. Plan charitable gifts\t27. Quit");
             let mut action = String::new();
             io::stdin().read_line(&mut action)
                 .expect("Failed to read line");
             let choice: u8 = action.trim().parse().unwrap_or(0);
             match choice {
-                1 => match account::request_allocation() {
+                1 => match self.request_target() {
                     Ok(allocation) => match self.target_allocation(allocation) {
                         Ok(()) => println!("Successfully set target allocation"),
                         Err(why) => println!("{:?}", why),
                     }
                     Err(why) => println!("{}", why)
                 }
-                2 => match account::setup_new_account() {
+                2 => match account::setup_new_account(&self.prices, self.jurisdiction.as_ref()) {
                     Ok(account) => self.add_account(account),
                     Err(why) => println!("{}", why),
                 }
                 3 => println!("{}", self),
                 4 => self.display_allocation_diff(),
-                5 => break,
+                5 => self.record_transaction(),
+                6 => self.display_transaction_history(),
+                7 => println!("{}", snapshot::history_report(&self.history)),
+                8 => println!("{}", performance::report(&self.history, &self.accounts, &Date::today())),
+                9 => self.load_prices(),
+                10 => self.display_holdings_at_date(),
+                11 => self.backtest(),
+                12 => self.project(),
+                13 => self.request_glide_path(),
+                14 => self.load_return_history(),
+                15 => self.stress_test(),
+                16 => self.display_contribution_limits(),
+                17 => self.plan_contributions(),
+                18 => self.request_tax_adjustment(),
+                19 => self.optimize_location(),
+                20 => self.plan_roth_conversions(),
+                21 => self.display_required_distributions(),
+                22 => self.compare_withdrawal_orders(),
+                23 => self.compare_withdrawal_strategies(),
+                24 => self.request_tax_tables(),
+                25 => self.display_capital_gains(),
+                26 => self.plan_charitable_gifts(),
+                27 => break,
+                _ => continue,
+            }
+
+        }
+    }
+
+    /// Ask user of the program for a target allocation, either typed in directly or proposed by the
+    /// risk questionnaire
+    fn request_target(&self) -> Result<Account, String> {
+        loop {
+            println!("1. Enter percentages\t2. Answer risk questionnaire\t3. Optimize for a volatility or return");
+            println!("4. Risk parity or inverse volatility from price history\t5. Cancel");
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)
+                .expect("Failed to read line");
+            match choice.trim().parse().unwrap_or(0) {
+                1 => return account::request_allocation(),
+                2 => break,
+                3 => return request_optimized_allocation(),
+                4 => return request_risk_based_allocation(&self.get_holdings()),
+                5 => return Err("Cancelled target allocation".to_string()),
+                _ => continue,
+            }
+        }
+        let rules = match request_path("Path of the questionnaire rules file (lines of name,value)? Leave blank for standard rules") {
+            Some(path) => Rules::load(&path)?,
+            None => Rules::standard(),
+        };
+        let age = self.birth_date.map(|birth| (birth.days_until(&Date::today()) as f64 / 365.25) as u32);
+        let answers = questionnaire::request_answers(age)?;
+        questionnaire::review_proposal(questionnaire::propose(&answers, &rules))
+    }
+
+    /// Ask user of the program which of the User's accounts to act on
+    fn choose_account(&self) -> Option<usize> {
+        if self.accounts.is_empty() {
+            println!("Add an account first");
+            return None;
+        }
+        loop {
+            println!("Which account?");
+            for (i, account) in self.accounts.iter().enumerate() {
+                print!("{}. {}\t", i + 1, account.name);
+            }
+            println!("{}. Cancel", self.accounts.len() + 1);
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)
+                .expect("Failed to read line");
+            let choice: usize = choice.trim().parse().unwrap_or(0);
+            if choice == self.accounts.len() + 1 {
+                return None;
+            } else if (1..=self.accounts.len()).contains(&choice) {
+                return Some(choice - 1);
+            }
+        }
+    }
+
+    /// Records a transaction against one of the User's accounts, then recalculates the users
+    /// asset allocation
+    fn record_transaction(&mut self) {
+        let index = match self.choose_account() {
+            Some(index) => index,
+            None => return,
+        };
+        let transaction = match account::request_transaction() {
+            Ok(transaction) => transaction,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        if transaction.kind == TransactionKind::Contribution {
+            let year = self.jurisdiction.tax_year(&transaction.date);
+            let contributor = Contributor { year, age: self.age_at_end_of(year), income: self.income };
+            let warning = self.limits.check(&self.accounts, &self.accounts[index].classification,
+                                            transaction.asset.value, &contributor, self.jurisdiction.as_ref());
+            if let Some(warning) = warning {
+                println!("{}", warning);
+                println!("Record it anyway? (y/n)");
+                let mut choice = String::new();
+                io::stdin().read_line(&mut choice)
+                    .expect("Failed to read line");
+                if !matches!(choice.trim().to_lowercase().as_str(), "y" | "yes") {
+                    return;
+                }
+            }
+        }
+        match self.accounts[index].record(transaction) {
+            Ok(()) => self.current_allocation(),
+            Err(why) => println!("{}", why),
+        }
+    }
+
+    /// Ask user of the program for the rates used to count balances after tax, or turn tax-adjusted
+    /// mode off, then recompute the allocation
+    fn request_tax_adjustment(&mut self) {
+        println!("Expected marginal income tax rate in retirement, in percent? Leave blank to count every dollar alike");
+        let mut rate = String::new();
+        io::stdin().read_line(&mut rate)
+            .expect("Failed to read line");
+        if rate.trim().is_empty() {
+            self.tax_adjustment = None;
+            println!("Counting balances at face value");
+            self.current_allocation();
+            return;
+        }
+        let income_rate = match rate.trim().parse::<f64>() {
+            Ok(rate) if (0.0..=100.0).contains(&rate) => rate / 100.0,
+            _ => {
+                println!("Rate must be a percentage from 0 to 100");
+                return;
+            }
+        };
+        println!("Capital gains tax rate on unrealized gains in taxable accounts, in percent? Leave blank to ignore gains");
+        let mut rate = String::new();
+        io::stdin().read_line(&mut rate)
+            .expect("Failed to read line");
+        let gains_rate = match rate.trim() {
+            "" => None,
+            rate => match rate.parse::<f64>() {
+                Ok(rate) if (0.0..=100.0).contains(&rate) => Some(rate / 100.0),
+                _ => {
+                    println!("Rate must be a percentage from 0 to 100");
+                    return;
+                }
+            },
+        };
+        let adjustment = TaxAdjustment { income_rate, gains_rate };
+        println!("{}", aftertax::report(&self.accounts, &self.effective_target(), &adjustment,
+                                        self.jurisdiction.as_ref()));
+        self.tax_adjustment = Some(adjustment);
+        self.current_allocation();
+    }
+
+    /// Ask user of the program for their tax rates and show where each asset class is best held
+    /// across the User's accounts, and what moving it there is worth
+    fn optimize_location(&self) {
+        if self.get_total_value() <= 0.0 {
+            println!("Add an account first");
+            return;
+        }
+        let path = request_path("Path of the location assumptions file (lines of class,return,yield,qualified,foreign_credit)? Leave blank for standard assumptions");
+        let assumptions = match path.map(|path| LocationAssumptions::load(&path)) {
+            None => LocationAssumptions::standard(),
+            Some(Ok(assumptions)) => assumptions,
+            Some(Err(why)) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        let mut rates = Vec::new();
+        for question in &["Marginal income tax rate, in percent?",
+                          "Tax rate on qualified dividends and long-term gains, in percent?",
+                          "Expected tax rate on withdrawals from tax-deferred accounts, in percent?"] {
+            match request_rate(question) {
+                Ok(rate) => rates.push(rate),
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        println!("How many years until the money is spent?");
+        let mut years = String::new();
+        io::stdin().read_line(&mut years)
+            .expect("Failed to read line");
+        let years: u32 = years.trim().parse().unwrap_or(0);
+        let rates = TaxRates { ordinary: rates[0], qualified: rates[1], withdrawal: rates[2], years };
+        let jurisdiction = self.jurisdiction.as_ref();
+        let proposed = location::optimize(&self.accounts, &self.get_holdings(), &assumptions, &rates, jurisdiction);
+        println!("{}", location::report(&self.accounts, &proposed, &assumptions, &rates, jurisdiction));
+        // Moving to the target changes what is held as well as where, so it is shown apart from
+        // the gain of placing the current holdings better
+        let target = self.effective_target();
+        if target.is_full_allocation() {
+            let at_target = location::optimize(&self.accounts, &target, &assumptions, &rates, jurisdiction);
+            let before = location::after_tax_wealth(&self.accounts, &assumptions, &rates, jurisdiction);
+            let after = location::after_tax_wealth(&at_target, &assumptions, &rates, jurisdiction);
+            println!("Moving to the target allocation with the best placement instead: {}, difference {}",
+                     after.separated_string_with_fixed_place(2), (after - before).separated_string_with_fixed_place(2));
+        }
+    }
+
+    /// Ask user of the program for a file of tax tables and a state income tax rate, then show the
+    /// tax on the User's income
+    fn request_tax_tables(&mut self) {
+        if let Some(path) = request_path("Path of the tax table file (lines of year,kind followed by the amounts of ordinary, gains and state brackets, the deduction and the NIIT)? Leave blank to keep the current tables") {
+            match TaxTables::load(&path) {
+                Ok(taxes) => self.taxes = taxes,
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        println!("Flat state income tax rate, in percent? Leave blank to keep the state brackets");
+        let mut rate = String::new();
+        io::stdin().read_line(&mut rate)
+            .expect("Failed to read line");
+        if !rate.trim().is_empty() {
+            match rate.trim().parse::<f64>() {
+                Ok(rate) if (0.0..=100.0).contains(&rate) => self.taxes.set_state(Brackets::flat(rate / 100.0)),
+                _ => {
+                    println!("Rate must be a percentage from 0 to 100");
+                    return;
+                }
+            }
+        }
+        let year = self.jurisdiction.tax_year(&Date::today());
+        match self.income {
+            Some(income) => println!("Tax on {} of ordinary income in {}\n{}", income.separated_string_with_fixed_place(2), year,
+                                     self.taxes.for_year(year).tax(&Income::ordinary(income))),
+            None => println!("Enter your income when planning contributions to see the tax on it"),
+        }
+    }
+
+    /// Ask user of the program for a tax year, then display the gains realized in the User's taxable
+    /// accounts that year and the gains not yet realized, optionally exporting the sales as Form 8949
+    fn display_capital_gains(&self) {
+        let today = Date::today();
+        let current = self.jurisdiction.tax_year(&today);
+        println!("Which tax year? Leave blank for {}", current);
+        let mut year = String::new();
+        io::stdin().read_line(&mut year)
+            .expect("Failed to read line");
+        let year: i32 = if year.trim().is_empty() {
+            current
+        } else {
+            match year.trim().parse() {
+                Ok(year) => year,
+                Err(_) => {
+                    println!("Year must be a whole number");
+                    return;
+                }
+            }
+        };
+        if !self.accounts.iter().any(|a| a.classification == AccountType::Taxable) {
+            println!("Add a taxable account first");
+            return;
+        }
+        let accounts: Vec<&Account> = self.accounts.iter().collect();
+        let sales = gains::realized(&accounts, year, self.jurisdiction.as_ref());
+        print!("{}", gains::realized_report(&sales, year));
+        let short_term: f64 = sales.iter().filter(|s| !s.is_long_term()).map(|s| s.gain()).sum();
+        let long_term: f64 = sales.iter().filter(|s| s.is_long_term()).map(|s| s.gain()).sum();
+        let realized = Income::capital_gains(short_term, long_term);
+        if let Some(income) = self.income {
+            let tax = self.taxes.for_year(year).incremental(&Income::ordinary(income), &realized);
+            println!("Estimated tax on the net gains: {}", tax.total().separated_string_with_fixed_place(2));
+        }
+        for account in &self.accounts {
+            if account.classification == AccountType::Taxable {
+                println!("Unrealized gains in {} as of {}", account.name, today);
+                print!("{}", gains::unrealized_report(account, &self.prices, &today));
+            }
+        }
+        if sales.is_empty() {
+            return;
+        }
+        if let Some(path) = request_path("Path to export the sales as Form 8949 CSV? Leave blank to skip") {
+            match fs::write(&path, gains::form_8949(&sales)) {
+                Ok(()) => println!("Wrote {} sales to {}", sales.len(), path),
+                Err(why) => println!("Could not write Form 8949 file {}: {}", path, why),
+            }
+        }
+    }
+
+    /// Ask user of the program how much they mean to donate, then propose appreciated lots to give
+    /// in place of cash and the purchases that restore the target with the cash kept
+    fn plan_charitable_gifts(&self) {
+        if self.prices.is_empty() {
+            println!("Load a price file first");
+            return;
+        }
+        println!("How much do you plan to donate?");
+        let mut amount = String::new();
+        io::stdin().read_line(&mut amount)
+            .expect("Failed to read line");
+        let amount: f64 = match amount.trim().parse() {
+            Ok(amount) if amount > 0.0 => amount,
+            _ => {
+                println!("Donation must be a positive number");
+                return;
+            }
+        };
+        let today = Date::today();
+        let plan = charity::plan(&self.accounts, &self.prices, &today, amount, &self.effective_target(),
+                                 self.jurisdiction.as_ref());
+        let year = self.jurisdiction.tax_year(&today);
+        let base = Income::ordinary(self.income.unwrap_or(0.0));
+        let avoided = charity::tax_avoided(&plan, self.taxes.for_year(year), &base);
+        print!("{}", charity::report(&plan, &self.accounts, avoided));
+        if self.income.is_none() {
+            println!("Enter your income when planning contributions for a closer estimate of the tax avoided");
+        }
+    }
+
+    /// Ask user of the program for their income, brackets and plans, then propose yearly
+    /// conversions from traditional accounts to Roth and show what they cost and are worth
+    fn plan_roth_conversions(&self) {
+        match self.request_conversion_inputs() {
+            Ok(inputs) => match conversion::plan(&self.accounts, &inputs) {
+                Ok(plan) => println!("{}", conversion::report(&plan, &inputs)),
+                Err(why) => println!("{}", why),
+            },
+            Err(why) => println!("{}", why),
+        }
+    }
+
+    fn request_conversion_inputs(&self) -> Result<ConversionInputs, String> {
+        println!("Ordinary income each year before conversions and deductions?");
+        let mut income = String::new();
+        io::stdin().read_line(&mut income)
+            .expect("Failed to read line");
+        let income: f64 = match income.trim() {
+            "" => self.income.ok_or_else(|| "No income on file, enter the income instead".to_string())?,
+            income => income.parse().map_err(|_| "Income must be a number".to_string())?,
+        };
+        let fill_rate = request_rate("Convert up to the top of which bracket, by its rate in percent?")?;
+        let projected_rate = request_rate("Expected tax rate on traditional withdrawals later, in percent?")?;
+        let growth = request_rate("Expected yearly return, in percent?")?;
+        println!("How many years should conversions run for?");
+        let mut years = String::new();
+        io::stdin().read_line(&mut years)
+            .expect("Failed to read line");
+        let years: u32 = years.trim().parse().map_err(|_| "Years must be a whole number".to_string())?;
+        println!("How many years until the money is spent?");
+        let mut horizon = String::new();
+        io::stdin().read_line(&mut horizon)
+            .expect("Failed to read line");
+        let horizon: u32 = horizon.trim().parse().map_err(|_| "Years must be a whole number".to_string())?;
+        let first_year = self.jurisdiction.tax_year(&Date::today());
+        Ok(ConversionInputs {
+            first_year,
+            income,
+            table: self.taxes.for_year(first_year).clone(),
+            fill_rate,
+            projected_rate,
+            growth,
+            years,
+            horizon,
+        })
+    }
+
+    /// Shows this year's required minimum distributions and what to sell to raise them, asking for
+    /// the User's birth date and the details of any inherited accounts
+    fn display_required_distributions(&mut self) {
+        if self.birth_date.is_none() {
+            match request_birth_date() {
+                Ok(date) => self.birth_date = Some(date),
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        let birth_year = self.birth_date.map(|birth| birth.year).unwrap_or(0);
+        let mut tables = LifeTables::standard();
+        if let Some(path) = request_path("Path of the Uniform Lifetime Table file (lines of age,divisor)? Leave blank for the IRS table") {
+            match LifeTable::load(&path) {
+                Ok(table) => tables.uniform = table,
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        let mut inheritances = Vec::new();
+        for (index, account) in self.accounts.iter().enumerate() {
+            if account.classification != AccountType::InheritedIra {
+                continue;
+            }
+            println!("{}: in what year did the original owner die?", account.name);
+            let mut death_year = String::new();
+            io::stdin().read_line(&mut death_year)
+                .expect("Failed to read line");
+            let death_year: i32 = match death_year.trim().parse() {
+                Ok(year) => year,
+                Err(_) => {
+                    println!("Year must be a whole number");
+                    return;
+                }
+            };
+            println!("Had they started taking required distributions? (y/n)");
+            let mut started = String::new();
+            io::stdin().read_line(&mut started)
+                .expect("Failed to read line");
+            let owner_started = started.trim().eq_ignore_ascii_case("y");
+            inheritances.push((index, Inheritance { death_year, owner_started, first_age: death_year + 1 - birth_year }));
+        }
+        let year = Date::today().year;
+        let balances = rmd::prior_year_end_balances(&self.history, &self.accounts, year);
+        let distributions = rmd::plan(&self.accounts, &balances, year, birth_year, &inheritances, &tables,
+                                      &self.effective_target());
+        println!("{}", rmd::report(&distributions, &self.accounts, year));
+    }
+
+    /// Ask user of the program what retirement looks like, then simulate drawing the User's accounts
+    /// down in each withdrawal order and compare the taxes paid and what is left
+    fn compare_withdrawal_orders(&self) {
+        if self.get_total_value() <= 0.0 {
+            println!("Add an account first");
+            return;
+        }
+        match self.request_decumulation() {
+            Ok(decumulation) => {
+                let targets = withdrawal::yearly_targets(decumulation.first_year, decumulation.years,
+                                                         &self.target, self.glide_path.as_ref(),
+                                                         self.birth_date.as_ref());
+                let outcomes: Vec<withdrawal::Outcome> = Order::all().into_iter()
+                    .map(|order| withdrawal::simulate(&self.accounts, &decumulation, &targets, order,
+                                                      self.jurisdiction.as_ref()))
+                    .collect();
+                println!("{}", withdrawal::report(&outcomes, self.jurisdiction.as_ref()));
+            }
+            Err(why) => println!("{}", why),
+        }
+    }
+
+    fn request_decumulation(&self) -> Result<Decumulation, String> {
+        let this_year = Date::today().year;
+        println!("What year does retirement start? Leave blank for {}", this_year);
+        let mut first_year = String::new();
+        io::stdin().read_line(&mut first_year)
+            .expect("Failed to read line");
+        let first_year: i32 = match first_year.trim() {
+            "" => this_year,
+            year => year.parse().map_err(|_| "Year must be a whole number".to_string())?,
+        };
+        println!("How many years should retirement last?");
+        let mut years = String::new();
+        io::stdin().read_line(&mut years)
+            .expect("Failed to read line");
+        let years: u32 = years.trim().parse().map_err(|_| "Years must be a whole number".to_string())?;
+        println!("How much will you spend each year after tax, in today's dollars?");
+        let mut spending = String::new();
+        io::stdin().read_line(&mut spending)
+            .expect("Failed to read line");
+        let spending: f64 = spending.trim().parse().map_err(|_| "Spending must be a number".to_string())?;
+        println!("Taxable income from pensions or Social Security each year, in today's dollars? Leave blank for none");
+        let mut other_income = String::new();
+        io::stdin().read_line(&mut other_income)
+            .expect("Failed to read line");
+        let other_income: f64 = match other_income.trim() {
+            "" => 0.0,
+            income => income.parse().map_err(|_| "Income must be a number".to_string())?,
+        };
+        let inflation = request_rate("Expected yearly inflation, in percent?")?;
+        let fill_rate = request_rate("Bracket filling draws tax-deferred accounts up to the top of which bracket, by its rate in percent?")?;
+        let assumptions = request_assumptions()?;
+        Ok(Decumulation {
+            first_year,
+            years,
+            spending,
+            other_income,
+            inflation,
+            table: self.taxes.for_year(first_year).clone(),
+            fill_rate,
+            returns: assumptions.classes.iter().cloned().zip(assumptions.expected_returns.iter().cloned()).collect(),
+            birth_year: self.birth_date.map(|birth| birth.year),
+        })
+    }
+
+    /// Ask user of the program how long retirement lasts and where returns come from, then show how
+    /// each withdrawal strategy would have spent the User's portfolio
+    fn compare_withdrawal_strategies(&self) {
+        let wealth: f64 = self.accounts.iter()
+            .filter(|a| withdrawal::spendable(&a.classification, self.jurisdiction.as_ref()))
+            .map(|a| a.get_total_value())
+            .sum();
+        if wealth <= 0.0 {
+            println!("Add an account that can be spent from first");
+            return;
+        }
+        println!("How many years should withdrawals last?");
+        let mut years = String::new();
+        io::stdin().read_line(&mut years)
+            .expect("Failed to read line");
+        let years: usize = match years.trim().parse() {
+            Ok(years) if years > 0 => years,
+            _ => {
+                println!("Years must be a positive whole number");
+                return;
+            }
+        };
+        let rates = ["Initial withdrawal rate, in percent?", "Expected yearly inflation, in percent?",
+                     "Expected real return for variable percentage withdrawals, in percent?"].iter()
+            .map(|question| request_rate(question))
+            .collect::<Result<Vec<f64>, String>>();
+        let rates = match rates {
+            Ok(rates) => rates,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        let weights = spending::weights(&self.get_holdings(), &self.effective_target());
+        let paths = loop {
+            println!("1. Monte Carlo returns\t2. Historical returns");
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)
+                .expect("Failed to read line");
+            match choice.trim().parse().unwrap_or(0) {
+                1 => {
+                    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_nanos() as u64).unwrap_or(0);
+                    break request_assumptions()
+                        .and_then(|assumptions| spending::simulated_paths(&assumptions, &weights, years, 1000, seed));
+                }
+                2 => {
+                    let path = request_path("Path of the return history file (date followed by a return column per asset class)?");
+                    break ReturnHistory::load(&path.unwrap_or_default())
+                        .and_then(|history| spending::historical_paths(&history, &weights, years));
+                }
                 _ => continue,
             }
+        };
+        match paths {
+            Ok(paths) => {
+                let evaluations: Vec<spending::Evaluation> = Strategy::standard(rates[0], rates[2]).iter()
+                    .map(|strategy| spending::evaluate(strategy, wealth, &paths, rates[1]))
+                    .collect();
+                println!("{}", spending::report(&evaluations, paths.len()));
+            }
+            Err(why) => println!("{}", why),
+        }
+    }
+
+    /// Returns the age the User turns in a tax year, if their birth date is known
+    fn age_at_end_of(&self, year: i32) -> Option<i32> {
+        self.birth_date.map(|birth| year - birth.year)
+    }
+
+    /// Shows how much of this year's contribution limits the User has used, optionally loading
+    /// the limits from a file first
+    fn display_contribution_limits(&mut self) {
+        if let Some(path) = request_path("Path of the contribution limit file (lines of year,accounts,limit,catch_up_age,catch_up)? Leave blank to keep the current limits") {
+            match ContributionLimits::load(&path) {
+                Ok(limits) => self.limits = limits.starting(self.jurisdiction.tax_year_start()),
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            }
+        }
+        let year = self.jurisdiction.tax_year(&Date::today());
+        println!("{}", limits::report(&self.limits, &self.accounts, year, self.age_at_end_of(year), self.income,
+                                      self.jurisdiction.as_ref()));
+    }
+
+    /// Displays every transaction recorded against one of the User's accounts
+    fn display_transaction_history(&self) {
+        if let Some(index) = self.choose_account() {
+            let account = &self.accounts[index];
+            println!("Transactions for {}", self.jurisdiction.account_name(&account.classification));
+            for transaction in account.transactions() {
+                println!("{}", transaction);
+            }
+            println!("Holdings as of {}", Date::today());
+            println!("{}", account.holdings_at(&Date::today()).describe(self.jurisdiction.as_ref()));
+        }
+    }
 
+    /// Loads a price file and revalues the User's accounts at today's prices
+    fn load_prices(&mut self) {
+        match PriceTable::load(&request_path("Path of the price file (CSV of date, ticker, close)?").unwrap_or_default()) {
+            Ok(table) => self.prices = table,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        }
+        let today = Date::today();
+        for account in &mut self.accounts {
+            let (holdings, warnings) = prices::value_account(account, &self.prices, &today);
+            for warning in warnings {
+                println!("Warning: {}", warning);
+            }
+            if let Err(why) = account.revalue(&holdings, today) {
+                println!("{}", why);
+            }
+        }
+        self.current_allocation();
+    }
+
+    /// Displays each of the User's accounts valued from the price file at a date
+    fn display_holdings_at_date(&self) {
+        if self.prices.is_empty() {
+            println!("Load a price file first");
+            return;
+        }
+        println!("What date should holdings be valued at (YYYY-MM-DD)?");
+        let mut date = String::new();
+        io::stdin().read_line(&mut date)
+            .expect("Failed to read line");
+        let date = match Date::parse(&date) {
+            Ok(date) => date,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        for account in &self.accounts {
+            let (holdings, warnings) = prices::value_account(account, &self.prices, &date);
+            println!("{}", holdings.describe(self.jurisdiction.as_ref()));
+            for warning in warnings {
+                println!("Warning: {}", warning);
+            }
         }
     }
 
@@ -96,12 +872,24 @@ impl User {
             io::stdin().read_line(&mut choice)
                 .expect("Failed to read line");
             let choice: u8 = choice.trim().parse().unwrap_or(0);
-            let mut diff = self.allocation.diff(&self.target); 
+            let target = self.effective_target();
+            let diff = self.allocation.diff(&target); 
             println!("The user's accounts differ from the target allocation by: ");
             println!("(+) too high, need to take some out, (-) too low, need to add some");
             match choice {
-                1 => { diff.change_account_classification(AccountType::Taxable);
-                    println!("{}", diff.multiply(0.01*self.get_total_value())); 
+                1 => {
+                    match &self.tax_adjustment {
+                        Some(adjustment) => {
+                            println!("In account dollars, to trade:\n{}",
+                                     aftertax::pre_tax_drift(&self.accounts, &target, adjustment,
+                                                             self.jurisdiction.as_ref()));
+                            println!("In after-tax dollars:\n{}", rebalance::drift(&self.counted_holdings(), &target));
+                        }
+                        None => println!("{}", rebalance::drift(&self.counted_holdings(), &target)),
+                    }
+                    if target.is_full_allocation() {
+                        self.display_rebalancing_tax(&target);
+                    }
                     break; },
                 2 => { 
                     println!("{}", diff); 
@@ -111,6 +899,219 @@ impl User {
         }
     }
 
+    /// Shows the gains rebalancing to the target would realize in taxable accounts and the tax owed
+    /// on them on top of the User's income
+    fn display_rebalancing_tax(&self, target: &Account) {
+        let today = Date::today();
+        let (gains, warnings) = tax::realized_by_rebalancing(&self.accounts, target, &self.prices, &today,
+                                                             self.jurisdiction.as_ref());
+        for warning in warnings {
+            println!("Warning: {}", warning);
+        }
+        if gains.qualified + gains.ordinary < rebalance::MINIMUM_TRADE {
+            return;
+        }
+        let year = self.jurisdiction.tax_year(&today);
+        let cost = self.taxes.for_year(year)
+            .incremental(&Income::ordinary(self.income.unwrap_or(0.0)), &gains)
+            .total();
+        println!("Rebalancing would realize {} of short-term and {} of long-term gains, costing about {} in tax",
+                 gains.ordinary.separated_string_with_fixed_place(2), gains.qualified.separated_string_with_fixed_place(2),
+                 cost.separated_string_with_fixed_place(2));
+    }
+
+    /// Simulates the user's target allocation under each rebalancing policy using a file of
+    /// monthly asset class returns
+    fn backtest(&self) {
+        let target = self.effective_target();
+        if !target.is_full_allocation() {
+            println!("Set a target allocation first");
+            return;
+        }
+        let path = request_path("Path of the return file (CSV of date followed by a column of monthly returns per asset class)?");
+        let history = match ReturnHistory::load(&path.unwrap_or_default()) {
+            Ok(history) => history,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        println!("How much is contributed each month? Enter a negative amount for withdrawals");
+        let mut contribution = String::new();
+        io::stdin().read_line(&mut contribution)
+            .expect("Failed to read line");
+        let contribution: f64 = contribution.trim().parse().unwrap_or(0.0);
+        let start = if self.get_total_value() > 0.0 { self.get_total_value() } else { 10_000.0 };
+        println!("{}", backtest::report(&history, &target, start, contribution));
+    }
+
+    /// Ask user of the program for a return file and risk-free rate used to show the risk of the
+    /// current and target allocations with the user info
+    fn load_return_history(&mut self) {
+        let path = request_path("Path of the return file (CSV of date followed by a column of returns per asset class)?");
+        let history = match ReturnHistory::load(&path.unwrap_or_default()) {
+            Ok(history) => history,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        println!("Annual risk-free rate in percent? Leave blank for {}%", 100.0 * risk::DEFAULT_RISK_FREE_RATE);
+        let mut rate = String::new();
+        io::stdin().read_line(&mut rate)
+            .expect("Failed to read line");
+        self.risk_free_rate = match rate.trim() {
+            "" => risk::DEFAULT_RISK_FREE_RATE,
+            rate => match rate.parse::<f64>() {
+                Ok(rate) => rate / 100.0,
+                Err(_) => {
+                    println!("Risk-free rate must be a number");
+                    return;
+                }
+            },
+        };
+        println!("{}", risk::report(&history, &self.get_holdings(), &self.effective_target(), self.risk_free_rate));
+        self.returns = Some(history);
+    }
+
+    /// Ask user of the program for a scenario, from a file or typed in, and show what it would do
+    /// to the user's accounts
+    fn stress_test(&self) {
+        if self.get_total_value() <= 0.0 {
+            println!("Add an account first");
+            return;
+        }
+        let path = request_path("Path of the scenario file (CSV of scenario name followed by a shock per asset class)? Leave blank for standard scenarios");
+        let mut scenarios = match path.map(|path| stress::load(&path)) {
+            None => stress::standard(),
+            Some(Ok(scenarios)) => scenarios,
+            Some(Err(why)) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        let scenario = loop {
+            for (i, scenario) in scenarios.iter().enumerate() {
+                print!("{}. {}\t", i + 1, scenario.name);
+            }
+            println!("{}. Custom\t{}. Cancel", scenarios.len() + 1, scenarios.len() + 2);
+            let mut choice = String::new();
+            io::stdin().read_line(&mut choice)
+                .expect("Failed to read line");
+            let choice: usize = choice.trim().parse().unwrap_or(0);
+            if (1..=scenarios.len()).contains(&choice) {
+                break scenarios.swap_remove(choice - 1);
+            } else if choice == scenarios.len() + 1 {
+                break request_custom_scenario();
+            } else if choice == scenarios.len() + 2 {
+                return;
+            }
+        };
+        println!("{}", stress::report(&scenario, &self.accounts, &self.effective_target()));
+    }
+
+    /// Ask user of the program for a year's savings and the order to fill accounts in, then show
+    /// how much goes to each account and what to buy there
+    fn plan_contributions(&mut self) {
+        println!("How much will you save this year?");
+        let mut savings = String::new();
+        io::stdin().read_line(&mut savings)
+            .expect("Failed to read line");
+        let savings: f64 = match savings.trim().parse() {
+            Ok(savings) if savings > 0.0 => savings,
+            _ => {
+                println!("Savings must be a positive number");
+                return;
+            }
+        };
+        let path = request_path("Path of the waterfall file (a line per step of account type, optionally followed by the most to put in)? Leave blank for the usual order");
+        let waterfall = match path.map(|path| Waterfall::load(&path, self.jurisdiction.as_ref())) {
+            None => match request_employer_match(self.jurisdiction.as_ref()) {
+                Ok(employer_match) => self.jurisdiction.waterfall(employer_match),
+                Err(why) => {
+                    println!("{}", why);
+                    return;
+                }
+            },
+            Some(Ok(waterfall)) => waterfall,
+            Some(Err(why)) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        match self.income {
+            Some(income) => println!("Income for limit phase-outs? Leave blank for {}", income),
+            None => println!("Income for limit phase-outs? Leave blank to ignore phase-outs"),
+        }
+        let mut income = String::new();
+        io::stdin().read_line(&mut income)
+            .expect("Failed to read line");
+        if !income.trim().is_empty() {
+            match income.trim().parse::<f64>() {
+                Ok(income) => self.income = Some(income),
+                Err(_) => {
+                    println!("Income must be a number");
+                    return;
+                }
+            }
+        }
+        let year = self.jurisdiction.tax_year(&Date::today());
+        let contributor = Contributor { year, age: self.age_at_end_of(year), income: self.income };
+        let plan = waterfall::plan(&self.accounts, &waterfall, &self.limits, &contributor, savings,
+                                   &self.effective_target(), self.jurisdiction.as_ref());
+        println!("{}", waterfall::report(&plan, &self.accounts));
+    }
+
+    /// Projects the user's holdings forward with random returns, rebalancing annually to the
+    /// target allocation
+    fn project(&self) {
+        let target = self.effective_target();
+        if !target.is_full_allocation() || self.get_total_value() <= 0.0 {
+            println!("Set a target allocation and add an account first");
+            return;
+        }
+        println!("How many years should be projected?");
+        let mut years = String::new();
+        io::stdin().read_line(&mut years)
+            .expect("Failed to read line");
+        let years: usize = years.trim().parse().unwrap_or(0);
+        println!("How much is contributed each year? Enter a negative amount for withdrawals");
+        let mut cash_flow = String::new();
+        io::stdin().read_line(&mut cash_flow)
+            .expect("Failed to read line");
+        let cash_flow: f64 = cash_flow.trim().parse().unwrap_or(0.0);
+        let assumptions = match request_assumptions() {
+            Ok(assumptions) => assumptions,
+            Err(why) => {
+                println!("{}", why);
+                return;
+            }
+        };
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|t| t.as_nanos() as u64).unwrap_or(0);
+        let projection = Projection {
+            years,
+            annual_cash_flow: cash_flow,
+            policy: Policy::Calendar(12),
+            simulations: 1000,
+            seed,
+        };
+        match montecarlo::project(&self.get_holdings(), &target, &assumptions, &projection) {
+            Ok(result) => println!("{}", montecarlo::report(&result, &projection)),
+            Err(why) => println!("{}", why),
+        }
+    }
+
+    /// Returns the combined holdings of all of the user's accounts
+    fn get_holdings(&self) -> Account {
+        let mut holdings = Account::new(AccountType::Taxable);
+        for account in &self.accounts {
+            for asset in &account.assets {
+                holdings.add_asset(Asset::new(asset.class.clone(), asset.value));
+            }
+        }
+        holdings
+    }
+
     /// Returns the total combined value of all of the user's accounts 
     fn get_total_value(&self) -> f64 {
         let mut total = 0.0;
@@ -122,38 +1123,27 @@ impl User {
 
     /// Display the account allocation of the user
     fn display_account_allocation(&self) -> String {
-        let mut tax = 0.0;
-        let mut trad = 0.0;
-        let mut roth = 0.0;
-        for i in &self.accounts {
-            match i.classification {
-                AccountType::Taxable => tax = tax + i.get_total_value(),
-                AccountType::Traditional => trad = trad + i.get_total_value(),
-                AccountType::Roth => roth += i.get_total_value(),
-                _ => continue,
-            }
-        }
         let mut disp = String::new();
-        disp.push_str(&format!("Taxable:        ${:>15}{:>8.2} %\n", tax.separated_string_with_fixed_place(2),  100.0*tax/self.get_total_value()));
-        disp.push_str(&format!("Traditional:    ${:>15}{:>8.2} %\n", trad.separated_string_with_fixed_place(2), 100.0*trad/self.get_total_value()));
-        disp.push_str(&format!("Roth:           ${:>15}{:>8.2} %\n", roth.separated_string_with_fixed_place(2), 100.0*roth/self.get_total_value()));
+        for bucket in &["Taxable", "Tax-deferred", "Tax-free"] {
+            let value: f64 = self.accounts.iter()
+                .filter(|a| self.jurisdiction.tax_treatment(&a.classification).bucket() == *bucket)
+                .map(|a| a.get_total_value())
+                .sum();
+            disp.push_str(&format!("{:<16}${:>15}{:>8.2} %\n", format!("{}:", bucket),
+                                   value.separated_string_with_fixed_place(2), 100.0*value/self.get_total_value()));
+        }
         disp
     }
 
-    fn get_asset_value(&self, class: &AssetClass) -> f64 {
-        let mut value = 0.0;
-        for account in &self.accounts {
-            for asset in &account.assets {
-                if asset == class {
-                    value += asset.value;
-                }
-            }
-        }
-        return value;
+    /// Returns the combined holdings as counted for the allocation: at face value, or after tax
+    /// when the User has turned on tax-adjusted mode
+    fn counted_holdings(&self) -> Account {
+        aftertax::combined(&self.accounts, self.tax_adjustment.as_ref(), self.jurisdiction.as_ref())
     }
 
     fn get_asset_share(&self, class: &AssetClass) -> f64 {
-        return 100.0*self.get_asset_value(class) / self.get_total_value();
+        let holdings = self.counted_holdings();
+        100.0*holdings.get_asset_value(class.clone()) / holdings.get_total_value()
     }
 
     fn current_allocation(&mut self) {
@@ -175,5 +1165,168 @@ impl User {
         //cur.add_asset(cds);
         cur.add_asset(rle);
         self.allocation = cur;
+        self.record_snapshot();
+    }
+
+    /// Saves the state of the user's portfolio as of today to their history
+    fn record_snapshot(&mut self) {
+        let snapshot = Snapshot::new(Date::today(), &self.accounts, &self.allocation, &self.effective_target());
+        snapshot::add_to_history(&mut self.history, snapshot);
+        if let Some(path) = &self.history_path {
+            if let Err(why) = snapshot::save(&self.history, path) {
+                println!("Warning: {}", why);
+            }
+        }
+        if let Some(path) = &self.accounts_path {
+            if let Err(why) = snapshot::save_accounts(&self.accounts, path) {
+                println!("Warning: {}", why);
+            }
+        }
+    }
+}
+
+/// Ask user of the program for a file of market assumptions, using the standard assumptions when
+/// none is given
+fn request_assumptions() -> Result<MarketAssumptions, String> {
+    match request_path("Path of the market assumptions file (CSV of class, return, volatility and correlations)? Leave blank for standard assumptions") {
+        Some(path) => MarketAssumptions::load(&path),
+        None => Ok(MarketAssumptions::standard()),
+    }
+}
+
+/// Ask user of the program for the path of a file, returning None when it is left blank
+fn request_path(question: &str) -> Option<String> {
+    println!("{}", question);
+    let mut path = String::new();
+    io::stdin().read_line(&mut path)
+        .expect("Failed to read line");
+    match path.trim() {
+        "" => None,
+        path => Some(path.to_string()),
+    }
+}
+
+/// Ask user of the program for a rate in percent, returned as a fraction
+fn request_rate(question: &str) -> Result<f64, String> {
+    println!("{}", question);
+    let mut rate = String::new();
+    io::stdin().read_line(&mut rate)
+        .expect("Failed to read line");
+    match rate.trim().parse::<f64>() {
+        Ok(rate) if (0.0..=100.0).contains(&rate) => Ok(rate / 100.0),
+        _ => Err("Rate must be a percentage from 0 to 100".to_string()),
+    }
+}
+
+/// Ask user of the program how much of a year's savings the employer matches in its plan. Only
+/// asked where the jurisdiction has 401(k) accounts; elsewhere nothing is matched.
+fn request_employer_match(jurisdiction: &dyn Jurisdiction) -> Result<f64, String> {
+    if !jurisdiction.account_types().contains(&AccountType::Plan401k) {
+        return Ok(0.0);
+    }
+    println!("How much can you put in your {} before the employer stops matching? Leave blank for no match",
+             jurisdiction.account_name(&AccountType::Plan401k));
+    let mut amount = String::new();
+    io::stdin().read_line(&mut amount)
+        .expect("Failed to read line");
+    if amount.trim().is_empty() {
+        return Ok(0.0);
+    }
+    match amount.trim().parse::<f64>() {
+        Ok(amount) if amount >= 0.0 => Ok(amount),
+        _ => Err("Match must be a positive amount".to_string()),
+    }
+}
+
+/// Ask user of the program for the User's birth date
+fn request_birth_date() -> Result<Date, String> {
+    println!("What is your birth date (YYYY-MM-DD)?");
+    let mut date = String::new();
+    io::stdin().read_line(&mut date)
+        .expect("Failed to read line");
+    Date::parse(&date)
+}
+
+/// Ask user of the program for market assumptions and constraints, show the efficient frontier
+/// and propose the allocation on it that matches a chosen volatility or return
+fn request_optimized_allocation() -> Result<Account, String> {
+    let assumptions = request_assumptions()?;
+    let constraints = match request_path("Path of the constraints file (lines of class,min,max in percent)? Leave blank for no constraints") {
+        Some(path) => Constraints::load(&path)?,
+        None => Constraints::unconstrained(),
+    };
+    let frontier = optimizer::efficient_frontier(&assumptions, &constraints);
+    if frontier.is_empty() {
+        return Err("No allocation satisfies the constraints".to_string());
+    }
+    println!("Efficient frontier\n{}", optimizer::frontier_report(&frontier, &assumptions, 10));
+    let by_volatility = loop {
+        println!("Choose by (1) volatility or (2) return?");
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        match choice.trim().parse().unwrap_or(0) {
+            1 => break true,
+            2 => break false,
+            _ => continue,
+        }
+    };
+    println!("Target percent?");
+    let mut level = String::new();
+    io::stdin().read_line(&mut level)
+        .expect("Failed to read line");
+    let level: f64 = level.trim().parse::<f64>().map_err(|_| "Target must be a number".to_string())? / 100.0;
+    let portfolio = if by_volatility {
+        optimizer::for_volatility(frontier, level)
+    } else {
+        optimizer::for_return(frontier, level)
+    };
+    match portfolio {
+        Some(portfolio) => {
+            println!("Expected return {:.2}%, volatility {:.2}%", 100.0 * portfolio.expected_return,
+                     100.0 * portfolio.volatility);
+            questionnaire::review_proposal(portfolio.allocation(&assumptions))
+        }
+        None => Err("No allocation on the frontier reaches that level".to_string()),
+    }
+}
+
+/// Ask user of the program for a price history and build a target that spreads risk evenly, showing
+/// how much of the risk each asset class carries now and under the proposal
+fn request_risk_based_allocation(holdings: &Account) -> Result<Account, String> {
+    let path = request_path("Path of the price history file (date followed by a price column per asset class)?");
+    let model = RiskModel::from_history(&ReturnHistory::load_prices(&path.unwrap_or_default())?)?;
+    let method = loop {
+        println!("1. Risk parity (equal risk contribution)\t2. Inverse volatility");
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)
+            .expect("Failed to read line");
+        match choice.trim().parse().unwrap_or(0) {
+            1 => break Method::RiskParity,
+            2 => break Method::InverseVolatility,
+            _ => continue,
+        }
+    };
+    let proposal = model.allocation(&model.weights(method)?);
+    println!("Risk contributions\n{}", riskparity::report(&model, holdings, &proposal));
+    questionnaire::review_proposal(proposal)
+}
+
+/// Ask user of the program for the percent gained or lost by each asset class
+fn request_custom_scenario() -> Scenario {
+    let mut shocks = Vec::new();
+    for class in AssetClass::all() {
+        let shock = loop {
+            println!("Percent change in {}? (e.g. -30)", class);
+            let mut shock = String::new();
+            io::stdin().read_line(&mut shock)
+                .expect("Failed to read line");
+            match shock.trim().parse::<f64>() {
+                Ok(shock) if shock >= -100.0 => break shock / 100.0,
+                _ => println!("Please enter a number no lower than -100"),
+            }
+        };
+        shocks.push((class, shock));
     }
+    Scenario { name: "Custom".to_string(), shocks }
 }
@@ -0,0 +1,246 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fs;
+use crate::account::{Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::jurisdiction::Jurisdiction;
+use crate::limits::{ContributionLimits, Contributor};
+use crate::rebalance;
+
+/// One step of the waterfall: money goes to an account type up to its contribution limit, or up
+/// to a smaller cap such as the amount the employer matches
+pub struct Step {
+    pub account_type: AccountType,
+    pub cap: Option<f64>,
+}
+
+/// The order in which savings are spread across account types
+pub struct Waterfall {
+    pub steps: Vec<Step>,
+}
+
+impl Waterfall {
+    /// Puts money in a 401(k) up to the amount the employer matches, then fills an HSA, a Roth IRA
+    /// and the rest of the 401(k) up to their limits and puts the rest in a taxable account
+    pub fn standard(employer_match: f64) -> Waterfall {
+        let mut steps = vec![
+            Step { account_type: AccountType::Hsa, cap: None },
+            Step { account_type: AccountType::Roth, cap: None },
+            Step { account_type: AccountType::Plan401k, cap: None },
+            Step { account_type: AccountType::Taxable, cap: None },
+        ];
+        if employer_match > 0.0 {
+            steps.insert(0, Step { account_type: AccountType::Plan401k, cap: Some(employer_match) });
+        }
+        Waterfall { steps }
+    }
+
+    /// Loads a waterfall from a file
+    pub fn load(path: &str, jurisdiction: &dyn Jurisdiction) -> Result<Waterfall, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|why| format!("Could not read waterfall file {}: {}", path, why))?;
+        Waterfall::parse(&text, jurisdiction)
+    }
+
+    /// Parses lines of account type, optionally followed by the most to put in at that step.
+    /// Steps without an amount fill the account type up to its limit. Account types the
+    /// jurisdiction does not let take contributions are rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// // 401(k) up to a $6,000 match, then Roth IRA, then the rest of the 401(k), then taxable
+    /// let waterfall = Waterfall::parse("401k,6000\nroth\n401k\ntaxable\n", &UnitedStates).unwrap();
+    /// ```
+    pub fn parse(text: &str, jurisdiction: &dyn Jurisdiction) -> Result<Waterfall, String> {
+        let mut steps = Vec::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+            if fields.len() > 2 {
+                return Err(format!("Line {}: expected account[,amount] but found {}", number + 1, line));
+            }
+            let account_type = AccountType::parse(fields[0]).map_err(|why| format!("Line {}: {}", number + 1, why))?;
+            if !jurisdiction.tax_treatment(&account_type).accepts_contributions {
+                return Err(format!("Line {}: {} accounts do not accept contributions", number + 1,
+                                   jurisdiction.account_name(&account_type)));
+            }
+            let cap = match fields.get(1) {
+                None => None,
+                Some(amount) if amount.eq_ignore_ascii_case("limit") => None,
+                Some(amount) => match amount.parse::<f64>() {
+                    Ok(amount) if amount >= 0.0 => Some(amount),
+                    _ => return Err(format!("Line {}: invalid amount {}", number + 1, amount)),
+                },
+            };
+            steps.push(Step { account_type, cap });
+        }
+        if steps.is_empty() {
+            return Err("Waterfall has no steps".to_string());
+        }
+        Ok(Waterfall { steps })
+    }
+}
+
+/// Money planned for one of the user's accounts and the asset classes to buy with it
+pub struct Allotment {
+    pub account: usize,
+    pub amount: f64,
+    pub trades: Account,
+}
+
+/// Where a year's savings would go
+pub struct Plan {
+    pub allotments: Vec<Allotment>,
+    /// Savings that no step could take
+    pub unplaced: f64,
+    pub notes: Vec<String>,
+}
+
+/// Spreads savings down the waterfall into the first of the user's accounts of each step's type,
+/// keeping every account within the room left under its limit for the tax year. The money is
+/// then split across asset classes to move the combined holdings toward the target.
+pub fn plan(accounts: &[Account], waterfall: &Waterfall, limits: &ContributionLimits, contributor: &Contributor,
+            savings: f64, target: &Account, jurisdiction: &dyn Jurisdiction) -> Plan {
+    let year = contributor.year;
+    let mut remaining = savings;
+    let mut planned: Vec<(usize, f64)> = Vec::new();
+    let mut notes = Vec::new();
+    for step in &waterfall.steps {
+        if remaining < rebalance::MINIMUM_TRADE {
+            break;
+        }
+        let index = match accounts.iter().position(|a| a.classification == step.account_type) {
+            Some(index) => index,
+            None => {
+                notes.push(format!("Skipped {}: no account of that type", jurisdiction.account_name(&step.account_type)));
+                continue;
+            }
+        };
+        let room = limits.for_account(&step.account_type, year).into_iter()
+            .map(|limit| {
+                let already: f64 = planned.iter()
+                    .filter(|(i, _)| limit.types.contains(&accounts[*i].classification))
+                    .map(|(_, amount)| amount)
+                    .sum();
+                (limit.room(accounts, year, contributor.age, contributor.income) - already).max(0.0)
+            })
+            .fold(f64::INFINITY, f64::min);
+        let amount = remaining.min(room).min(step.cap.unwrap_or(f64::INFINITY));
+        if amount < rebalance::MINIMUM_TRADE {
+            notes.push(format!("Skipped {}: no room left under its {} limit", jurisdiction.account_name(&step.account_type),
+                               year));
+            continue;
+        }
+        match planned.iter_mut().find(|(i, _)| *i == index) {
+            Some((_, total)) => *total += amount,
+            None => planned.push((index, amount)),
+        }
+        remaining -= amount;
+    }
+    if remaining >= rebalance::MINIMUM_TRADE {
+        notes.push(format!("{} could not be placed without going over a limit",
+                           remaining.separated_string_with_fixed_place(2)));
+    }
+
+    // Split the money across asset classes for the portfolio as a whole, then give every account
+    // the same mix
+    let mut holdings = Account::new(AccountType::Taxable);
+    for account in accounts {
+        rebalance::apply(&mut holdings, account);
+    }
+    let placed = savings - remaining;
+    let buys = if target.is_full_allocation() {
+        rebalance::contribution_trades(&holdings, target, placed)
+    } else {
+        notes.push("Set a target allocation to see what to buy".to_string());
+        Account::new(AccountType::Taxable)
+    };
+    let allotments = planned.into_iter()
+        .map(|(account, amount)| {
+            let mut trades = Account::new(accounts[account].classification.clone());
+            for class in AssetClass::all() {
+                let share = if placed > 0.0 { buys.get_asset_value(class.clone()) / placed } else { 0.0 };
+                trades.add_asset(Asset::new(class, amount * share));
+            }
+            Allotment { account, amount, trades }
+        })
+        .collect();
+    Plan { allotments, unplaced: remaining, notes }
+}
+
+/// Lays out how much goes to each account and what to buy there
+pub fn report(plan: &Plan, accounts: &[Account]) -> String {
+    let mut disp = format!("{:<28}{:>14}", "Account", "Contribution");
+    for class in AssetClass::all() {
+        disp.push_str(&format!("{:>15}", class));
+    }
+    disp.push('\n');
+    for allotment in &plan.allotments {
+        disp.push_str(&format!("{:<28}{:>14}",
+                               accounts[allotment.account].name,
+                               allotment.amount.separated_string_with_fixed_place(2)));
+        for class in AssetClass::all() {
+            disp.push_str(&format!("{:>15}", allotment.trades.get_asset_value(class).separated_string_with_fixed_place(2)));
+        }
+        disp.push('\n');
+    }
+    if plan.unplaced >= rebalance::MINIMUM_TRADE {
+        disp.push_str(&format!("{:<28}{:>14}\n", "Not placed", plan.unplaced.separated_string_with_fixed_place(2)));
+    }
+    for note in &plan.notes {
+        disp.push_str(&format!("{}\n", note));
+    }
+    disp
+}
+
+#[test]
+fn waterfall_fills_steps_within_limits() {
+    use crate::jurisdiction::UnitedStates;
+    let accounts = vec![Account::new(AccountType::Plan401k), Account::new(AccountType::Roth),
+                        Account::new(AccountType::Taxable)];
+    let waterfall = Waterfall::parse("401k,6000\nroth\n401k\ntaxable\n", &UnitedStates).unwrap();
+    assert!(Waterfall::parse("inherited ira\n", &UnitedStates).is_err());
+    let limits = ContributionLimits::standard();
+    let target = Account::new(AccountType::Allocation);
+    let contributor = Contributor { year: 2024, age: Some(40), income: Some(100_000.0) };
+    let plan = plan(&accounts, &waterfall, &limits, &contributor, 40_000.0, &target, &UnitedStates);
+    let amounts: Vec<(usize, f64)> = plan.allotments.iter().map(|a| (a.account, a.amount)).collect();
+    assert_eq!(vec![(0, 23_000.0), (1, 7_000.0), (2, 10_000.0)], amounts);
+    assert_eq!(0.0, plan.unplaced);
+}
+
+#[test]
+fn waterfall_respects_phase_outs_and_buys_toward_target() {
+    use crate::jurisdiction::UnitedStates;
+    let mut roth = Account::new(AccountType::Roth);
+    roth.add_asset(Asset::new(AssetClass::Domestic, 1000.0));
+    let accounts = vec![roth];
+    let mut target = Account::new(AccountType::Allocation);
+    target.add_asset(Asset::new(AssetClass::Domestic, 50.0));
+    target.add_asset(Asset::new(AssetClass::Bond, 50.0));
+    let limits = ContributionLimits::standard();
+    let high_earner = Contributor { year: 2024, age: Some(40), income: Some(200_000.0) };
+    let result = plan(&accounts, &Waterfall::standard(0.0), &limits, &high_earner, 5_000.0, &target, &UnitedStates);
+    assert!(result.allotments.is_empty());
+    assert_eq!(5_000.0, result.unplaced);
+
+    let contributor = Contributor { year: 2024, age: Some(40), income: Some(100_000.0) };
+    let result = plan(&accounts, &Waterfall::standard(0.0), &limits, &contributor, 1_000.0, &target, &UnitedStates);
+    assert_eq!(1000.0, result.allotments[0].trades.get_asset_value(AssetClass::Bond));
+}
+
+#[test]
+fn standard_waterfall_takes_the_match_before_other_accounts() {
+    use crate::jurisdiction::UnitedStates;
+    let accounts = vec![Account::new(AccountType::Plan401k), Account::new(AccountType::Hsa),
+                        Account::new(AccountType::Roth), Account::new(AccountType::Taxable)];
+    let limits = ContributionLimits::standard();
+    let target = Account::new(AccountType::Allocation);
+    let contributor = Contributor { year: 2024, age: Some(40), income: Some(100_000.0) };
+    let plan = plan(&accounts, &Waterfall::standard(3_000.0), &limits, &contributor, 40_000.0, &target,
+                    &UnitedStates);
+    let amounts: Vec<(usize, f64)> = plan.allotments.iter().map(|a| (a.account, a.amount)).collect();
+    assert_eq!(vec![(0, 23_000.0), (1, 4_150.0), (2, 7_000.0), (3, 5_850.0)], amounts);
+    assert!(Waterfall::standard(3_000.0).steps[0].account_type == AccountType::Plan401k);
+    assert_eq!(Some(3_000.0), Waterfall::standard(3_000.0).steps[0].cap);
+}
@@ -0,0 +1,310 @@
+extern crate separator;
+use separator::FixedPlaceSeparatable;
+use std::fmt;
+use crate::account::{Access, Account, AccountType};
+use crate::asset::{Asset, AssetClass};
+use crate::date::Date;
+use crate::glidepath::GlidePath;
+use crate::jurisdiction::Jurisdiction;
+use crate::rebalance;
+use crate::returns;
+use crate::rmd::{self, LifeTables};
+use crate::tax::{Income, TaxTable};
+
+/// Payments the tax is worked out to within
+const TAX_TOLERANCE: f64 = 0.01;
+const MAX_ITERATIONS: usize = 100;
+
+/// The order accounts are drawn down in
+#[derive(Clone, Copy, PartialEq)]
+pub enum Order {
+    /// Taxable accounts first, then tax-deferred, then tax-free
+    TaxableFirst,
+    /// Every account in proportion to its balance
+    Proportional,
+    /// Tax-deferred accounts up to the top of a bracket, then taxable, tax-deferred and tax-free
+    BracketFilling,
+}
+
+impl Order {
+    pub fn all() -> Vec<Order> {
+        vec![Order::TaxableFirst, Order::Proportional, Order::BracketFilling]
+    }
+}
+
+impl fmt::Display for Order {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let disp = match self {
+            Order::TaxableFirst => "Taxable first",
+            Order::Proportional => "Proportional",
+            Order::BracketFilling => "Bracket filling",
+        };
+        write!(f, "{}", disp)
+    }
+}
+
+/// How an account's withdrawals are taxed
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Taxable,
+    Deferred,
+    Free,
+}
+
+fn kind(account_type: &AccountType, jurisdiction: &dyn Jurisdiction) -> Kind {
+    let treatment = jurisdiction.tax_treatment(account_type);
+    if treatment.taxable_growth {
+        Kind::Taxable
+    } else if treatment.taxed_withdrawals {
+        Kind::Deferred
+    } else {
+        Kind::Free
+    }
+}
+
+/// Accounts that can only be spent on qualified expenses are left alone
+pub fn spendable(account_type: &AccountType, jurisdiction: &dyn Jurisdiction) -> bool {
+    !matches!(jurisdiction.tax_treatment(account_type).access, Access::QualifiedExpenses(_))
+}
+
+/// What retirement looks like. Amounts are in today's dollars and grow with inflation, as do the
+/// tax brackets and deduction. Withdrawals from tax-deferred accounts are ordinary income and
+/// gains realized in taxable accounts are long-term.
+pub struct Decumulation {
+    pub first_year: i32,
+    pub years: u32,
+    /// Spent each year after tax
+    pub spending: f64,
+    /// Taxable income from outside the accounts, such as a pension or Social Security
+    pub other_income: f64,
+    pub inflation: f64,
+    pub table: TaxTable,
+    /// Bracket filling draws tax-deferred accounts up to the top of the bracket taxed at this rate
+    pub fill_rate: f64,
+    /// Yearly return expected from each asset class
+    pub returns: Vec<(AssetClass, f64)>,
+    /// Used for required minimum distributions, which are taken first whatever the order
+    pub birth_year: Option<i32>,
+}
+
+/// How one withdrawal order played out
+pub struct Outcome {
+    pub order: Order,
+    pub taxes: f64,
+    /// Ending balance of each account type
+    pub ending: Vec<(AccountType, f64)>,
+    /// First year spending could not be met
+    pub depleted: Option<i32>,
+}
+
+/// Splits the cash needed across the accounts in the order, on top of the amounts already forced
+/// out of them. Returns what to take from each account.
+fn allocate(order: Order, balances: &[f64], kinds: &[Kind], forced: &[f64], need: f64, deferred_room: f64) -> Vec<f64> {
+    let mut amounts = forced.to_vec();
+    let mut remaining = need - forced.iter().sum::<f64>();
+    let take = |amounts: &mut Vec<f64>, remaining: &mut f64, kind: Kind, limit: f64| {
+        let mut limit = limit;
+        for i in (0..amounts.len()).filter(|i| kinds[*i] == kind) {
+            let amount = (balances[i] - amounts[i]).min(*remaining).min(limit).max(0.0);
+            amounts[i] += amount;
+            *remaining -= amount;
+            limit -= amount;
+        }
+    };
+    if remaining <= 0.0 {
+        return amounts;
+    }
+    match order {
+        Order::Proportional => {
+            let available: Vec<f64> = balances.iter().zip(&amounts).map(|(b, a)| (b - a).max(0.0)).collect();
+            let total: f64 = available.iter().sum();
+            let share = if total > 0.0 { (remaining / total).min(1.0) } else { 0.0 };
+            for (amount, available) in amounts.iter_mut().zip(available) {
+                *amount += available * share;
+            }
+        }
+        Order::TaxableFirst | Order::BracketFilling => {
+            if order == Order::BracketFilling {
+                take(&mut amounts, &mut remaining, Kind::Deferred, deferred_room);
+            }
+            for kind in &[Kind::Taxable, Kind::Deferred, Kind::Free] {
+                take(&mut amounts, &mut remaining, *kind, f64::INFINITY);
+            }
+        }
+    }
+    amounts
+}
+
+/// Simulates drawing the accounts down in each year of retirement: required distributions are
+/// taken first, then enough to cover spending and the tax on the withdrawals themselves. What is
+/// left grows at the expected returns and every account is rebalanced to the year's target.
+/// Distributions beyond what is needed are reinvested in a taxable account.
+pub fn simulate(accounts: &[Account], decumulation: &Decumulation, targets: &[Account], order: Order,
+                jurisdiction: &dyn Jurisdiction) -> Outcome {
+    let spent: Vec<&Account> = accounts.iter().filter(|a| spendable(&a.classification, jurisdiction)).collect();
+    // Taxable accounts without a ledger are taken to have no gains
+    let mut basis: Vec<f64> = spent.iter()
+        .map(|a| if kind(&a.classification, jurisdiction) != Kind::Taxable {
+            0.0
+        } else {
+            a.cost_basis().map_or_else(|| a.get_total_value(), |basis| basis.get_total_value())
+        })
+        .collect();
+    let mut accounts: Vec<Account> = spent.iter().map(|a| a.snapshot()).collect();
+    if !accounts.iter().any(|a| kind(&a.classification, jurisdiction) == Kind::Taxable) {
+        accounts.push(Account::new(AccountType::Taxable));
+        basis.push(0.0);
+    }
+    let kinds: Vec<Kind> = accounts.iter().map(|a| kind(&a.classification, jurisdiction)).collect();
+    let tables = LifeTables::standard();
+    let mut taxes = 0.0;
+    let mut depleted = None;
+    let no_target = Account::new(AccountType::Allocation);
+    for year in 0..decumulation.years {
+        let calendar_year = decumulation.first_year + year as i32;
+        let inflation = (1.0 + decumulation.inflation).powi(year as i32);
+        let target = targets.get(year as usize).or_else(|| targets.last());
+        let balances: Vec<f64> = accounts.iter().map(|a| a.get_total_value()).collect();
+        let mut forced = vec![0.0; accounts.len()];
+        if let Some(birth_year) = decumulation.birth_year {
+            let prior: Vec<Option<f64>> = balances.iter().map(|b| Some(*b)).collect();
+            for distribution in rmd::plan(&accounts, &prior, calendar_year, birth_year, &[], &tables,
+                                          target.unwrap_or(&no_target)) {
+                forced[distribution.account] = distribution.amount;
+            }
+        }
+        let other_income = decumulation.other_income * inflation;
+        let table = decumulation.table.indexed(inflation);
+        let forced_deferred: f64 = kinds.iter().zip(&forced).filter(|(k, _)| **k == Kind::Deferred).map(|(_, f)| f).sum();
+        let deferred_room = (table.top_of_bracket(decumulation.fill_rate) - other_income - forced_deferred).max(0.0);
+        let tax_on = |amounts: &[f64]| {
+            let mut ordinary = other_income;
+            let mut gains = 0.0;
+            for (i, amount) in amounts.iter().enumerate() {
+                match kinds[i] {
+                    Kind::Deferred => ordinary += amount,
+                    Kind::Taxable if balances[i] > 0.0 => gains += amount * (1.0 - basis[i] / balances[i]).max(0.0),
+                    _ => (),
+                }
+            }
+            table.tax(&Income { ordinary, qualified: gains, investment: 0.0 }).total()
+        };
+        let spending = decumulation.spending * inflation;
+        let mut tax = tax_on(&forced);
+        let mut amounts = forced.clone();
+        for _ in 0..MAX_ITERATIONS {
+            amounts = allocate(order, &balances, &kinds, &forced, (spending + tax - other_income).max(0.0), deferred_room);
+            let next = tax_on(&amounts);
+            let settled = (next - tax).abs() < TAX_TOLERANCE;
+            tax = next;
+            if settled {
+                break;
+            }
+        }
+        let raised: f64 = amounts.iter().sum::<f64>() + other_income - tax;
+        if raised < spending - rebalance::MINIMUM_TRADE && depleted.is_none() {
+            depleted = Some(calendar_year);
+        }
+        taxes += tax;
+
+        for (i, account) in accounts.iter_mut().enumerate() {
+            if balances[i] > 0.0 {
+                let kept = 1.0 - (amounts[i] / balances[i]).min(1.0);
+                basis[i] *= kept;
+                *account = account.multiply(kept);
+            }
+        }
+        let excess = raised - spending;
+        if excess >= rebalance::MINIMUM_TRADE {
+            if let Some(i) = kinds.iter().position(|k| *k == Kind::Taxable) {
+                accounts[i].add_asset(Asset::new(AssetClass::Bond, excess));
+                basis[i] += excess;
+            }
+        }
+        for account in accounts.iter_mut() {
+            for asset in account.assets.iter_mut() {
+                asset.value *= 1.0 + returns::return_of(&decumulation.returns, &asset.class);
+            }
+            if let Some(target) = target.filter(|t| t.is_full_allocation()) {
+                let trades = rebalance::trades_to_target(account, target);
+                rebalance::apply(account, &trades);
+            }
+        }
+    }
+    let mut ending: Vec<(AccountType, f64)> = Vec::new();
+    for account in &accounts {
+        match ending.iter_mut().find(|(t, _)| *t == account.classification) {
+            Some((_, total)) => *total += account.get_total_value(),
+            None => ending.push((account.classification.clone(), account.get_total_value())),
+        }
+    }
+    Outcome { order, taxes, ending, depleted }
+}
+
+/// Returns the target for each year of retirement, from the glide path when there is one
+pub fn yearly_targets(first_year: i32, years: u32, target: &Account,
+                      glide_path: Option<&GlidePath>, birth_date: Option<&Date>) -> Vec<Account> {
+    (0..years)
+        .map(|year| {
+            let date = Date::new(first_year + year as i32, 1, 1).unwrap_or_else(|_| Date::today());
+            glide_path.and_then(|path| path.target_on(&date, birth_date)).unwrap_or_else(|| target.snapshot())
+        })
+        .collect()
+}
+
+/// Lays out each order's lifetime taxes, ending balances by account type and when it ran short
+pub fn report(outcomes: &[Outcome], jurisdiction: &dyn Jurisdiction) -> String {
+    let types: Vec<AccountType> = match outcomes.first() {
+        Some(outcome) => outcome.ending.iter().map(|(t, _)| t.clone()).collect(),
+        None => return String::new(),
+    };
+    let mut disp = format!("{:<18}{:>16}", "Order", "Lifetime tax");
+    for account_type in &types {
+        disp.push_str(&format!("{:>16}", jurisdiction.account_name(account_type)));
+    }
+    disp.push_str(&format!("{:>16}{:>12}\n", "Ending total", "Runs short"));
+    for outcome in outcomes {
+        disp.push_str(&format!("{:<18}{:>16}", outcome.order.to_string(), outcome.taxes.separated_string_with_fixed_place(2)));
+        for (_, balance) in &outcome.ending {
+            disp.push_str(&format!("{:>16}", balance.separated_string_with_fixed_place(2)));
+        }
+        let total: f64 = outcome.ending.iter().map(|(_, b)| b).sum();
+        let depleted = outcome.depleted.map(|y| y.to_string()).unwrap_or_else(|| "Never".to_string());
+        disp.push_str(&format!("{:>16}{:>12}\n", total.separated_string_with_fixed_place(2), depleted));
+    }
+    disp.push_str("Balances are in future dollars; ending tax-deferred balances still owe income tax\n");
+    disp
+}
+
+#[test]
+fn withdrawal_orders_draw_accounts_down_differently() {
+    use crate::jurisdiction::UnitedStates;
+    use crate::tax::Brackets;
+    let mut taxable = Account::new(AccountType::Taxable);
+    taxable.add_asset(Asset::new(AssetClass::Bond, 100_000.0));
+    let mut traditional = Account::new(AccountType::Traditional);
+    traditional.add_asset(Asset::new(AssetClass::Bond, 200_000.0));
+    let accounts = vec![taxable, traditional];
+    let decumulation = Decumulation {
+        first_year: 2030,
+        years: 10,
+        spending: 20_000.0,
+        other_income: 0.0,
+        inflation: 0.0,
+        table: TaxTable::new(2030, Brackets::new(vec![(0.0, 0.1), (20_000.0, 0.3)]).unwrap(), 0.0),
+        fill_rate: 0.1,
+        returns: Vec::new(),
+        birth_year: None,
+    };
+    // Filling the 10% bracket each year empties the traditional account at 2,000 of tax a year
+    let filling = simulate(&accounts, &decumulation, &[], Order::BracketFilling, &UnitedStates);
+    assert!((filling.taxes - 20_000.0).abs() < 0.1);
+    assert!((filling.ending[0].1 - 80_000.0).abs() < 0.1);
+    assert!(filling.ending[1].1.abs() < 0.1);
+    assert_eq!(None, filling.depleted);
+    // Spending taxable money first pushes later withdrawals into the 30% bracket
+    let first = simulate(&accounts, &decumulation, &[], Order::TaxableFirst, &UnitedStates);
+    let withdrawal = 20_000.0 + 2_000.0 / 0.7;
+    assert!((first.taxes - 5.0 * (withdrawal - 20_000.0)).abs() < 0.1);
+    assert!((first.ending[1].1 - (200_000.0 - 5.0 * withdrawal)).abs() < 0.1);
+}